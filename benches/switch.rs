@@ -0,0 +1,74 @@
+//! End-to-end cost of `git-wt switch` in a repository with many worktrees.
+//!
+//! The exact-match case should stay at a single `git worktree list`; compare
+//! runs with `cargo bench --bench switch`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+const WORKTREES: usize = 50;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args([
+            "-c",
+            "user.name=bench",
+            "-c",
+            "user.email=bench@example.com",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .expect("failed to run git");
+    assert!(status.success(), "git {} failed", args.join(" "));
+}
+
+/// Builds a `.bare` layout with `main` plus [`WORKTREES`] feature worktrees.
+fn setup() -> PathBuf {
+    let root = env::temp_dir().join(format!("git-wt-bench-{}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    git(&root, &["init", "--bare", "--initial-branch=main", ".bare"]);
+    fs::write(root.join(".git"), "gitdir: ./.bare\n").unwrap();
+    git(&root, &["worktree", "add", "--orphan", "main", "main"]);
+    git(
+        &root.join("main"),
+        &["commit", "--allow-empty", "-m", "init"],
+    );
+    for i in 0..WORKTREES {
+        let branch = format!("feature/bench-{i}");
+        git(&root, &["worktree", "add", "-b", &branch, &branch, "main"]);
+    }
+
+    root
+}
+
+fn switch(c: &mut Criterion) {
+    let root = setup();
+    let worktree = root.join("main");
+    let target = format!("feature/bench-{}", WORKTREES / 2);
+
+    let run = |branch: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_git-wt"))
+            .args(["switch", branch])
+            .current_dir(&worktree)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "switch {branch} failed");
+    };
+
+    c.bench_function("switch exact match", |b| b.iter(|| run(&target)));
+    c.bench_function("switch by index", |b| b.iter(|| run("2")));
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, switch);
+criterion_main!(benches);
@@ -1,32 +1,85 @@
 
-# git-wt integration v0.1.0
+# git-wt integration v0.2.0
+git-wt() {
+    local directives exit_code line value
+    local cd_path=""
+    local title=""
+    local env_vars=()
+    local exec_commands=()
+
+    # Directives come through a file only this shell knows about, never
+    # stdout, which may hold anything a command run by git-wt printed. Their
+    # values escape backslashes and newlines, undone by printf %b
+    directives=$(mktemp -t git-wt.XXXXXX) || return 1
+    command git-wt --directive-file "$directives" "$@"
+    exit_code=$?
+    while IFS= read -r line; do
+        case "$line" in
+            CD:?*)
+                printf -v cd_path '%b' "${line#CD:}"
+                ;;
+            ENV:[A-Za-z_]*=*)
+                printf -v value '%b' "${line#ENV:}"
+                env_vars+=("$value")
+                ;;
+            TITLE:*)
+                printf -v title '%b' "${line#TITLE:}"
+                ;;
+            EXEC:?*)
+                printf -v value '%b' "${line#EXEC:}"
+                exec_commands+=("$value")
+                ;;
+        esac
+    done < "$directives"
+    rm -f "$directives"
+
+    # If we got a CD directive, change directory
+    if [ -n "$cd_path" ] && [ -d "$cd_path" ]; then
+        cd "$cd_path" || return 1
+
+        # Drop the variables of the worktree we left, then export the new ones
+        local name assignment
+        for name in "${_GIT_WT_ENV_NAMES[@]}"; do
+            unset "$name"
+        done
+        _GIT_WT_ENV_NAMES=()
+        for assignment in "${env_vars[@]}"; do
+            export "$assignment"
+            _GIT_WT_ENV_NAMES+=("${assignment%%=*}")
+        done
+    fi
+
+    if [ -n "$title" ] && [ -t 1 ]; then
+        printf '\033]0;%s\007' "$title"
+    fi
+
+    local exec_command
+    for exec_command in "${exec_commands[@]}"; do
+        eval "$exec_command"
+    done
+
+    return $exit_code
+}
+
 git() {
     if [ "$1" = "wt" ]; then
         shift
-        
-        local output
-        local cd_path=""
-        
-        # Capture only stdout, let stderr pass through (for interactive prompts)
-        while IFS= read -r line; do
-            if [[ "$line" =~ ^CD:(.+)$ ]]; then
-                # Extract path after "CD:"
-                cd_path="${BASH_REMATCH[1]}"
-            else
-                # Print normal output
-                echo "$line"
-            fi
-        done < <(command git-wt "$@")
-        
-        local exit_code=$?
-        
-        # If we got a CD directive, change directory
-        if [ -n "$cd_path" ] && [ -d "$cd_path" ]; then
-            cd "$cd_path" || return 1
-        fi
-        
-        return $exit_code
+        git-wt "$@"
     else
         command git "$@"
     fi
 }
+
+_git_wt_complete() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local kind=worktrees
+    if [ "$prev" = "--from" ]; then
+        kind=bases
+    fi
+    COMPREPLY=($(compgen -W "$(command git-wt __complete "$kind" 2>/dev/null)" -- "$cur"))
+}
+
+if [ -n "$BASH_VERSION" ]; then
+    complete -o default -F _git_wt_complete git-wt
+fi
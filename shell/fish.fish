@@ -1,24 +1,67 @@
-# git-wt integration v0.1.0
+
+# git-wt integration v0.2.0
+function git-wt
+    set -l cd_path ""
+    set -l title ""
+    set -l env_vars
+    set -l exec_commands
+
+    # Directives come through a file only this shell knows about, never
+    # stdout, which may hold anything a command run by git-wt printed. Their
+    # values escape backslashes and newlines, undone by printf %b
+    set -l directives (mktemp -t git-wt.XXXXXX)
+    or return 1
+    command git-wt --directive-file $directives $argv
+    set -l exit_code $status
+    for line in (cat $directives)
+        switch $line
+            case 'CD:?*'
+                set cd_path (printf '%b' (string sub -s 4 -- $line) | string collect -N)
+            case 'ENV:*=*'
+                if string match -qr '^ENV:[A-Za-z_][A-Za-z0-9_]*=' -- $line
+                    set -a env_vars (printf '%b' (string sub -s 5 -- $line) | string collect -N)
+                end
+            case 'TITLE:*'
+                set title (printf '%b' (string sub -s 7 -- $line) | string collect -N)
+            case 'EXEC:?*'
+                set -a exec_commands (printf '%b' (string sub -s 6 -- $line) | string collect -N)
+        end
+    end
+    rm -f $directives
+
+    # Change directory if we got a CD directive
+    if test -n "$cd_path" -a -d "$cd_path"
+        cd $cd_path; or return 1
+
+        # Drop the variables of the worktree we left, then export the new ones
+        for name in $_git_wt_env_names
+            set -e -g $name
+        end
+        set -g _git_wt_env_names
+        for assignment in $env_vars
+            set -l parts (string split -m 1 = -- $assignment)
+            set -gx $parts[1] $parts[2]
+            set -a _git_wt_env_names $parts[1]
+        end
+    end
+
+    if test -n "$title"; and isatty stdout
+        printf '\e]0;%s\a' $title
+    end
+
+    for exec_command in $exec_commands
+        eval $exec_command
+    end
+end
+
 function git --wraps=git
     if test "$argv[1]" = "wt"
         set -e argv[1]
-        
-        set -l cd_path ""
-        
-        # Capture only stdout, let stderr pass through (for interactive prompts)
-        for line in (command git-wt $argv)
-            if string match -qr '^CD:(.+)$' -- $line
-                set cd_path (string replace -r '^CD:(.+)$' '$1' -- $line)
-            else
-                echo $line
-            end
-        end
-        
-        # Change directory if we got a CD directive
-        if test -n "$cd_path" -a -d "$cd_path"
-            cd $cd_path
-        end
+        git-wt $argv
     else
         command git $argv
     end
 end
+
+complete -c git-wt -f -n '__fish_seen_subcommand_from switch rm path open pull' -a '(command git-wt __complete worktrees)'
+complete -c git-wt -f -l from -x -a '(command git-wt __complete bases)'
@@ -0,0 +1,366 @@
+//! Prompts for screen readers (`accessible = true` or `--accessible`).
+//!
+//! inquire's pickers redraw in place and mark the selection by highlighting
+//! it, which screen readers can't follow. In accessible mode every prompt
+//! goes through [`Ask`] instead: the options are printed once as a numbered
+//! list and the answer is read as a line from stdin. Typing text instead of
+//! a number lists the options containing it.
+
+use crate::config;
+use inquire::error::InquireResult;
+use inquire::{Confirm, MultiSelect, Select};
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+
+pub fn enabled() -> bool {
+    config::get().accessible.unwrap_or(false)
+}
+
+/// Shows a prompt, in accessible mode as plain numbered text; `None` if the
+/// user cancelled.
+pub trait Ask {
+    type Answer;
+    fn ask(self) -> InquireResult<Option<Self::Answer>>;
+}
+
+/// The question of a prompt; this crate's prompts carry it in the prefix.
+fn label<'a>(message: &'a str, prefix: &'a str) -> &'a str {
+    if message.is_empty() { prefix } else { message }
+}
+
+impl<T: Display> Ask for Select<'_, T> {
+    type Answer = T;
+
+    fn ask(self) -> InquireResult<Option<T>> {
+        if !enabled() {
+            return self.prompt_skippable();
+        }
+        let options: Vec<String> = self.options.iter().map(ToString::to_string).collect();
+        let label = label(self.message, self.render_config.prompt_prefix.content);
+        let choice = select(
+            &mut io::stdin().lock(),
+            &mut io::stderr(),
+            label,
+            &options,
+            self.starting_cursor,
+        )?;
+        Ok(choice.and_then(|index| self.options.into_iter().nth(index)))
+    }
+}
+
+impl<T: Display> Ask for MultiSelect<'_, T> {
+    type Answer = Vec<T>;
+
+    fn ask(self) -> InquireResult<Option<Vec<T>>> {
+        if !enabled() {
+            return self.prompt_skippable();
+        }
+        let options: Vec<String> = self.options.iter().map(ToString::to_string).collect();
+        let label = label(self.message, self.render_config.prompt_prefix.content);
+        let chosen = multi_select(
+            &mut io::stdin().lock(),
+            &mut io::stderr(),
+            label,
+            &options,
+            self.default.as_deref().unwrap_or_default(),
+        )?;
+        Ok(chosen.map(|chosen| {
+            self.options
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| chosen.contains(index))
+                .map(|(_, option)| option)
+                .collect()
+        }))
+    }
+}
+
+impl Ask for Confirm<'_> {
+    type Answer = bool;
+
+    fn ask(self) -> InquireResult<Option<bool>> {
+        if !enabled() {
+            return self.prompt_skippable();
+        }
+        let label = label(self.message, self.render_config.prompt_prefix.content);
+        Ok(confirm(
+            &mut io::stdin().lock(),
+            &mut io::stderr(),
+            label,
+            self.default,
+        )?)
+    }
+}
+
+/// Asks for a line of text, `default` if it's left empty.
+pub fn text(prompt: &str, default: &str) -> io::Result<Option<String>> {
+    let mut stderr = io::stderr();
+    write!(stderr, "{prompt} (Enter for \"{default}\") ")?;
+    stderr.flush()?;
+    Ok(read_answer(&mut io::stdin().lock())?.map(|answer| {
+        if answer.is_empty() {
+            default.to_string()
+        } else {
+            answer
+        }
+    }))
+}
+
+/// A line of input without its line ending; `None` at the end of input.
+fn read_answer(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+fn list(
+    output: &mut impl Write,
+    options: &[String],
+    numbers: impl Iterator<Item = usize>,
+) -> io::Result<()> {
+    for index in numbers {
+        writeln!(output, "  {}. {}", index + 1, options[index])?;
+    }
+    Ok(())
+}
+
+/// The options containing `text`, ignoring case.
+fn matching(options: &[String], text: &str) -> Vec<usize> {
+    let text = text.to_lowercase();
+    (0..options.len())
+        .filter(|&index| options[index].to_lowercase().contains(&text))
+        .collect()
+}
+
+fn select(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    label: &str,
+    options: &[String],
+    default: usize,
+) -> io::Result<Option<usize>> {
+    writeln!(output, "{label} {} options:", options.len())?;
+    list(output, options, 0..options.len())?;
+    loop {
+        write!(
+            output,
+            "Number, or text to search (Enter for {}, q to cancel): ",
+            default + 1
+        )?;
+        output.flush()?;
+        let Some(answer) = read_answer(input)? else {
+            return Ok(None);
+        };
+        if answer.is_empty() {
+            return Ok(Some(default));
+        }
+        if answer == "q" {
+            return Ok(None);
+        }
+        if let Ok(number) = answer.parse::<usize>() {
+            if (1..=options.len()).contains(&number) {
+                return Ok(Some(number - 1));
+            }
+            writeln!(output, "There is no option {number}.")?;
+            continue;
+        }
+        match matching(options, &answer)[..] {
+            [] => writeln!(output, "No option contains \"{answer}\".")?,
+            [only] => {
+                writeln!(output, "Selected {}.", options[only])?;
+                return Ok(Some(only));
+            }
+            ref several => {
+                writeln!(output, "{} options contain \"{answer}\":", several.len())?;
+                list(output, options, several.iter().copied())?;
+            }
+        }
+    }
+}
+
+fn multi_select(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    label: &str,
+    options: &[String],
+    default: &[usize],
+) -> io::Result<Option<Vec<usize>>> {
+    writeln!(output, "{label} {} options:", options.len())?;
+    list(output, options, 0..options.len())?;
+    let default_numbers: Vec<String> = default
+        .iter()
+        .map(|index| (index + 1).to_string())
+        .collect();
+    let default_hint = if default_numbers.is_empty() {
+        "none".to_string()
+    } else {
+        default_numbers.join(" ")
+    };
+    loop {
+        write!(
+            output,
+            "Numbers separated by spaces, or all (Enter for {default_hint}, q to cancel): "
+        )?;
+        output.flush()?;
+        let Some(answer) = read_answer(input)? else {
+            return Ok(None);
+        };
+        match answer.as_str() {
+            "" => return Ok(Some(default.to_vec())),
+            "q" => return Ok(None),
+            "all" => return Ok(Some((0..options.len()).collect())),
+            _ => {}
+        }
+        let numbers: Option<Vec<usize>> = answer
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                part.parse::<usize>()
+                    .ok()
+                    .filter(|number| (1..=options.len()).contains(number))
+                    .map(|number| number - 1)
+            })
+            .collect();
+        match numbers {
+            Some(mut numbers) => {
+                numbers.sort_unstable();
+                numbers.dedup();
+                return Ok(Some(numbers));
+            }
+            None => writeln!(
+                output,
+                "Answer with numbers from 1 to {}, separated by spaces.",
+                options.len()
+            )?,
+        }
+    }
+}
+
+fn confirm(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    label: &str,
+    default: Option<bool>,
+) -> io::Result<Option<bool>> {
+    let hint = match default {
+        Some(true) => "yes or no, Enter for yes",
+        Some(false) => "yes or no, Enter for no",
+        None => "yes or no",
+    };
+    loop {
+        write!(output, "{label} ({hint}) ")?;
+        output.flush()?;
+        let Some(answer) = read_answer(input)? else {
+            return Ok(None);
+        };
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Some(true)),
+            "n" | "no" => return Ok(Some(false)),
+            "" if default.is_some() => return Ok(default),
+            _ => writeln!(output, "Please answer yes or no.")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> Vec<String> {
+        ["main", "feature/api", "feature/login"]
+            .map(str::to_string)
+            .to_vec()
+    }
+
+    #[test]
+    fn select_takes_numbers_defaults_and_unique_searches() {
+        let cases = [
+            ("2\n", Some(1)),
+            ("\n", Some(2)),
+            ("login\n", Some(2)),
+            ("q\n", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            let mut output = Vec::new();
+            let choice = select(
+                &mut input.as_bytes(),
+                &mut output,
+                "Switch to:",
+                &options(),
+                2,
+            )
+            .unwrap();
+            assert_eq!(choice, expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn select_lists_matches_and_asks_again() {
+        let mut output = Vec::new();
+        let choice = select(
+            &mut "feature\n9\n3\n".as_bytes(),
+            &mut output,
+            "Switch to:",
+            &options(),
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(choice, Some(2));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Switch to: 3 options:\n  1. main\n"));
+        assert!(
+            output
+                .contains("2 options contain \"feature\":\n  2. feature/api\n  3. feature/login\n")
+        );
+        assert!(output.contains("There is no option 9."));
+    }
+
+    #[test]
+    fn multi_select_reads_lists_of_numbers() {
+        let mut output = Vec::new();
+        let chosen = multi_select(
+            &mut "3, 1 x\n3,1\n".as_bytes(),
+            &mut output,
+            "Move:",
+            &options(),
+            &[0],
+        )
+        .unwrap();
+        assert_eq!(chosen, Some(vec![0, 2]));
+
+        let chosen = multi_select(
+            &mut "\n".as_bytes(),
+            &mut Vec::new(),
+            "Move:",
+            &options(),
+            &[0],
+        )
+        .unwrap();
+        assert_eq!(chosen, Some(vec![0]));
+    }
+
+    #[test]
+    fn confirm_insists_on_yes_or_no() {
+        let mut output = Vec::new();
+        let answer = confirm(&mut "maybe\nYes\n".as_bytes(), &mut output, "Delete?", None).unwrap();
+        assert_eq!(answer, Some(true));
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("Please answer yes or no.")
+        );
+
+        let answer = confirm(
+            &mut "\n".as_bytes(),
+            &mut Vec::new(),
+            "Delete?",
+            Some(false),
+        )
+        .unwrap();
+        assert_eq!(answer, Some(false));
+    }
+}
@@ -0,0 +1,84 @@
+//! What the shell integration does after switching to a worktree.
+//!
+//! `[switch_env]` maps branch patterns to variables, exported for the new
+//! worktree and unset again when switching away, like direnv.
+//! `switch_title` sets the terminal title, and `switch_exec` runs commands in
+//! the shell itself, e.g. to activate a virtualenv, which hooks can't do
+//! since they run in a child process.
+
+use crate::directive::Directive;
+use crate::hooks::{HookContext, sh_quote};
+use crate::list::glob_match;
+use crate::{config, get_worktree_root, log_info, same_path, worktree};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The directives following `CD:` for the worktree at `path`, with
+/// `{branch}`, `{path}`, `{root}`, `{project}` and `{index}` replaced as in
+/// hooks. Nothing for a detached worktree.
+pub fn directives(path: &Path) -> Vec<Directive> {
+    let config = config::get();
+    if config.switch_env.is_empty() && config.switch_title.is_none() && config.switch_exec.is_none()
+    {
+        return Vec::new();
+    }
+    let Some(branch) = worktree::list()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|worktree| same_path(Path::new(&worktree.path), path))
+        .and_then(|worktree| worktree.branch)
+    else {
+        return Vec::new();
+    };
+    let root = get_worktree_root().unwrap_or_default();
+    let context = HookContext {
+        branch: &branch,
+        path,
+        root: &root,
+        base: None,
+    };
+
+    let mut directives: Vec<Directive> = variables(&branch)
+        .into_iter()
+        .map(|(name, value)| Directive::Env(name.to_string(), context.expand(value)))
+        .collect();
+    if let Some(title) = &config.switch_title {
+        directives.push(Directive::Title(context.expand(title)));
+    }
+    for command in config.switch_exec.iter().flatten() {
+        directives.push(Directive::Exec(context.expand_with(command, sh_quote)));
+    }
+    directives
+}
+
+/// The `[switch_env]` variables for `branch`. When several patterns match,
+/// the longer pattern wins for a variable both set.
+fn variables(branch: &str) -> BTreeMap<&str, &str> {
+    let mut patterns: Vec<_> = config::get()
+        .switch_env
+        .iter()
+        .filter(|(pattern, _)| glob_match(pattern, branch))
+        .collect();
+    patterns.sort_by_key(|(pattern, _)| pattern.len());
+
+    let mut variables = BTreeMap::new();
+    for (name, value) in patterns.into_iter().flat_map(|(_, variables)| variables) {
+        if !valid_name(name) {
+            log_info(&format!(
+                "Note: '{name}' in switch_env isn't a valid variable name, skipping it"
+            ));
+            continue;
+        }
+        variables.insert(name.as_str(), value.as_str());
+    }
+    variables
+}
+
+/// Whether `name` can be exported by every supported shell.
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
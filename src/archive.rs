@@ -0,0 +1,158 @@
+//! `archive`/`unarchive`: dropping a dormant worktree's directory while
+//! remembering it, so it can be recreated later at the same commit.
+//!
+//! Archived worktrees are recorded in `<git-common-dir>/git-wt/archive.json`.
+
+use crate::{
+    AddArgs, add_worktree, change_directory, check_git_repo, default_remote, format,
+    get_worktree_root, git_common_dir, git_stdout, log_error, log_info, ref_exists,
+    remove_worktree, resolve_worktree, run_command, shared,
+};
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Serialize, Deserialize)]
+pub struct Archived {
+    /// Where the worktree was
+    pub path: PathBuf,
+    /// The commit it had checked out
+    pub commit: String,
+    /// Unix time it was archived
+    pub time: u64,
+}
+
+fn archive_path() -> Result<PathBuf> {
+    let common_dir = git_common_dir().context("Not in a git repository")?;
+    Ok(common_dir.join("git-wt").join("archive.json"))
+}
+
+/// Every archived worktree by branch.
+pub fn load() -> Result<BTreeMap<String, Archived>> {
+    let path = archive_path()?;
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn save(archived: &BTreeMap<String, Archived>) -> Result<()> {
+    let path = archive_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(archived)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    shared::state_file(&path);
+    Ok(())
+}
+
+/// Pushes the branch, records it and removes its worktree.
+pub fn archive(branch: Option<&str>) -> Result<()> {
+    check_git_repo();
+    let (branch, path) = resolve_worktree(branch)?;
+    let worktree = Path::new(&path);
+
+    // Only commits survive archiving
+    if git_stdout(
+        &["--no-optional-locks", "status", "--porcelain"],
+        Some(worktree),
+    )
+    .is_none_or(|s| !s.is_empty())
+    {
+        log_error(&format!(
+            "'{branch}' has uncommitted changes; commit or stash them before archiving"
+        ));
+        process::exit(1);
+    }
+
+    push(&branch, worktree)?;
+
+    let commit = git_stdout(&["rev-parse", "HEAD"], Some(worktree))
+        .context("Failed to read the worktree's commit")?;
+    let mut archived = load()?;
+    archived.insert(
+        branch.clone(),
+        Archived {
+            path: worktree.to_path_buf(),
+            commit,
+            time: format::now(),
+        },
+    );
+    save(&archived)?;
+
+    // Don't remove the directory from underneath ourselves
+    let root = get_worktree_root()?;
+    let inside = env::current_dir().is_ok_and(|cwd| cwd.starts_with(worktree));
+    if inside {
+        env::set_current_dir(&root)?;
+    }
+    remove_worktree(Some(&branch), false, false, true)?;
+    if inside {
+        change_directory(&root);
+    }
+
+    log_info(&format!(
+        "Archived '{branch}'; `git-wt unarchive {branch}` brings it back."
+    ));
+    Ok(())
+}
+
+/// Makes sure the remote has every commit of `branch`.
+fn push(branch: &str, worktree: &Path) -> Result<()> {
+    let upstream_ref = format!("{branch}@{{upstream}}");
+    let Some(upstream) = git_stdout(
+        &["rev-parse", "--abbrev-ref", &upstream_ref],
+        Some(worktree),
+    ) else {
+        let remote = default_remote();
+        log_info(&format!("Pushing '{branch}' to {remote}..."));
+        return run_command("git", &["push", "-u", &remote, branch], Some(worktree));
+    };
+
+    let range = format!("{upstream}..HEAD");
+    if git_stdout(&["rev-list", "--count", &range], Some(worktree)).as_deref() != Some("0") {
+        log_info(&format!("Pushing '{branch}' to {upstream}..."));
+        run_command("git", &["push"], Some(worktree))?;
+    }
+    Ok(())
+}
+
+/// Recreates an archived worktree at the commit it was archived at.
+pub fn unarchive(branch: &str) -> Result<()> {
+    check_git_repo();
+    let mut archived = load()?;
+    let Some(entry) = archived.remove(branch) else {
+        log_error(&format!("'{branch}' is not archived"));
+        process::exit(1);
+    };
+
+    let branch_exists = ref_exists(&format!("refs/heads/{branch}"));
+    if branch_exists
+        && git_stdout(&["rev-parse", branch], None).as_deref() != Some(entry.commit.as_str())
+    {
+        log_info(&format!(
+            "Note: '{branch}' moved since it was archived at {}",
+            &entry.commit[..entry.commit.len().min(12)]
+        ));
+    }
+
+    // A deleted branch is recreated at the archived commit
+    let path = add_worktree(&AddArgs {
+        branch: branch.to_string(),
+        from: Some(entry.commit),
+        ..AddArgs::default()
+    })?;
+    save(&archived)?;
+
+    change_directory(&path);
+    Ok(())
+}
@@ -0,0 +1,110 @@
+//! Audit log of operations that change worktrees, kept in
+//! `<git-common-dir>/git-wt/audit.log` and shown by `git-wt history`.
+//!
+//! Every line holds tab-separated fields: UTC time, user, process id, the
+//! directory git-wt ran in, the command line and what happened. A run of a
+//! mutating command ends with an `ok` or `failed: <reason>` line; the lines
+//! before it with the same process id record what it did, e.g. `removed
+//! worktree <path> (<branch>)`.
+
+use crate::{format, git_common_dir, owner, shared};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the running command is one that changes things, so its outcome
+/// is recorded.
+static MUTATING: AtomicBool = AtomicBool::new(false);
+
+fn log_path() -> Option<PathBuf> {
+    Some(git_common_dir()?.join("git-wt").join("audit.log"))
+}
+
+/// Marks the running command as mutating; its result is recorded by
+/// [`finished`] or [`failed`].
+pub fn start() {
+    MUTATING.store(true, Ordering::Relaxed);
+}
+
+pub fn finished() {
+    if MUTATING.swap(false, Ordering::Relaxed) {
+        record("ok");
+    }
+}
+
+pub fn failed(message: &str) {
+    if MUTATING.swap(false, Ordering::Relaxed) {
+        record(&format!("failed: {message}"));
+    }
+}
+
+/// Appends `what` to the audit log. Outside a repository, or if the log
+/// can't be written, nothing is recorded; auditing never fails a command.
+pub fn record(what: &str) {
+    let Some(path) = log_path() else {
+        return;
+    };
+    let command: Vec<String> = env::args().skip(1).collect();
+    let cwd = env::current_dir()
+        .map(|cwd| cwd.display().to_string())
+        .unwrap_or_default();
+    let user = owner::current_user();
+    let fields = [
+        utc_now(),
+        user,
+        process::id().to_string(),
+        cwd,
+        format!("git-wt {}", command.join(" ")),
+        what.to_string(),
+    ];
+    // Tabs and newlines would break the line format
+    let line: Vec<String> = fields
+        .iter()
+        .map(|field| field.replace(['\t', '\n', '\r'], " "))
+        .collect();
+
+    let _ = fs::create_dir_all(path.parent().unwrap_or(&path));
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = writeln!(file, "{}", line.join("\t"));
+    }
+    shared::state_file(&path);
+}
+
+/// Prints the audit log, the last `limit` entries if given, optionally only
+/// those by `user`.
+pub fn history(limit: Option<usize>, user: Option<&str>) -> Result<()> {
+    let Some(path) = log_path() else {
+        return Ok(());
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+
+    let entries: Vec<Vec<&str>> = contents
+        .lines()
+        .map(|line| line.split('\t').collect::<Vec<_>>())
+        .filter(|fields| fields.len() == 6)
+        .filter(|fields| user.is_none_or(|user| fields[1] == user))
+        .collect();
+    let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+    for fields in &entries[skip..] {
+        let [time, user, _, cwd, command, what] = fields[..] else {
+            continue;
+        };
+        println!("{time}  {user}  {what}");
+        println!("    {command}  (in {cwd})");
+    }
+    Ok(())
+}
+
+/// The current time as `YYYY-MM-DDTHH:MM:SSZ`.
+fn utc_now() -> String {
+    format::iso(format::now(), 0)
+}
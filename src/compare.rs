@@ -0,0 +1,85 @@
+//! `compare`: how two worktrees differ, uncommitted changes included.
+//!
+//! Each worktree's working tree is written as a tree object through a
+//! temporary index, so neither the worktrees nor their real indexes are
+//! touched, and the two trees are diffed.
+
+use crate::runner::{self, Capture};
+use crate::{
+    check_git_repo, failure_reason, git_stdout, log_error, process, resolve_worktree,
+    run_command_status,
+};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use std::fs;
+use std::path::Path;
+
+pub fn run(a: &str, b: &str, patch: bool) -> Result<()> {
+    check_git_repo();
+    let (branch_a, path_a) = resolve_worktree(Some(a))?;
+    let (branch_b, path_b) = resolve_worktree(Some(b))?;
+    let tree_a = snapshot_tree(Path::new(&path_a))?;
+    let tree_b = snapshot_tree(Path::new(&path_b))?;
+
+    let format = if patch { "--patch-with-stat" } else { "--stat" };
+    let src_prefix = format!("--src-prefix={branch_a}/");
+    let dst_prefix = format!("--dst-prefix={branch_b}/");
+    let status = run_command_status(
+        "git",
+        &["diff", format, &src_prefix, &dst_prefix, &tree_a, &tree_b],
+        Some(Path::new(&path_a)),
+    )?;
+    if !status.success() {
+        process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// Writes the working tree of `worktree`, untracked files included and
+/// ignored ones not, as a tree object and returns its id.
+fn snapshot_tree(worktree: &Path) -> Result<String> {
+    let Some(index) = git_stdout(&["rev-parse", "--git-path", "index"], Some(worktree)) else {
+        log_error(&format!(
+            "Failed to locate the index of {}",
+            worktree.display()
+        ));
+        process::exit(1);
+    };
+    // Relative to the worktree unless it lives elsewhere
+    let index = worktree.join(index);
+    let temp_index = Path::new(&index).with_file_name(format!("git-wt-compare-{}", process::id()));
+
+    // Starting from the real index keeps git from rehashing unchanged files
+    let result = (|| {
+        match fs::copy(&index, &temp_index) {
+            Ok(_) => {}
+            // A worktree without an index has nothing staged yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("Failed to copy the index"),
+        }
+        git_with_index(worktree, &temp_index, &["add", "--all", "--", "."])?;
+        git_with_index(worktree, &temp_index, &["write-tree"])
+    })();
+    let _ = fs::remove_file(&temp_index);
+    result
+}
+
+fn git_with_index(worktree: &Path, index: &Path, args: &[&str]) -> Result<String> {
+    let output = runner::run_with_env(
+        "git",
+        args,
+        &[("GIT_INDEX_FILE", index.as_os_str())],
+        Some(worktree),
+        Capture::StdoutAndStderr,
+    )?;
+    if !output.status.success() {
+        log_error(&format!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            worktree.display(),
+            failure_reason(&output.stderr)
+        ));
+        process::exit(1);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,1175 @@
+//! Layered configuration.
+//!
+//! Settings are resolved with the precedence CLI flags > `GIT_WT_*`
+//! environment variables > repo config (`<git-common-dir>/git-wt/config.toml`)
+//! > global config (`$XDG_CONFIG_HOME/git-wt/config.toml`).
+
+use crate::{log_info, shared};
+use clap::ValueEnum;
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat, bail};
+use inquire::ui::Color;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemePreset {
+    /// Colored prompts with a highlighted selection
+    #[default]
+    Default,
+    /// No colors or attributes, ASCII markers only
+    Plain,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullStrategy {
+    /// Rebase local commits onto the upstream
+    Rebase,
+    /// Only fast-forward, failing if the branches diverged
+    FfOnly,
+    /// Merge the upstream, creating a merge commit if needed
+    Merge,
+}
+
+impl PullStrategy {
+    /// The `git pull` flag selecting this strategy.
+    pub fn flag(self) -> &'static str {
+        match self {
+            PullStrategy::Rebase => "--rebase",
+            PullStrategy::FfOnly => "--ff-only",
+            PullStrategy::Merge => "--no-rebase",
+        }
+    }
+}
+
+/// How dates are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DateStyle {
+    /// How long ago, e.g. "3 days ago"
+    #[default]
+    Relative,
+    /// ISO 8601 date and time
+    Iso,
+}
+
+/// Which side of the WSL boundary the shell integration runs on, when it
+/// differs from git-wt's.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum WslPaths {
+    /// Leave paths as they are
+    #[default]
+    Off,
+    /// A Windows shell runs git-wt inside WSL: `C:\src\app`
+    Windows,
+    /// A WSL shell runs the Windows git-wt: `/mnt/c/src/app`
+    Unix,
+}
+
+/// Who besides the owner may write worktrees and state files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SharedMode {
+    /// Members of the files' group
+    Group,
+}
+
+/// How template `copy` rules put files into a new worktree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyStrategy {
+    /// Reflink where the filesystem supports it, otherwise copy
+    #[default]
+    Auto,
+    /// Share the data copy-on-write (btrfs, XFS, APFS), failing elsewhere
+    Reflink,
+    /// Hardlink, so every worktree sees the same file; falls back to a copy
+    /// across filesystems. Only for files nothing modifies in place
+    Hardlink,
+    /// Always copy the data
+    Copy,
+}
+
+/// A template `copy` rule: a path, or a table choosing how it's copied.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum CopyRule {
+    Path(PathBuf),
+    WithStrategy {
+        path: PathBuf,
+        #[serde(default)]
+        strategy: CopyStrategy,
+    },
+}
+
+impl CopyRule {
+    pub fn path(&self) -> &Path {
+        match self {
+            CopyRule::Path(path) | CopyRule::WithStrategy { path, .. } => path,
+        }
+    }
+
+    pub fn strategy(&self) -> CopyStrategy {
+        match self {
+            CopyRule::Path(_) => CopyStrategy::default(),
+            CopyRule::WithStrategy { strategy, .. } => *strategy,
+        }
+    }
+}
+
+/// A prompt color given by name (`darkgrey`, `blue`, ...) or as `#rrggbb`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ThemeColor(pub Color);
+
+impl TryFrom<String> for ThemeColor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        parse_color(&value)
+            .map(ThemeColor)
+            .ok_or_else(|| format!("unknown color '{value}'"))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub preset: Option<ThemePreset>,
+    /// Prefix shown before selection prompts
+    pub prompt_prefix: Option<String>,
+    /// Marker in front of the highlighted option
+    pub highlight_prefix: Option<String>,
+    pub option_color: Option<ThemeColor>,
+    pub highlight_fg: Option<ThemeColor>,
+    pub highlight_bg: Option<ThemeColor>,
+    /// Number of options visible at once in pickers
+    pub page_size: Option<usize>,
+}
+
+impl ThemeConfig {
+    fn merge(self, over: ThemeConfig) -> ThemeConfig {
+        ThemeConfig {
+            preset: over.preset.or(self.preset),
+            prompt_prefix: over.prompt_prefix.or(self.prompt_prefix),
+            highlight_prefix: over.highlight_prefix.or(self.highlight_prefix),
+            option_color: over.option_color.or(self.option_color),
+            highlight_fg: over.highlight_fg.or(self.highlight_fg),
+            highlight_bg: over.highlight_bg.or(self.highlight_bg),
+            page_size: over.page_size.or(self.page_size),
+        }
+    }
+
+    fn from_env() -> Result<ThemeConfig> {
+        let color = |v: &str| parse_color(v).map(ThemeColor);
+        Ok(ThemeConfig {
+            preset: env_parse("GIT_WT_THEME_PRESET", |v| {
+                ThemePreset::from_str(v, true).ok()
+            })?,
+            prompt_prefix: env_var("GIT_WT_THEME_PROMPT_PREFIX"),
+            highlight_prefix: env_var("GIT_WT_THEME_HIGHLIGHT_PREFIX"),
+            option_color: env_parse("GIT_WT_THEME_OPTION_COLOR", color)?,
+            highlight_fg: env_parse("GIT_WT_THEME_HIGHLIGHT_FG", color)?,
+            highlight_bg: env_parse("GIT_WT_THEME_HIGHLIGHT_BG", color)?,
+            page_size: env_parse("GIT_WT_THEME_PAGE_SIZE", |v| v.parse().ok())?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Set to false to skip all hooks
+    pub enabled: Option<bool>,
+    /// Commands run in the worktree root before a worktree is created
+    pub pre_add: Option<Vec<String>>,
+    /// Commands run in a worktree after it was created
+    pub post_add: Option<Vec<String>>,
+    /// Commands run in a worktree before it is removed
+    pub pre_remove: Option<Vec<String>>,
+    /// Commands run in the worktree root after a worktree was removed
+    pub post_remove: Option<Vec<String>>,
+}
+
+impl HooksConfig {
+    fn merge(self, over: HooksConfig) -> HooksConfig {
+        HooksConfig {
+            enabled: over.enabled.or(self.enabled),
+            pre_add: over.pre_add.or(self.pre_add),
+            post_add: over.post_add.or(self.post_add),
+            pre_remove: over.pre_remove.or(self.pre_remove),
+            post_remove: over.post_remove.or(self.post_remove),
+        }
+    }
+}
+
+/// Per-branch docker compose stacks, brought up by `add` and down by `rm`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ComposeConfig {
+    pub enabled: Option<bool>,
+    /// Project name template (defaults to `{project}`, the sanitized branch)
+    pub project: Option<String>,
+    /// Compose files passed with `-f`, relative to the worktree
+    pub files: Option<Vec<String>>,
+}
+
+impl ComposeConfig {
+    fn merge(self, over: ComposeConfig) -> ComposeConfig {
+        ComposeConfig {
+            enabled: over.enabled.or(self.enabled),
+            project: over.project.or(self.project),
+            files: over.files.or(self.files),
+        }
+    }
+}
+
+/// Files rendered into every new worktree from templates (`[env_files]`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvFilesConfig {
+    /// Files to write, by path in the worktree, mapped to the template they
+    /// are rendered from (relative to the worktree)
+    pub files: BTreeMap<PathBuf, PathBuf>,
+    /// `{{port}}` is this plus the worktree's index (defaults to 3000)
+    pub port_base: Option<u16>,
+}
+
+impl EnvFilesConfig {
+    fn merge(self, over: EnvFilesConfig) -> EnvFilesConfig {
+        EnvFilesConfig {
+            files: {
+                let mut files = self.files;
+                files.extend(over.files);
+                files
+            },
+            port_base: over.port_base.or(self.port_base),
+        }
+    }
+}
+
+/// Defaults for `release` (`[release]`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReleaseConfig {
+    /// Ref release branches start from (defaults to the default branch)
+    pub base: Option<String>,
+    /// Commands run in the new worktree to bump the version; `{version}` is
+    /// replaced and exported as `GIT_WT_VERSION`
+    pub bump: Option<Vec<String>>,
+}
+
+impl ReleaseConfig {
+    fn merge(self, over: ReleaseConfig) -> ReleaseConfig {
+        ReleaseConfig {
+            base: over.base.or(self.base),
+            bump: over.bump.or(self.bump),
+        }
+    }
+}
+
+/// Limits on git commands talking to remotes (`[remotes]`), to stay under
+/// rate limits and SSH `MaxSessions` of the server.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemotesConfig {
+    /// Remote operations running at once (defaults to 4)
+    pub max_concurrent: Option<usize>,
+    /// Milliseconds between starting two operations against the same host
+    /// (defaults to 0)
+    pub host_delay: Option<u64>,
+}
+
+impl RemotesConfig {
+    fn merge(self, over: RemotesConfig) -> RemotesConfig {
+        RemotesConfig {
+            max_concurrent: over.max_concurrent.or(self.max_concurrent),
+            host_delay: over.host_delay.or(self.host_delay),
+        }
+    }
+}
+
+/// When `rm` asks before removing a worktree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RmConfirm {
+    /// Every time, unless `--yes` is passed
+    #[default]
+    Always,
+    /// Never
+    Never,
+    /// Only when the worktree has changes or commits no remote has
+    UnpushedOnly,
+}
+
+/// Defaults for `rm` (`[rm]`).
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RmConfig {
+    /// When to ask before removing (defaults to always)
+    pub confirm: Option<RmConfirm>,
+}
+
+impl RmConfig {
+    fn merge(self, over: RmConfig) -> RmConfig {
+        RmConfig {
+            confirm: over.confirm.or(self.confirm),
+        }
+    }
+}
+
+/// A workspace layout applied by `clone --template <name>`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Template {
+    /// Branches to create worktrees for after cloning
+    pub worktrees: Vec<String>,
+    /// Directory of git hook scripts installed into the bare repo
+    pub hooks: Option<PathBuf>,
+    /// Files and directories copied into the root of every created worktree
+    pub copy: Vec<CopyRule>,
+    /// Paths inside every created worktree, mapped to the shared directory
+    /// they are symlinked to
+    pub share: BTreeMap<PathBuf, PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Ref new branches start from when `--from` is omitted and no
+    /// same-named remote branch exists
+    pub default_base: Option<String>,
+    /// Remote git-wt fetches from and pushes to, and `add` looks for
+    /// `<remote>/<branch>` on (defaults to git's `checkout.defaultRemote`, then
+    /// origin)
+    pub default_remote: Option<String>,
+    /// Directory new worktrees are created in, relative to the worktree root
+    /// (`../<repo>-worktrees` in a standard clone)
+    pub worktree_dir: Option<PathBuf>,
+    /// Never open interactive prompts
+    pub non_interactive: Option<bool>,
+    /// When to color output
+    pub color: Option<ColorMode>,
+    /// Plain numbered prompts read line by line, no animations and no
+    /// information shown only by color, for screen readers (`--accessible`)
+    pub accessible: Option<bool>,
+    /// How dates are shown (`--iso`)
+    pub dates: Option<DateStyle>,
+    /// Show ISO dates in UTC instead of local time (`--utc`)
+    pub utc: Option<bool>,
+    /// Seconds after which git commands are killed
+    pub timeout: Option<u64>,
+    /// Let git and ssh prompt for credentials on the terminal
+    pub terminal_prompt: Option<bool>,
+    /// Command `open` starts with the worktree path (defaults to $VISUAL or $EDITOR)
+    pub editor: Option<String>,
+    /// Name of the shell function `init` defines (defaults to `git-wt`)
+    pub shell_function: Option<String>,
+    /// How `pull` integrates upstream changes, instead of each worktree's
+    /// `pull.rebase`/`pull.ff` settings
+    pub pull_strategy: Option<PullStrategy>,
+    /// Show a desktop notification when clone, pull, fetch --update or clean
+    /// finish or fail
+    pub notify: Option<bool>,
+    /// Make the repository, worktrees and state files writable for the
+    /// group, for bare repositories shared by a team
+    pub shared: Option<SharedMode>,
+    /// Refspecs `clone --all-refs` fetches in addition to the branches
+    /// (defaults to tags and notes)
+    pub clone_refspecs: Option<Vec<String>>,
+    /// Branches `clone` fetches, as names or globs such as `release/*`
+    /// (defaults to all); `git-wt track` adds more later
+    pub fetch_branches: Option<Vec<String>>,
+    /// Tell the remote only about worktree branches and their upstreams when
+    /// fetching, instead of every local ref, which speeds up negotiation with
+    /// hundreds of branches
+    pub fetch_negotiation_tips: Option<bool>,
+    /// Translate the directories to switch to with `wslpath` for a shell on
+    /// the other side of the WSL boundary
+    pub wsl_paths: Option<WslPaths>,
+    /// File directory changes are written to instead of the `CD:` marker;
+    /// only set per invocation (`--cd-file` or `GIT_WT_CD_FILE`)
+    #[serde(skip)]
+    pub cd_file: Option<PathBuf>,
+    /// File the shell integration reads directives from instead of stdout;
+    /// only set per invocation (`--directive-file`)
+    #[serde(skip)]
+    pub directive_file: Option<PathBuf>,
+    /// Skip invalid settings with a warning instead of failing; only set per
+    /// invocation (`--lenient` or `GIT_WT_LENIENT`)
+    #[serde(skip)]
+    pub lenient: Option<bool>,
+    /// Describe what `add`, `rm` and `clean` will do and ask first; only set
+    /// per invocation (`--explain`)
+    #[serde(skip)]
+    pub explain: Option<bool>,
+    /// Behave the same on every machine, for the integration tests; only set
+    /// per invocation (`--test-mode` or `GIT_WT_TEST_MODE`)
+    #[serde(skip)]
+    pub test_mode: Option<bool>,
+    /// Styling of interactive prompts
+    pub theme: ThemeConfig,
+    /// Named templates for `clone --template`
+    pub templates: BTreeMap<String, Template>,
+    pub hooks: HooksConfig,
+    pub compose: ComposeConfig,
+    pub env_files: EnvFilesConfig,
+    /// Environment variables the shell integration exports after switching
+    /// to a worktree, by branch glob pattern, mapped to their values
+    pub switch_env: BTreeMap<String, BTreeMap<String, String>>,
+    /// Terminal title the shell integration sets after switching
+    pub switch_title: Option<String>,
+    /// Commands the shell integration runs in the shell itself after
+    /// switching
+    pub switch_exec: Option<Vec<String>>,
+    pub release: ReleaseConfig,
+    pub remotes: RemotesConfig,
+    pub rm: RmConfig,
+}
+
+impl Config {
+    /// Returns `self` with every key set in `over` replaced.
+    fn merge(self, over: Config) -> Config {
+        Config {
+            default_base: over.default_base.or(self.default_base),
+            default_remote: over.default_remote.or(self.default_remote),
+            worktree_dir: over.worktree_dir.or(self.worktree_dir),
+            non_interactive: over.non_interactive.or(self.non_interactive),
+            color: over.color.or(self.color),
+            accessible: over.accessible.or(self.accessible),
+            dates: over.dates.or(self.dates),
+            utc: over.utc.or(self.utc),
+            timeout: over.timeout.or(self.timeout),
+            terminal_prompt: over.terminal_prompt.or(self.terminal_prompt),
+            editor: over.editor.or(self.editor),
+            shell_function: over.shell_function.or(self.shell_function),
+            pull_strategy: over.pull_strategy.or(self.pull_strategy),
+            notify: over.notify.or(self.notify),
+            shared: over.shared.or(self.shared),
+            clone_refspecs: over.clone_refspecs.or(self.clone_refspecs),
+            fetch_branches: over.fetch_branches.or(self.fetch_branches),
+            fetch_negotiation_tips: over.fetch_negotiation_tips.or(self.fetch_negotiation_tips),
+            wsl_paths: over.wsl_paths.or(self.wsl_paths),
+            cd_file: over.cd_file.or(self.cd_file),
+            directive_file: over.directive_file.or(self.directive_file),
+            lenient: over.lenient.or(self.lenient),
+            explain: over.explain.or(self.explain),
+            test_mode: over.test_mode.or(self.test_mode),
+            theme: self.theme.merge(over.theme),
+            templates: {
+                let mut templates = self.templates;
+                templates.extend(over.templates);
+                templates
+            },
+            hooks: self.hooks.merge(over.hooks),
+            compose: self.compose.merge(over.compose),
+            env_files: self.env_files.merge(over.env_files),
+            switch_env: {
+                let mut switch_env = self.switch_env;
+                switch_env.extend(over.switch_env);
+                switch_env
+            },
+            switch_title: over.switch_title.or(self.switch_title),
+            switch_exec: over.switch_exec.or(self.switch_exec),
+            release: self.release.merge(over.release),
+            remotes: self.remotes.merge(over.remotes),
+            rm: self.rm.merge(over.rm),
+        }
+    }
+
+    /// Reads a config file. An invalid one is an error pointing at the
+    /// offending line, unless `lenient`, in which case the top-level keys
+    /// that are valid on their own are kept and the rest is skipped with a
+    /// warning.
+    fn from_file(path: &Path, lenient: bool) -> Result<Config> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        let error = match toml::from_str(&contents) {
+            Ok(config) => return Ok(config),
+            Err(error) => error,
+        };
+        let diagnostic = diagnose(path, &contents, &error);
+        if !lenient {
+            bail!("{diagnostic}");
+        }
+
+        log_info(&format!("Warning: {diagnostic}"));
+        let Ok(table) = toml::from_str::<toml::Table>(&contents) else {
+            log_info(&format!(
+                "Warning: ignoring all of {} (pass --lenient only once it parses to keep the valid keys)",
+                path.display()
+            ));
+            return Ok(Config::default());
+        };
+
+        let mut valid = toml::Table::new();
+        for (key, value) in table {
+            let mut single = toml::Table::new();
+            single.insert(key.clone(), value.clone());
+            let accepted = toml::to_string(&single)
+                .is_ok_and(|single| toml::from_str::<Config>(&single).is_ok());
+            if accepted {
+                valid.insert(key, value);
+            } else {
+                log_info(&format!(
+                    "Warning: ignoring `{key}` in {}, using its defaults",
+                    path.display()
+                ));
+            }
+        }
+        toml::to_string(&valid)
+            .ok()
+            .and_then(|valid| toml::from_str(&valid).ok())
+            .map_or_else(|| Ok(Config::default()), Ok)
+    }
+
+    fn from_env() -> Result<Config> {
+        Ok(Config {
+            default_base: env_var("GIT_WT_DEFAULT_BASE"),
+            default_remote: env_var("GIT_WT_DEFAULT_REMOTE"),
+            worktree_dir: env_var("GIT_WT_WORKTREE_DIR").map(PathBuf::from),
+            non_interactive: env_parse("GIT_WT_NON_INTERACTIVE", parse_bool)?,
+            color: env_parse("GIT_WT_COLOR", |v| ColorMode::from_str(v, true).ok())?,
+            accessible: env_parse("GIT_WT_ACCESSIBLE", parse_bool)?,
+            dates: env_parse("GIT_WT_DATES", |v| DateStyle::from_str(v, true).ok())?,
+            utc: env_parse("GIT_WT_UTC", parse_bool)?,
+            timeout: env_parse("GIT_WT_TIMEOUT", |v| v.parse().ok())?,
+            terminal_prompt: env_parse("GIT_WT_TERMINAL_PROMPT", parse_bool)?,
+            editor: env_var("GIT_WT_EDITOR"),
+            shell_function: env_var("GIT_WT_SHELL_FUNCTION"),
+            pull_strategy: env_parse("GIT_WT_PULL_STRATEGY", |v| {
+                PullStrategy::from_str(v, true).ok()
+            })?,
+            notify: env_parse("GIT_WT_NOTIFY", parse_bool)?,
+            shared: env_parse("GIT_WT_SHARED", |v| SharedMode::from_str(v, true).ok())?,
+            clone_refspecs: env_list("GIT_WT_CLONE_REFSPECS")?,
+            fetch_branches: env_list("GIT_WT_FETCH_BRANCHES")?,
+            fetch_negotiation_tips: env_parse("GIT_WT_FETCH_NEGOTIATION_TIPS", parse_bool)?,
+            wsl_paths: env_parse("GIT_WT_WSL_PATHS", |v| WslPaths::from_str(v, true).ok())?,
+            cd_file: env_var("GIT_WT_CD_FILE").map(PathBuf::from),
+            directive_file: None,
+            lenient: env_parse("GIT_WT_LENIENT", parse_bool)?,
+            explain: None,
+            test_mode: env_parse("GIT_WT_TEST_MODE", parse_bool)?,
+            theme: ThemeConfig::from_env()?,
+            templates: BTreeMap::new(),
+            hooks: HooksConfig {
+                enabled: env_parse("GIT_WT_HOOKS_ENABLED", parse_bool)?,
+                pre_add: env_list("GIT_WT_HOOKS_PRE_ADD")?,
+                post_add: env_list("GIT_WT_HOOKS_POST_ADD")?,
+                pre_remove: env_list("GIT_WT_HOOKS_PRE_REMOVE")?,
+                post_remove: env_list("GIT_WT_HOOKS_POST_REMOVE")?,
+            },
+            compose: ComposeConfig {
+                enabled: env_parse("GIT_WT_COMPOSE_ENABLED", parse_bool)?,
+                project: env_var("GIT_WT_COMPOSE_PROJECT"),
+                files: env_list("GIT_WT_COMPOSE_FILES")?,
+            },
+            env_files: EnvFilesConfig {
+                port_base: env_parse("GIT_WT_ENV_FILES_PORT_BASE", |v| v.parse().ok())?,
+                ..EnvFilesConfig::default()
+            },
+            switch_env: BTreeMap::new(),
+            switch_title: env_var("GIT_WT_SWITCH_TITLE"),
+            switch_exec: env_list("GIT_WT_SWITCH_EXEC")?,
+            release: ReleaseConfig {
+                base: env_var("GIT_WT_RELEASE_BASE"),
+                bump: env_list("GIT_WT_RELEASE_BUMP")?,
+            },
+            remotes: RemotesConfig {
+                max_concurrent: env_parse("GIT_WT_REMOTES_MAX_CONCURRENT", |v| v.parse().ok())?,
+                host_delay: env_parse("GIT_WT_REMOTES_HOST_DELAY", |v| v.parse().ok())?,
+            },
+            rm: RmConfig {
+                confirm: env_parse("GIT_WT_RM_CONFIRM", |v| RmConfirm::from_str(v, true).ok())?,
+            },
+        })
+    }
+
+    /// Returns why prompts can't be shown, or `None` if they can.
+    ///
+    /// Prompts read from stdin and render on stderr, so both need to be
+    /// terminals; stdout is captured by the shell integration.
+    pub fn non_interactive_reason(&self) -> Option<&'static str> {
+        if self.test_mode() {
+            Some("test mode is enabled")
+        } else if self.non_interactive.unwrap_or(false) {
+            Some("non-interactive mode is enabled")
+        } else if !io::stdin().is_terminal() {
+            Some("stdin is not a terminal")
+        } else if !io::stderr().is_terminal() {
+            Some("stderr is not a terminal")
+        } else {
+            None
+        }
+    }
+
+    pub fn use_color(&self) -> bool {
+        if self.test_mode() {
+            return false;
+        }
+        match self.color.unwrap_or_default() {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => env::var_os("NO_COLOR").is_none() && io::stderr().is_terminal(),
+        }
+    }
+
+    /// Whether ISO dates are shown in UTC; always in test mode, so output
+    /// doesn't depend on the machine's time zone.
+    pub fn utc(&self) -> bool {
+        self.utc.unwrap_or(false) || self.test_mode()
+    }
+
+    pub fn test_mode(&self) -> bool {
+        self.test_mode.unwrap_or(false)
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    /// Whether prompts should be drawn without colors or attributes.
+    pub fn plain_prompts(&self) -> bool {
+        self.theme.preset == Some(ThemePreset::Plain) || !self.use_color()
+    }
+}
+
+pub fn repo_config_path(common_dir: &Path) -> PathBuf {
+    common_dir.join("git-wt").join("config.toml")
+}
+
+/// Returns the user-level config path, or `None` if no home directory is known.
+pub fn global_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .or_else(|| env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+
+    Some(config_home.join("git-wt").join("config.toml"))
+}
+
+/// Resolves all config layers and makes the result available through [`get`].
+///
+/// `cli` holds the values given as command line flags; `common_dir` is the
+/// git common dir of the current repository, if there is one.
+pub fn init(cli: Config, common_dir: Option<&Path>) -> Result<()> {
+    let env = Config::from_env()?;
+    let lenient = cli.lenient.or(env.lenient).unwrap_or(false);
+    // Tests mustn't pick up the settings of whoever runs them
+    let test_mode = cli.test_mode.or(env.test_mode).unwrap_or(false);
+    let global = match global_config_path() {
+        Some(path) if !test_mode => Config::from_file(&path, lenient)?,
+        _ => Config::default(),
+    };
+    let repo = match common_dir {
+        Some(dir) => Config::from_file(&repo_config_path(dir), lenient)?,
+        None => Config::default(),
+    };
+
+    let _ = CONFIG.set(layered(global, repo, env, cli));
+    Ok(())
+}
+
+/// The config layers combined, each overriding the keys set in the ones
+/// before it.
+fn layered(global: Config, repo: Config, env: Config, cli: Config) -> Config {
+    global.merge(repo).merge(env).merge(cli)
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// The file `config get/set/...` work on: the global one, or else the
+/// repository's.
+fn target_path(global: bool, common_dir: Option<&Path>) -> Result<PathBuf> {
+    if global {
+        return global_config_path().context("No home directory to keep the global config in");
+    }
+    match common_dir {
+        Some(dir) => Ok(repo_config_path(dir)),
+        None => bail!("Not in a git repository; pass --global for the user-wide config"),
+    }
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Looks up a dotted key such as `theme.preset`.
+fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let (parents, last) = match key.rsplit_once('.') {
+        Some((parents, last)) => (Some(parents), last),
+        None => (None, key),
+    };
+    let mut table = table;
+    for part in parents.into_iter().flat_map(|parents| parents.split('.')) {
+        table = table.get(part)?.as_table()?;
+    }
+    table.get(last)
+}
+
+/// Prints the value of `key`: from the global file with `global`, else from
+/// the repository's file, falling back to the global one. Exits with 1 if
+/// it isn't set, like `git config`.
+pub fn print_value(key: &str, global: bool, common_dir: Option<&Path>) -> Result<()> {
+    let mut paths = vec![target_path(global, common_dir)?];
+    if !global && let Some(path) = global_config_path() {
+        paths.push(path);
+    }
+
+    for path in paths {
+        if let Some(value) = lookup(&read_table(&path)?, key) {
+            match value {
+                // Strings unquoted so scripts can use them as they are
+                toml::Value::String(value) => println!("{value}"),
+                value => println!("{value}"),
+            }
+            return Ok(());
+        }
+    }
+    std::process::exit(1);
+}
+
+/// Prints every key set in the config files, each file under a comment
+/// naming it.
+pub fn print_all(global: bool, common_dir: Option<&Path>) -> Result<()> {
+    let mut paths = Vec::new();
+    if let Some(path) = global_config_path() {
+        paths.push(path);
+    }
+    if !global && let Some(dir) = common_dir {
+        paths.push(repo_config_path(dir));
+    }
+
+    for path in paths {
+        let table = read_table(&path)?;
+        if table.is_empty() {
+            continue;
+        }
+        println!("# {}", path.display());
+        print_entries(&table, "");
+    }
+    Ok(())
+}
+
+fn print_entries(table: &toml::Table, prefix: &str) {
+    for (key, value) in table {
+        let key = format!("{prefix}{key}");
+        match value {
+            toml::Value::Table(table) => print_entries(table, &format!("{key}.")),
+            value => println!("{key} = {value}"),
+        }
+    }
+}
+
+/// Sets `key` to `value` (or removes it if `value` is `None`) in the config
+/// file, refusing keys and values the config doesn't accept.
+///
+/// `value` is read as a TOML value if it is one (`true`, `120`,
+/// `["a", "b"]`) and as a string otherwise.
+pub fn set_value(
+    key: &str,
+    value: Option<&str>,
+    global: bool,
+    common_dir: Option<&Path>,
+) -> Result<()> {
+    let path = target_path(global, common_dir)?;
+    let mut table = read_table(&path)?;
+
+    let parts: Vec<&str> = key.split('.').collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        bail!("Invalid key '{key}'");
+    }
+    let (last, parents) = parts.split_last().unwrap_or((&key, &[]));
+    let mut parent = &mut table;
+    for part in parents {
+        if parent.get(*part).is_none() {
+            parent.insert((*part).to_string(), toml::Value::Table(toml::Table::new()));
+        }
+        parent = match parent.get_mut(*part).and_then(toml::Value::as_table_mut) {
+            Some(table) => table,
+            None => bail!("'{part}' in '{key}' is not a table"),
+        };
+    }
+    match value {
+        Some(value) => {
+            parent.insert((*last).to_string(), parse_value(value));
+        }
+        None if parent.remove(*last).is_none() => bail!("'{key}' is not set in {}", path.display()),
+        None => {}
+    }
+
+    let contents = toml::to_string_pretty(&table).context("Failed to serialize the config")?;
+    // Validate against the real schema so typos and wrong types are caught
+    // here rather than on the next run
+    if let Err(e) = toml::from_str::<Config>(&contents) {
+        bail!(
+            "Invalid setting for '{key}': {}",
+            with_suggestion(e.message())
+        );
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    shared::state_file(&path);
+    Ok(())
+}
+
+fn parse_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// Opens the config file in `$VISUAL`/`$EDITOR` and checks it afterwards.
+pub fn edit(global: bool, common_dir: Option<&Path>) -> Result<()> {
+    let path = target_path(global, common_dir)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let editor = env_var("VISUAL")
+        .or_else(|| env_var("EDITOR"))
+        .unwrap_or_else(|| "vi".to_string());
+    // Allow editors with arguments, e.g. "code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !status.success() {
+        bail!("{program} exited with {status}");
+    }
+
+    Config::from_file(&path, false).map(|_| ())
+}
+
+/// Describes a parse error as `<file>:<line>:<column>: <message>`, followed
+/// by the offending line and a suggestion for misspelled keys.
+fn diagnose(path: &Path, contents: &str, error: &toml::de::Error) -> String {
+    let message = with_suggestion(error.message());
+    let Some(span) = error.span() else {
+        return format!("Invalid config in {}: {message}", path.display());
+    };
+
+    let start = span.start.min(contents.len());
+    let line_start = contents[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_number = contents[..start].matches('\n').count() + 1;
+    let column = contents[line_start..start].chars().count() + 1;
+    let line = contents[line_start..].lines().next().unwrap_or_default();
+    let width = contents[start..span.end.min(contents.len())]
+        .lines()
+        .next()
+        .map_or(1, |marked| marked.chars().count().max(1));
+    let gutter = " ".repeat(line_number.to_string().len());
+    let indent = " ".repeat(column - 1);
+    format!(
+        "Invalid config in {}:{line_number}:{column}: {message}\n  {line_number} | {line}\n  {gutter} | {indent}{}",
+        path.display(),
+        "^".repeat(width)
+    )
+}
+
+/// Appends "did you mean" to serde's "unknown field `x`, expected one of
+/// `a`, `b`" when one of the expected keys is close to `x`.
+fn with_suggestion(message: &str) -> String {
+    let names: Vec<&str> = message.split('`').skip(1).step_by(2).collect();
+    let Some((unknown, expected)) = names
+        .split_first()
+        .filter(|_| message.starts_with("unknown field"))
+    else {
+        return message.to_string();
+    };
+
+    let closest = expected
+        .iter()
+        .map(|name| (edit_distance(unknown, name), name))
+        .filter(|(distance, name)| *distance <= name.len().div_ceil(3))
+        .min();
+    match closest {
+        Some((_, name)) => format!("{message}; did you mean `{name}`?"),
+        None => message.to_string(),
+    }
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Expands a leading `~` to the home directory.
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn env_parse<T>(name: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Option<T>> {
+    let Some(value) = env_var(name) else {
+        return Ok(None);
+    };
+
+    match parse(&value) {
+        Some(parsed) => Ok(Some(parsed)),
+        None => bail!("Invalid value for {name}: '{value}'"),
+    }
+}
+
+/// A list written like in the config file, e.g. `["main", "release/*"]`.
+fn env_list(name: &str) -> Result<Option<Vec<String>>> {
+    #[derive(Deserialize)]
+    struct List {
+        value: Vec<String>,
+    }
+
+    env_parse(name, |v| {
+        toml::from_str::<List>(&format!("value = {v}"))
+            .ok()
+            .map(|list| list.value)
+    })
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+
+    let color = match value.to_ascii_lowercase().as_str() {
+        "black" => Color::Black,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        "darkgrey" | "darkgray" => Color::DarkGrey,
+        "red" => Color::LightRed,
+        "darkred" => Color::DarkRed,
+        "green" => Color::LightGreen,
+        "darkgreen" => Color::DarkGreen,
+        "yellow" => Color::LightYellow,
+        "darkyellow" => Color::DarkYellow,
+        "blue" => Color::LightBlue,
+        "darkblue" => Color::DarkBlue,
+        "magenta" => Color::LightMagenta,
+        "darkmagenta" => Color::DarkMagenta,
+        "cyan" => Color::LightCyan,
+        "darkcyan" => Color::DarkCyan,
+        _ => return None,
+    };
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Config {
+        toml::from_str(contents).unwrap()
+    }
+
+    /// Writes `contents` to a config file of its own for test `name`.
+    fn write(name: &str, contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("git-wt-config-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn unknown_key_gets_a_suggestion() {
+        let path = write("unknown", "default_base = \"main\"\ntimout = 5\n");
+        let error = Config::from_file(&path, false).unwrap_err().to_string();
+        assert!(error.starts_with(&format!("Invalid config in {}:2:1: ", path.display())));
+        assert!(error.contains("unknown field `timout`"));
+        assert!(error.ends_with("did you mean `timeout`?\n  2 | timout = 5\n    | ^^^^^^"));
+
+        let message = "unknown field `frobnicate`, expected one of `timeout`, `utc`";
+        assert_eq!(with_suggestion(message), message);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn type_error_points_at_the_line() {
+        let path = write("type", "utc = true\ntimeout = \"soon\"\n");
+        let error = Config::from_file(&path, false).unwrap_err().to_string();
+        assert!(error.starts_with(&format!("Invalid config in {}:2:11: ", path.display())));
+        assert!(error.ends_with("  2 | timeout = \"soon\"\n    |           ^^^^^^"));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn lenient_keeps_the_valid_keys() {
+        let path = write(
+            "lenient",
+            "timeout = 5\nutc = \"yes\"\n[hooks]\npost_ad = [\"npm ci\"]\n",
+        );
+        let config = Config::from_file(&path, true).unwrap();
+        assert_eq!(config.timeout, Some(5));
+        assert_eq!(config.utc, None);
+        assert_eq!(config.hooks.post_add, None);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        // Nothing to keep when it doesn't even parse
+        let path = write("unparsable", "timeout = 5\n[hooks\n");
+        let config = Config::from_file(&path, true).unwrap();
+        assert_eq!(config.timeout, None);
+        assert!(Config::from_file(&path.with_file_name("missing.toml"), false).is_ok());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let global = || {
+            parse(
+                "default_base = \"develop\"\ntimeout = 10\n\
+                 [hooks]\npost_add = [\"npm ci\"]\n\
+                 [templates.web]\nworktrees = [\"main\"]\n",
+            )
+        };
+        let repo = || {
+            parse(
+                "timeout = 20\n[hooks]\npre_add = [\"make\"]\n\
+                 [templates.api]\nworktrees = [\"main\"]\n",
+            )
+        };
+        let env = || parse("timeout = 30");
+        let cli = parse("timeout = 40");
+
+        let config = layered(global(), repo(), env(), cli);
+        assert_eq!(config.timeout, Some(40));
+        // Keys no later layer sets survive, also inside tables
+        assert_eq!(config.default_base.as_deref(), Some("develop"));
+        assert_eq!(config.hooks.post_add, Some(vec!["npm ci".to_string()]));
+        assert_eq!(config.hooks.pre_add, Some(vec!["make".to_string()]));
+        assert!(config.templates.contains_key("web") && config.templates.contains_key("api"));
+
+        let config = layered(global(), repo(), env(), Config::default());
+        assert_eq!(config.timeout, Some(30));
+        let config = layered(global(), repo(), Config::default(), Config::default());
+        assert_eq!(config.timeout, Some(20));
+        let config = layered(
+            global(),
+            Config::default(),
+            Config::default(),
+            Config::default(),
+        );
+        assert_eq!(config.timeout, Some(10));
+        // With no layer setting it, callers fall back to their default
+        assert_eq!(Config::default().timeout(), None);
+        assert!(Config::default().hooks.enabled.unwrap_or(true));
+    }
+
+    #[test]
+    fn env_overrides_files() {
+        // SAFETY: no other test reads or writes these variables
+        unsafe {
+            env::set_var("GIT_WT_TIMEOUT", "30");
+            env::set_var("GIT_WT_FETCH_BRANCHES", r#"["main", "release/*"]"#);
+            env::set_var("GIT_WT_HOOKS_POST_ADD", r#"["echo a, b"]"#);
+        }
+        let env = Config::from_env();
+        unsafe {
+            env::set_var("GIT_WT_RELEASE_BUMP", "make bump");
+        }
+        let invalid = Config::from_env();
+        for name in [
+            "GIT_WT_TIMEOUT",
+            "GIT_WT_FETCH_BRANCHES",
+            "GIT_WT_HOOKS_POST_ADD",
+            "GIT_WT_RELEASE_BUMP",
+        ] {
+            unsafe { env::remove_var(name) };
+        }
+
+        let env = env.unwrap();
+        assert_eq!(
+            env.fetch_branches,
+            Some(vec!["main".to_string(), "release/*".to_string()])
+        );
+        let repo =
+            parse("timeout = 20\nfetch_branches = [\"main\"]\n[hooks]\npost_add = [\"x\"]\n");
+        let config = layered(Config::default(), repo, env, Config::default());
+        assert_eq!(config.timeout, Some(30));
+        assert_eq!(
+            config.fetch_branches.map(|branches| branches.len()),
+            Some(2)
+        );
+        assert_eq!(config.hooks.post_add, Some(vec!["echo a, b".to_string()]));
+
+        let error = invalid.unwrap_err().to_string();
+        assert_eq!(error, "Invalid value for GIT_WT_RELEASE_BUMP: 'make bump'");
+    }
+}
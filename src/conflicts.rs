@@ -0,0 +1,130 @@
+//! The dashboard shown when worktrees are left with conflicts.
+//!
+//! `pull --all` carries on past a worktree whose rebase or merge stopped on
+//! conflicts, so they're collected and listed at the end: the worktree, the
+//! conflicted files and the commands to continue or abort there. With a
+//! terminal it then offers to switch to one of them.
+
+use crate::accessible::Ask;
+use crate::{
+    change_directory, config, create_select_render_config, git_stdout, log_info, prompt_result,
+};
+use color_eyre::Result;
+use inquire::Select;
+use std::path::{Path, PathBuf};
+
+/// What stopped on conflicts.
+#[derive(Clone, Copy)]
+enum Operation {
+    Rebase,
+    Merge,
+    CherryPick,
+    Revert,
+}
+
+impl Operation {
+    fn command(self) -> &'static str {
+        match self {
+            Operation::Rebase => "rebase",
+            Operation::Merge => "merge",
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+        }
+    }
+}
+
+pub struct Conflict {
+    branch: String,
+    path: PathBuf,
+    operation: Operation,
+    files: Vec<String>,
+}
+
+/// The operation the worktree at `path` is in the middle of, and its
+/// conflicted files, if there is one.
+pub fn detect(branch: &str, path: &Path) -> Option<Conflict> {
+    let in_progress = |name: &str| {
+        git_stdout(&["rev-parse", "--git-path", name], Some(path))
+            .is_some_and(|git_path| path.join(git_path).exists())
+    };
+    let operation = if in_progress("rebase-merge") || in_progress("rebase-apply") {
+        Operation::Rebase
+    } else if in_progress("MERGE_HEAD") {
+        Operation::Merge
+    } else if in_progress("CHERRY_PICK_HEAD") {
+        Operation::CherryPick
+    } else if in_progress("REVERT_HEAD") {
+        Operation::Revert
+    } else {
+        return None;
+    };
+
+    let files = git_stdout(&["diff", "--name-only", "--diff-filter=U"], Some(path))
+        .map(|files| files.lines().map(String::from).collect())
+        .unwrap_or_default();
+    Some(Conflict {
+        branch: branch.to_string(),
+        path: path.to_path_buf(),
+        operation,
+        files,
+    })
+}
+
+impl Conflict {
+    /// The summary detail for this worktree, e.g. "rebase stopped on
+    /// conflicts in 2 files".
+    pub fn describe(&self) -> String {
+        let operation = self.operation.command();
+        match self.files.len() {
+            0 => format!("{operation} in progress"),
+            1 => format!("{operation} stopped on conflicts in 1 file"),
+            n => format!("{operation} stopped on conflicts in {n} files"),
+        }
+    }
+}
+
+/// Lists the worktrees in `conflicts` with their files and how to go on,
+/// then offers to switch to one.
+pub fn report(conflicts: &[Conflict]) -> Result<()> {
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    log_info("Worktrees with unfinished operations:");
+    for conflict in conflicts {
+        let operation = conflict.operation.command();
+        log_info(&format!(
+            "  {} ({operation}) in {}",
+            conflict.branch,
+            conflict.path.display()
+        ));
+        for file in &conflict.files {
+            log_info(&format!("    {file}"));
+        }
+        let git = format!("git -C {}", conflict.path.display());
+        log_info(&format!("    resolve, then: {git} {operation} --continue"));
+        log_info(&format!("    or give up:    {git} {operation} --abort"));
+    }
+
+    if config::get().non_interactive_reason().is_some() {
+        return Ok(());
+    }
+    let stay = "Stay here".to_string();
+    let mut options: Vec<String> = conflicts
+        .iter()
+        .map(|conflict| format!("Switch to {}", conflict.branch))
+        .collect();
+    options.push(stay);
+    let selection = Select::new("", options.clone())
+        .with_render_config(create_select_render_config(false))
+        .without_help_message()
+        .ask();
+
+    if let Some(choice) = prompt_result(selection)?
+        && let Some(index) = options.iter().position(|option| *option == choice)
+        && let Some(conflict) = conflicts.get(index)
+    {
+        change_directory(&conflict.path);
+    }
+    Ok(())
+}
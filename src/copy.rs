@@ -0,0 +1,66 @@
+//! Copying template files into new worktrees without duplicating their data
+//! where possible.
+//!
+//! Large ignored artifacts (datasets, model weights) copied into every
+//! worktree add up quickly. Reflinks share the data copy-on-write on btrfs,
+//! XFS and APFS, so `auto` tries them first and copies where they aren't
+//! supported. Hardlinks work on any filesystem but share the file itself, so
+//! they are only used when a rule asks for them.
+
+use crate::config::CopyStrategy;
+use color_eyre::Result;
+use color_eyre::eyre::{Context, bail};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Copies the file or directory `source` to `target` with `strategy`.
+pub fn copy(source: &Path, target: &Path, strategy: CopyStrategy) -> Result<()> {
+    if source.is_dir() {
+        fs::create_dir_all(target)
+            .with_context(|| format!("Failed to create {}", target.display()))?;
+        let entries =
+            fs::read_dir(source).with_context(|| format!("Failed to read {}", source.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            copy(&entry.path(), &target.join(entry.file_name()), strategy)?;
+        }
+        return Ok(());
+    }
+
+    match strategy {
+        CopyStrategy::Auto if reflink(source, target) => Ok(()),
+        CopyStrategy::Reflink if !reflink(source, target) => bail!(
+            "Failed to reflink {}; the filesystem may not support reflinks",
+            source.display()
+        ),
+        CopyStrategy::Reflink => Ok(()),
+        // Hardlinks can't cross filesystems
+        CopyStrategy::Hardlink if fs::hard_link(source, target).is_ok() => Ok(()),
+        CopyStrategy::Auto | CopyStrategy::Hardlink | CopyStrategy::Copy => {
+            fs::copy(source, target)
+                .with_context(|| format!("Failed to copy {}", source.display()))?;
+            Ok(())
+        }
+    }
+}
+
+/// Clones `source` to `target` copy-on-write with the system's `cp`, which
+/// knows the platform's clone call. Returns false where that isn't supported.
+fn reflink(source: &Path, target: &Path) -> bool {
+    let flag = if cfg!(target_os = "macos") {
+        "-c"
+    } else if cfg!(target_os = "linux") {
+        "--reflink=always"
+    } else {
+        return false;
+    };
+    Command::new("cp")
+        .arg(flag)
+        .arg(source)
+        .arg(target)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
@@ -0,0 +1,112 @@
+//! `copy-file`: grabs files from another worktree without switching to it.
+//!
+//! Paths are relative to the current directory and name the same place in
+//! both worktrees. By default the files are copied as they are in the other
+//! worktree, uncommitted changes included; `--committed` takes them from its
+//! `HEAD` instead. Files that differ from what they'd be replaced with are
+//! only overwritten after asking, or with `--force`.
+
+use crate::accessible::Ask;
+use crate::runner::{self, Capture};
+use crate::{
+    check_git_repo, config, create_confirm_render_config, git_stdout, log_error, log_info, process,
+    prompt_result, resolve_worktree,
+};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use inquire::Confirm;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn run(from: &str, paths: &[PathBuf], committed: bool, force: bool) -> Result<()> {
+    check_git_repo();
+    let (branch, worktree) = resolve_worktree(Some(from))?;
+    let prefix = git_stdout(&["rev-parse", "--show-prefix"], None).unwrap_or_default();
+    let source_dir = Path::new(&worktree).join(prefix);
+    let commit = if committed {
+        git_stdout(
+            &["rev-parse", "--verify", "HEAD"],
+            Some(Path::new(&worktree)),
+        )
+    } else {
+        None
+    };
+
+    for path in paths {
+        let contents = match &commit {
+            Some(commit) => committed_contents(commit, path)?,
+            None => worktree_contents(&branch, &source_dir.join(path), path)?,
+        };
+
+        if fs::read(path).is_ok_and(|existing| existing != contents)
+            && !force
+            && !confirm_overwrite(path)?
+        {
+            log_info(&format!("Skipped {}", path.display()));
+            continue;
+        }
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        if commit.is_none() {
+            copy_permissions(&source_dir.join(path), path);
+        }
+        log_info(&format!("Copied {} from '{branch}'", path.display()));
+    }
+    Ok(())
+}
+
+/// The contents of `source`, the file at `path` in the worktree of `branch`.
+fn worktree_contents(branch: &str, source: &Path, path: &Path) -> Result<Vec<u8>> {
+    if !source.is_file() {
+        log_error(&format!("'{branch}' has no file {}", path.display()));
+        process::exit(1);
+    }
+    fs::read(source).with_context(|| format!("Failed to read {}", source.display()))
+}
+
+/// The contents of `path`, relative to the current directory, in `commit`.
+fn committed_contents(commit: &str, path: &Path) -> Result<Vec<u8>> {
+    let blob = format!("{commit}:./{}", path.display());
+    let output = runner::run(
+        "git",
+        &["cat-file", "blob", &blob],
+        None,
+        Capture::StdoutAndStderr,
+    )?;
+    if !output.status.success() {
+        log_error(&format!("No file {} in {commit}", path.display()));
+        process::exit(1);
+    }
+    Ok(output.stdout)
+}
+
+/// Asks whether to overwrite `path`. Without a terminal this is an error
+/// pointing at `--force`.
+fn confirm_overwrite(path: &Path) -> Result<bool> {
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_error(&format!(
+            "{} has different contents and {reason}; pass --force to overwrite it",
+            path.display()
+        ));
+        process::exit(1);
+    }
+
+    let prompt = format!("Overwrite {}?", path.display());
+    let confirmed = Confirm::new("")
+        .with_default(false)
+        .with_render_config(create_confirm_render_config(&prompt))
+        .ask();
+    Ok(prompt_result(confirmed)? == Some(true))
+}
+
+/// Gives `to` the permissions of `from`, so scripts stay executable.
+fn copy_permissions(from: &Path, to: &Path) {
+    if let Ok(metadata) = fs::metadata(from) {
+        let _ = fs::set_permissions(to, metadata.permissions());
+    }
+}
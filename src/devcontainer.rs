@@ -0,0 +1,69 @@
+//! Dev Container support through the `devcontainer` CLI.
+
+use crate::{log_error, log_info};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use serde::Deserialize;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+
+/// The JSON result `devcontainer up` prints as the last line of its stdout.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpResult {
+    outcome: String,
+    container_id: Option<String>,
+    remote_user: Option<String>,
+    remote_workspace_folder: Option<String>,
+    message: Option<String>,
+}
+
+/// Starts the dev container for the worktree at `path` and prints how to
+/// connect to it.
+pub fn up(path: &Path) -> Result<()> {
+    log_info(&format!("Starting dev container for {}...", path.display()));
+
+    let output = Command::new("devcontainer")
+        .arg("up")
+        .arg("--workspace-folder")
+        .arg(path)
+        .stderr(Stdio::inherit())
+        .output()
+        .context("Failed to run devcontainer (is @devcontainers/cli installed?)")?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let result = stdout
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .and_then(|line| serde_json::from_str::<UpResult>(line).ok());
+
+    let result = match result {
+        Some(result) if output.status.success() && result.outcome == "success" => result,
+        result => {
+            let message = result.and_then(|r| r.message);
+            log_error(&format!(
+                "devcontainer up failed{}",
+                message.map(|m| format!(": {m}")).unwrap_or_default()
+            ));
+            process::exit(1);
+        }
+    };
+
+    log_info("Dev container is running.");
+    if let Some(id) = &result.container_id {
+        log_info(&format!("  Container:  {id}"));
+    }
+    if let Some(user) = &result.remote_user {
+        log_info(&format!("  User:       {user}"));
+    }
+    if let Some(folder) = &result.remote_workspace_folder {
+        log_info(&format!("  Workspace:  {folder}"));
+    }
+    log_info(&format!(
+        "  Connect:    devcontainer exec --workspace-folder {} bash",
+        path.display()
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! The protocol between git-wt and the shell integration.
+//!
+//! The shell wrappers create a private file, pass it with
+//! `--directive-file` and, once git-wt exits, act on its lines:
+//!
+//! - `CD:<path>`: change into the directory
+//! - `ENV:<name>=<value>`: export the variable once changed into the
+//!   directory, unsetting those exported on the previous `CD:`
+//! - `TITLE:<text>`: set the terminal title
+//! - `EXEC:<command>`: run the command in the shell itself, after everything
+//!   else
+//!
+//! Stdout isn't part of the protocol for the wrappers: commands like `exec`
+//! pass through what other programs print, which could spoof any directive.
+//! Without a directive file the directives are still printed for other
+//! tools, except `EXEC:`, which would run whatever such output says.
+//!
+//! Each directive is one line, so backslashes in its value are written as
+//! `\\` and newlines as `\n`; the shells undo that with `printf %b`.
+
+use crate::config;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub enum Directive {
+    Cd(PathBuf),
+    Env(String, String),
+    Title(String),
+    Exec(String),
+}
+
+impl Directive {
+    fn line(&self) -> String {
+        match self {
+            Directive::Cd(path) => format!("CD:{}", escape(&path.to_string_lossy())),
+            Directive::Env(name, value) => format!("ENV:{name}={}", escape(value)),
+            Directive::Title(title) => format!("TITLE:{}", escape(title)),
+            Directive::Exec(command) => format!("EXEC:{}", escape(command)),
+        }
+    }
+}
+
+/// Hands `directives` to the shell integration: written to the
+/// `--directive-file` if one was given, or else printed.
+pub fn emit(directives: &[Directive]) -> io::Result<()> {
+    let Some(file) = &config::get().directive_file else {
+        for directive in directives {
+            if !matches!(directive, Directive::Exec(_)) {
+                println!("{}", directive.line());
+            }
+        }
+        return Ok(());
+    };
+
+    let mut contents = String::new();
+    for directive in directives {
+        let _ = writeln!(contents, "{}", directive.line());
+    }
+    fs::write(file, contents)
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
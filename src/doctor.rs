@@ -0,0 +1,297 @@
+//! `doctor`: checks that the environment supports what git-wt relies on,
+//! with `--optimize` that status checks across many worktrees are fast, and
+//! with `--fsck` that the object store they all share is intact.
+
+use crate::accessible::Ask;
+use crate::runner::{self, Capture};
+use crate::{
+    config, create_confirm_render_config, git_stdout, log_error, log_info, prompt_result,
+    run_command,
+};
+use color_eyre::Result;
+use inquire::Confirm;
+use std::process;
+use std::sync::OnceLock;
+
+/// Oldest git git-wt runs with at all: `worktree list --porcelain`.
+const MINIMUM_GIT: (u32, u32) = (2, 7);
+
+/// Git features git-wt uses, with the release that introduced them and what
+/// to do without them.
+const FEATURES: &[(&str, (u32, u32), &str)] = &[
+    (
+        "worktree list --porcelain",
+        (2, 7),
+        "git-wt can't list worktrees; upgrade git",
+    ),
+    (
+        "fetch --negotiation-tip",
+        (2, 19),
+        "`fetch_negotiation_tips` has no effect",
+    ),
+    (
+        "per-worktree config (config --worktree)",
+        (2, 20),
+        "`hooks install --worktree` is unavailable; use `hooks install`",
+    ),
+    (
+        "sparse-checkout cone mode",
+        (2, 25),
+        "check out full worktrees instead",
+    ),
+    (
+        "worktree repair",
+        (2, 30),
+        "re-create moved worktrees with `git-wt rm` and `git-wt add`",
+    ),
+    (
+        "locked/prunable fields in worktree porcelain",
+        (2, 31),
+        "locked and prunable worktrees aren't marked",
+    ),
+];
+
+/// Settings that keep `git status` fast in large worktrees, with the git
+/// release that introduced them.
+const STATUS_SETTINGS: &[(&str, (u32, u32), &str)] = &[
+    (
+        "core.untrackedCache",
+        (2, 8),
+        "caches which directories have untracked files",
+    ),
+    (
+        "core.fsmonitor",
+        (2, 37),
+        "watches the file system instead of scanning every file",
+    ),
+];
+
+/// The installed git version as `(major, minor)`, if git runs at all.
+pub fn git_version() -> Option<(u32, u32)> {
+    static VERSION: OnceLock<Option<(u32, u32)>> = OnceLock::new();
+    *VERSION.get_or_init(|| {
+        // "git version 2.39.2", "git version 2.39.2.windows.1" or
+        // "git version 2.39.2 (Apple Git-143)"
+        let output = git_stdout(&["--version"], None)?;
+        let mut parts = output.split_whitespace().nth(2)?.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some((major, minor))
+    })
+}
+
+/// Whether the installed git is at least `version`. Unknown versions are
+/// assumed to be recent enough.
+pub fn git_supports(version: (u32, u32)) -> bool {
+    git_version().is_none_or(|installed| installed >= version)
+}
+
+/// Exits with a readable error when git is too old for `feature`.
+pub fn require_git(version: (u32, u32), feature: &str) {
+    if !git_supports(version) {
+        let (major, minor) = version;
+        let (installed_major, installed_minor) = git_version().unwrap_or_default();
+        log_error(&format!(
+            "{feature} needs git {major}.{minor} or newer, but git {installed_major}.{installed_minor} is installed"
+        ));
+        process::exit(1);
+    }
+}
+
+/// Refuses to run on a git too old for git-wt.
+pub fn require_minimum_git() {
+    require_git(MINIMUM_GIT, "git-wt");
+}
+
+/// Prints the git version and which features it supports.
+pub fn versions() {
+    let Some((major, minor)) = git_version() else {
+        log_error("Could not determine the git version (is git installed?)");
+        process::exit(1);
+    };
+
+    log_info(&format!("git-wt {}", env!("CARGO_PKG_VERSION")));
+    log_info(&format!("git {major}.{minor}"));
+
+    let width = FEATURES
+        .iter()
+        .map(|(feature, _, _)| feature.len())
+        .max()
+        .unwrap_or(0);
+    let mut missing = false;
+    for (feature, (needs_major, needs_minor), fallback) in FEATURES {
+        if git_supports((*needs_major, *needs_minor)) {
+            log_info(&format!("  {feature:<width$}  ok"));
+        } else {
+            missing = true;
+            log_info(&format!(
+                "  {feature:<width$}  missing (git {needs_major}.{needs_minor}): {fallback}"
+            ));
+        }
+    }
+
+    if missing {
+        log_info("Some features are unavailable; upgrading git enables them.");
+    }
+}
+
+/// Whether `key` is supported here: the built-in file system monitor only
+/// runs on macOS and Windows.
+fn setting_available(key: &str, version: (u32, u32)) -> bool {
+    git_supports(version) && (key != "core.fsmonitor" || cfg!(any(target_os = "macos", windows)))
+}
+
+/// Whether `key` is on. `core.fsmonitor` may also name a hook script.
+fn setting_enabled(key: &str) -> bool {
+    git_stdout(&["config", "--get", key], None)
+        .is_some_and(|value| !matches!(value.as_str(), "" | "false" | "no" | "off" | "0"))
+}
+
+/// The status settings that are available here but not turned on.
+pub fn missing_status_settings() -> Vec<&'static str> {
+    STATUS_SETTINGS
+        .iter()
+        .filter(|(key, version, _)| setting_available(key, *version) && !setting_enabled(key))
+        .map(|(key, _, _)| *key)
+        .collect()
+}
+
+/// Reports the status settings and offers to turn on the missing ones for
+/// the repository, which all its worktrees share.
+pub fn optimize(yes: bool) -> Result<()> {
+    let width = STATUS_SETTINGS
+        .iter()
+        .map(|(key, _, _)| key.len())
+        .max()
+        .unwrap_or(0);
+    for (key, version, purpose) in STATUS_SETTINGS {
+        let state = if !setting_available(key, *version) {
+            "unavailable"
+        } else if setting_enabled(key) {
+            "on"
+        } else {
+            "off"
+        };
+        log_info(&format!("  {key:<width$}  {state:<11}  {purpose}"));
+    }
+
+    let missing = missing_status_settings();
+    if missing.is_empty() {
+        log_info("Status checks are as fast as this git can make them.");
+        return Ok(());
+    }
+
+    if !yes {
+        if let Some(reason) = config::get().non_interactive_reason() {
+            log_info(&format!(
+                "Not changing settings because {reason}; pass --yes to turn on {}",
+                missing.join(" and ")
+            ));
+            return Ok(());
+        }
+        let prompt = format!("Turn on {} for all worktrees?", missing.join(" and "));
+        let confirmed = Confirm::new("")
+            .with_default(true)
+            .with_render_config(create_confirm_render_config(&prompt))
+            .ask();
+        if prompt_result(confirmed)? != Some(true) {
+            return Ok(());
+        }
+    }
+
+    for key in missing {
+        run_command("git", &["config", key, "true"], None)?;
+        log_info(&format!("Turned on {key}."));
+    }
+    Ok(())
+}
+
+/// Kinds of problems `git fsck` reports, by a text its lines contain, with
+/// what to do about them.
+const FSCK_PROBLEMS: &[(&str, &str, &str)] = &[
+    (
+        "missing ",
+        "missing objects",
+        "fetch them again with `git fetch --refetch origin`",
+    ),
+    (
+        "broken link",
+        "broken links between objects",
+        "fetch the objects again with `git fetch --refetch origin`",
+    ),
+    (
+        "corrupt",
+        "corrupt objects",
+        "move the broken files out of .bare/objects and run `git fetch --refetch origin`",
+    ),
+    (
+        "hash mismatch",
+        "corrupt objects",
+        "move the broken files out of .bare/objects and run `git fetch --refetch origin`",
+    ),
+    (
+        "invalid sha1 pointer",
+        "refs pointing at missing objects",
+        "fetch the objects again with `git fetch --refetch origin`, or reset the refs with `git update-ref`",
+    ),
+    (
+        "reflog",
+        "broken reflog entries",
+        "drop them with `git reflog expire --expire-unreachable=now --all`",
+    ),
+    (
+        "ref",
+        "broken refs",
+        "delete or reset the refs named above with `git update-ref`",
+    ),
+];
+
+/// Runs `git fsck` on the repository and summarizes what it found, exiting
+/// with an error if anything is wrong.
+pub fn fsck() -> Result<()> {
+    log_info("Checking the object store, this can take a while...");
+    let output = runner::run(
+        "git",
+        &["fsck", "--no-dangling", "--no-progress"],
+        None,
+        Capture::StdoutAndStderr,
+    )?;
+    let mut report = String::from_utf8_lossy(&output.stdout).into_owned();
+    report.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    // Each line counts once, under the first kind it matches
+    let mut found: Vec<(&str, &str, usize)> = Vec::new();
+    let mut unknown = 0;
+    for line in report
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let kind = FSCK_PROBLEMS
+            .iter()
+            .find(|(pattern, _, _)| line.contains(pattern));
+        match kind {
+            Some((_, problem, advice)) => match found.iter_mut().find(|(p, _, _)| p == problem) {
+                Some((_, _, count)) => *count += 1,
+                None => found.push((problem, advice, 1)),
+            },
+            None => unknown += 1,
+        }
+    }
+
+    if output.status.success() && found.is_empty() {
+        log_info("The object store is intact.");
+        return Ok(());
+    }
+
+    log_info(report.trim_end());
+    log_info("Summary:");
+    for (problem, advice, count) in &found {
+        log_info(&format!("  {problem} ({count}): {advice}"));
+    }
+    if unknown > 0 {
+        log_info(&format!("  other messages ({unknown}): see above"));
+    }
+    log_error("The repository has problems; every worktree shares them");
+    process::exit(1);
+}
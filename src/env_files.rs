@@ -0,0 +1,61 @@
+//! Per-worktree files such as `.env.local`, rendered from templates when a
+//! worktree is created.
+//!
+//! Templates may use `{{branch}}`, `{{project}}` (the branch made safe for
+//! names like databases), `{{path}}`, `{{root}}`, `{{index}}` (the
+//! worktree's stable index, see [`crate::index`]) and `{{port}}`
+//! (`port_base` plus the index). Anything else in double braces is left as
+//! it is.
+
+use crate::hooks::HookContext;
+use crate::{config, index, log_info, shared};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use std::fs;
+
+const DEFAULT_PORT_BASE: u16 = 3000;
+
+/// Writes every configured file into the worktree of `context`.
+pub fn render(context: &HookContext) -> Result<()> {
+    let env_files = &config::get().env_files;
+    if env_files.files.is_empty() {
+        return Ok(());
+    }
+
+    let index = index::assign(context.path)?;
+    let port = env_files
+        .port_base
+        .unwrap_or(DEFAULT_PORT_BASE)
+        .saturating_add(u16::try_from(index).unwrap_or(u16::MAX));
+
+    for (target, source) in &env_files.files {
+        let source = context.path.join(config::expand_tilde(source));
+        let Ok(template) = fs::read_to_string(&source) else {
+            log_info(&format!(
+                "Note: {} doesn't exist, not writing {}",
+                source.display(),
+                target.display()
+            ));
+            continue;
+        };
+
+        let rendered = template
+            .replace("{{branch}}", context.branch)
+            .replace("{{project}}", &context.project())
+            .replace("{{path}}", &context.path.to_string_lossy())
+            .replace("{{root}}", &context.root.to_string_lossy())
+            .replace("{{index}}", &index.to_string())
+            .replace("{{port}}", &port.to_string());
+
+        let target = context.path.join(target);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, rendered)
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+        shared::state_file(&target);
+        log_info(&format!("Rendered {}", target.display()));
+    }
+
+    Ok(())
+}
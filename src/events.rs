@@ -0,0 +1,117 @@
+//! Machine-readable progress events for long operations (`--events ndjson`).
+//!
+//! Every event is one JSON object per line with an `event` of `started`,
+//! `progress`, `finished` or `error` and the `operation` it belongs to.
+//! Progress events carry a `percent` and a `message` naming the current item.
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum EventFormat {
+    Ndjson,
+}
+
+struct Stream {
+    output: Box<dyn Write + Send>,
+    operation: Option<&'static str>,
+}
+
+static STREAM: OnceLock<Mutex<Stream>> = OnceLock::new();
+
+#[derive(Serialize)]
+struct Line<'a> {
+    event: &'a str,
+    operation: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percent: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+}
+
+/// Enables events, written to file descriptor `fd`.
+pub fn init(format: EventFormat, fd: u32) -> io::Result<()> {
+    let EventFormat::Ndjson = format;
+    let output: Box<dyn Write + Send> = match fd {
+        1 => Box::new(io::stdout()),
+        2 => Box::new(io::stderr()),
+        fd => Box::new(open_fd(fd)?),
+    };
+
+    let _ = STREAM.set(Mutex::new(Stream {
+        output,
+        operation: None,
+    }));
+    Ok(())
+}
+
+#[cfg(unix)]
+fn open_fd(fd: u32) -> io::Result<File> {
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(format!("/dev/fd/{fd}"))
+}
+
+#[cfg(not(unix))]
+fn open_fd(fd: u32) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Only file descriptors 1 and 2 are supported here, not {fd}"),
+    ))
+}
+
+pub fn started(operation: &'static str) {
+    with_stream(|stream| {
+        stream.operation = Some(operation);
+        write(stream, "started", None, None);
+    });
+}
+
+/// Reports that `done` of `total` items are complete, `message` naming the
+/// last one.
+pub fn progress(done: usize, total: usize, message: &str) {
+    with_stream(|stream| {
+        let percent = (done * 100).checked_div(total).unwrap_or(100);
+        write(stream, "progress", Some(percent), Some(message));
+    });
+}
+
+pub fn finished() {
+    with_stream(|stream| {
+        write(stream, "finished", None, None);
+        stream.operation = None;
+    });
+}
+
+/// Reports an error in the running operation, if any.
+pub fn error(message: &str) {
+    with_stream(|stream| write(stream, "error", None, Some(message)));
+}
+
+fn with_stream(f: impl FnOnce(&mut Stream)) {
+    if let Some(stream) = STREAM.get() {
+        let mut stream = stream
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        f(&mut stream);
+    }
+}
+
+fn write(stream: &mut Stream, event: &str, percent: Option<usize>, message: Option<&str>) {
+    let Some(operation) = stream.operation else {
+        return;
+    };
+    let line = Line {
+        event,
+        operation,
+        percent,
+        message,
+    };
+    if let Ok(line) = serde_json::to_string(&line) {
+        let _ = writeln!(stream.output, "{line}");
+        let _ = stream.output.flush();
+    }
+}
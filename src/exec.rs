@@ -0,0 +1,236 @@
+//! `exec`: runs a command in one worktree or, with `--all`, in every one.
+//!
+//! Commands get the same `GIT_WT_*` variables as hooks. With `--all` they
+//! run one after another with their output streamed, or `--parallel` at a
+//! time with every line prefixed by its branch. `--capture` buffers each
+//! worktree's output and prints it in one piece when the command finished;
+//! `--json` prints the results as JSON instead.
+
+use crate::hooks::HookContext;
+use crate::summary::{Status, Summary};
+use crate::{
+    FailureArgs, check_git_repo, events, finish_summary, get_all_worktrees, get_worktree_root,
+    git_env, log_info, notify, process, resolve_worktree,
+};
+use clap::Args;
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::thread;
+
+#[derive(Args)]
+pub struct ExecArgs {
+    /// Branch name of the worktree to run in (defaults to current worktree)
+    #[arg(conflicts_with = "all")]
+    pub branch: Option<String>,
+    /// Run in every worktree
+    #[arg(short, long)]
+    pub all: bool,
+    /// Run in this many worktrees at once
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "N",
+        default_value = "1",
+        requires = "all"
+    )]
+    pub parallel: NonZeroUsize,
+    /// Print each worktree's output in one piece once its command finished
+    #[arg(long, requires = "all")]
+    pub capture: bool,
+    /// Print the results, including output and exit codes, as JSON
+    #[arg(long, requires = "all")]
+    pub json: bool,
+    #[command(flatten)]
+    pub failures: FailureArgs,
+    /// Command and its arguments
+    #[arg(last = true, required = true, value_name = "COMMAND")]
+    pub command: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Outcome {
+    branch: String,
+    path: String,
+    /// `None` if the command was killed by a signal or couldn't be started
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stderr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub fn run(args: &ExecArgs) -> Result<()> {
+    check_git_repo();
+    let root = get_worktree_root()?;
+
+    if !args.all {
+        let (branch, path) = resolve_worktree(args.branch.as_deref())?;
+        let status = command(&args.command, &branch, Path::new(&path), &root)
+            .status()
+            .with_context(|| format!("Failed to run {}", args.command[0]))?;
+        process::exit(status.code().unwrap_or(1));
+    }
+
+    events::started("exec");
+    notify::started("exec --all");
+    let worktrees = get_all_worktrees()?;
+    let queue = Mutex::new(worktrees.iter());
+    let outcomes = Mutex::new(Vec::new());
+    let failed = Mutex::new(false);
+    let capture = args.capture || args.json;
+
+    thread::scope(|scope| {
+        for _ in 0..args.parallel.get().min(worktrees.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    if args.failures.fail_fast && *lock(&failed) {
+                        return;
+                    }
+                    let Some((branch, path)) = lock(&queue).next() else {
+                        return;
+                    };
+
+                    let outcome = run_one(args, branch, path, &root, capture);
+                    if outcome.exit_code != Some(0) {
+                        *lock(&failed) = true;
+                    }
+                    if capture && !args.json {
+                        print_captured(&outcome);
+                    }
+                    lock(&outcomes).push(outcome);
+                }
+            });
+        }
+    });
+
+    let mut outcomes = outcomes.into_inner().unwrap_or_default();
+    // Report in list order, not in the order the commands finished
+    outcomes.sort_by_key(|outcome| {
+        worktrees
+            .iter()
+            .position(|(branch, _)| *branch == outcome.branch)
+    });
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&outcomes)?);
+        if outcomes.iter().any(|outcome| outcome.exit_code != Some(0)) {
+            process::exit(1);
+        }
+    } else {
+        let mut summary = Summary::default();
+        for outcome in &outcomes {
+            match (outcome.exit_code, &outcome.error) {
+                (Some(0), _) => summary.push(&outcome.branch, Status::Ok, ""),
+                (_, Some(error)) => summary.push(&outcome.branch, Status::Failed, error.as_str()),
+                (Some(code), None) => {
+                    summary.push(&outcome.branch, Status::Failed, format!("exit code {code}"));
+                }
+                (None, None) => summary.push(&outcome.branch, Status::Failed, "killed"),
+            }
+        }
+        finish_summary(&summary);
+    }
+    Ok(())
+}
+
+fn run_one(args: &ExecArgs, branch: &str, path: &str, root: &Path, capture: bool) -> Outcome {
+    let mut command = command(&args.command, branch, Path::new(path), root);
+    let result = if capture {
+        command.stdin(Stdio::null()).output().map(|output| {
+            (
+                output.status,
+                Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+                Some(String::from_utf8_lossy(&output.stderr).into_owned()),
+            )
+        })
+    } else if args.parallel.get() > 1 {
+        run_prefixed(&mut command, branch).map(|status| (status, None, None))
+    } else {
+        log_info(&format!("==> {branch}"));
+        command.status().map(|status| (status, None, None))
+    };
+
+    let (exit_code, stdout, stderr, error) = match result {
+        Ok((status, stdout, stderr)) => (status.code(), stdout, stderr, None),
+        Err(e) => (None, None, None, Some(format!("{}: {e}", args.command[0]))),
+    };
+    Outcome {
+        branch: branch.to_string(),
+        path: path.to_string(),
+        exit_code,
+        stdout,
+        stderr,
+        error,
+    }
+}
+
+fn command(argv: &[String], branch: &str, path: &Path, root: &Path) -> Command {
+    let context = HookContext {
+        branch,
+        path,
+        root,
+        base: None,
+    };
+    let mut command = context.command(&argv[0]);
+    git_env::in_dir(&mut command, path).args(&argv[1..]);
+    command
+}
+
+/// Runs `command`, printing each line of its output prefixed with `branch`
+/// so that output of parallel commands stays attributable.
+fn run_prefixed(command: &mut Command, branch: &str) -> io::Result<ExitStatus> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    thread::scope(|scope| {
+        if let Some(stdout) = stdout {
+            scope.spawn(|| forward(stdout, branch, &mut io::stdout()));
+        }
+        if let Some(stderr) = stderr {
+            scope.spawn(|| forward(stderr, branch, &mut io::stderr()));
+        }
+    });
+    child.wait()
+}
+
+fn forward(from: impl Read, branch: &str, to: &mut impl Write) {
+    for line in BufReader::new(from).lines().map_while(Result::ok) {
+        let _ = writeln!(to, "[{branch}] {line}");
+    }
+}
+
+fn print_captured(outcome: &Outcome) {
+    // Hold both streams so groups of parallel commands don't mix
+    let mut stdout = io::stdout().lock();
+    let mut stderr = io::stderr().lock();
+    let _ = writeln!(stderr, "==> {}", outcome.branch);
+    if let Some(output) = &outcome.stdout {
+        let _ = write!(stdout, "{output}");
+        let _ = stdout.flush();
+    }
+    if let Some(output) = &outcome.stderr {
+        let _ = write!(stderr, "{output}");
+    }
+    if let Some(error) = &outcome.error {
+        let _ = writeln!(stderr, "{error}");
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
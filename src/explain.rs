@@ -0,0 +1,68 @@
+//! `--explain`: describe what `add`, `rm` and `clean` are about to do and
+//! ask before doing it.
+//!
+//! Commands collect the refs they found, the git commands they will run and
+//! the hooks that will fire into a [`Plan`] once everything is resolved but
+//! before anything changed. Without a terminal the plan is printed and
+//! nothing runs, which makes `--explain` a dry run in scripts.
+
+use crate::accessible::Ask;
+use crate::hooks::{self, HookContext, HookEvent};
+use crate::{config, create_confirm_render_config, log_info, process, prompt_result};
+use color_eyre::Result;
+use inquire::Confirm;
+
+#[derive(Default)]
+pub struct Plan {
+    lines: Vec<String>,
+}
+
+impl Plan {
+    /// Something that was found or decided, e.g. which base will be used.
+    pub fn note(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+    }
+
+    pub fn command(&mut self, program: &str, args: &[&str]) {
+        self.lines
+            .push(format!("run: {program} {}", args.join(" ")));
+    }
+
+    pub fn hooks(&mut self, event: HookEvent, context: &HookContext) {
+        for line in hooks::describe(event, context) {
+            self.lines.push(format!("run: {line}"));
+        }
+    }
+}
+
+pub fn enabled() -> bool {
+    config::get().explain.unwrap_or(false)
+}
+
+/// Prints `plan` and asks whether to go ahead, exiting if not. Returns
+/// whether the plan was confirmed, which is `false` when `--explain` isn't
+/// given and nothing was asked.
+pub fn confirm(plan: &Plan) -> Result<bool> {
+    if !enabled() {
+        return Ok(false);
+    }
+
+    log_info("Plan:");
+    for line in &plan.lines {
+        log_info(&format!("  {line}"));
+    }
+
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_info(&format!("Not running anything because {reason}."));
+        process::exit(0);
+    }
+    let answer = Confirm::new("")
+        .with_default(true)
+        .with_render_config(create_confirm_render_config("Proceed?"))
+        .ask();
+    if prompt_result(answer)? != Some(true) {
+        eprintln!("Cancelled.");
+        process::exit(0);
+    }
+    Ok(true)
+}
@@ -0,0 +1,173 @@
+//! How dates and sizes look in output, the same in every command.
+//!
+//! Dates are relative ("3 days ago") unless `--iso` or `dates = "iso"` asks
+//! for `YYYY-MM-DDTHH:MM:SS+HH:MM` in local time, or UTC with `--utc`.
+//! Sizes use binary units.
+
+use crate::config::{self, DateStyle};
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// `time` (Unix seconds) as configured: relative or ISO.
+pub fn date(time: u64) -> String {
+    let config = config::get();
+    match config.dates.unwrap_or_default() {
+        DateStyle::Relative => relative(time, now()),
+        DateStyle::Iso if config.utc() => iso(time, 0),
+        DateStyle::Iso => iso(time, local_offset()),
+    }
+}
+
+/// How long before `now` `time` was, e.g. "3 days ago".
+pub fn relative(time: u64, now: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    if time > now {
+        return "in the future".to_string();
+    }
+    let elapsed = now - time;
+    let (count, unit) = match elapsed {
+        0..MINUTE => return "just now".to_string(),
+        MINUTE..HOUR => (elapsed / MINUTE, "minute"),
+        HOUR..DAY => (elapsed / HOUR, "hour"),
+        _ if elapsed < 14 * DAY => (elapsed / DAY, "day"),
+        _ if elapsed < 60 * DAY => (elapsed / (7 * DAY), "week"),
+        _ if elapsed < 365 * DAY => (elapsed / (30 * DAY), "month"),
+        _ => (elapsed / (365 * DAY), "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+/// `time` as `YYYY-MM-DDTHH:MM:SS+HH:MM`, `offset` seconds east of UTC, or
+/// with `Z` for UTC.
+pub fn iso(time: u64, offset: i64) -> String {
+    let local = i64::try_from(time).unwrap_or_default() + offset;
+    let days = local.div_euclid(86_400);
+    let seconds = local.rem_euclid(86_400);
+    let (year, month, day) = civil(days);
+    let zone = if offset == 0 {
+        "Z".to_string()
+    } else {
+        let sign = if offset < 0 { '-' } else { '+' };
+        let offset = offset.abs();
+        format!("{sign}{:02}:{:02}", offset / 3600, offset % 3600 / 60)
+    };
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}{zone}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// The date `days` after 1970-01-01 as `(year, month, day)`.
+fn civil(days: i64) -> (i64, i64, i64) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Seconds the local time zone is east of UTC, as `date` reports it; UTC
+/// where it can't tell.
+fn local_offset() -> i64 {
+    static OFFSET: OnceLock<i64> = OnceLock::new();
+    *OFFSET.get_or_init(|| {
+        let Ok(output) = Command::new("date").arg("+%z").output() else {
+            return 0;
+        };
+        // "+0200" or "-0530"
+        let offset = String::from_utf8_lossy(&output.stdout);
+        let offset = offset.trim();
+        let (sign, digits) = match offset.split_at_checked(1) {
+            Some(("-", digits)) => (-1, digits),
+            Some(("+", digits)) => (1, digits),
+            _ => return 0,
+        };
+        let (Some(hours), Some(minutes)) = (
+            digits.get(..2).and_then(|hours| hours.parse::<i64>().ok()),
+            digits
+                .get(2..4)
+                .and_then(|minutes| minutes.parse::<i64>().ok()),
+        ) else {
+            return 0;
+        };
+        sign * (hours * 3600 + minutes * 60)
+    })
+}
+
+/// `size` in bytes as a short human-readable string, e.g. "1.5 GiB".
+pub fn size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut value = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{value:.1} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_dates_pick_the_largest_fitting_unit() {
+        let now = 1_000_000_000;
+        let cases = [
+            (now, "just now"),
+            (now - 60, "1 minute ago"),
+            (now - 3 * 3600, "3 hours ago"),
+            (now - 3 * 86_400, "3 days ago"),
+            (now - 21 * 86_400, "3 weeks ago"),
+            (now - 90 * 86_400, "3 months ago"),
+            (now - 800 * 86_400, "2 years ago"),
+            (now + 10, "in the future"),
+        ];
+        for (time, expected) in cases {
+            assert_eq!(relative(time, now), expected);
+        }
+    }
+
+    #[test]
+    fn iso_dates_in_utc_and_with_an_offset() {
+        // 2001-09-09 01:46:40 UTC
+        let time = 1_000_000_000;
+        assert_eq!(iso(time, 0), "2001-09-09T01:46:40Z");
+        assert_eq!(iso(time, -5 * 3600 - 1800), "2001-09-08T20:16:40-05:30");
+    }
+
+    #[test]
+    fn sizes_use_binary_units() {
+        assert_eq!(size(512), "512 B");
+        assert_eq!(size(1536), "1.5 KiB");
+        assert_eq!(size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
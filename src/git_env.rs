@@ -0,0 +1,66 @@
+//! `GIT_DIR`, `GIT_WORK_TREE` and the other variables git locates the
+//! repository with.
+//!
+//! Git sets them for hooks, and IDE tasks and scripts set them too. They
+//! take precedence over the current directory, so git-wt honors them for the
+//! repository it was called in, as git does, and finds the repository
+//! through git instead of its `.git` shortcut while they're set. Commands run
+//! in a particular worktree are started without them, as they would
+//! otherwise act on the caller's worktree instead.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Variables naming the repository, worktree or index to use; a relative
+/// value is relative to the current directory.
+const LOCATING: [&str; 4] = [
+    "GIT_DIR",
+    "GIT_WORK_TREE",
+    "GIT_COMMON_DIR",
+    "GIT_INDEX_FILE",
+];
+
+/// Whether any variable overrides where git looks for the repository,
+/// including `GIT_CEILING_DIRECTORIES` limiting the search.
+pub fn overridden() -> bool {
+    LOCATING
+        .iter()
+        .chain(&["GIT_CEILING_DIRECTORIES"])
+        .any(|var| env::var_os(var).is_some())
+}
+
+/// Makes relative values absolute, so they keep naming the same repository
+/// after git-wt changes directory for `--root` or `GIT_PREFIX`. Must run
+/// before any other thread is started.
+pub fn absolutize() {
+    let Ok(cwd) = env::current_dir() else {
+        return;
+    };
+    for var in LOCATING {
+        if let Some(value) = env::var_os(var).filter(|value| !value.is_empty())
+            && Path::new(&value).is_relative()
+        {
+            let absolute: OsString = cwd.join(value).into();
+            // SAFETY: called first thing in main, while no other thread could
+            // read the environment
+            unsafe { env::set_var(var, absolute) };
+        }
+    }
+}
+
+/// The worktree `GIT_WORK_TREE` names, if set.
+pub fn work_tree() -> Option<PathBuf> {
+    env::var_os("GIT_WORK_TREE")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Runs `command` in `dir`, letting git find the repository from there.
+pub fn in_dir(command: &mut Command, dir: impl AsRef<Path>) -> &mut Command {
+    for var in LOCATING {
+        command.env_remove(var);
+    }
+    command.current_dir(dir)
+}
@@ -0,0 +1,144 @@
+//! `grep`: `git grep` in every worktree at once.
+//!
+//! Matches are printed as `<branch>:<file>:<line>:<text>`, grouped by
+//! worktree in list order. Like grep, exits 1 when nothing matched.
+
+use crate::{check_git_repo, get_all_worktrees, git_env, list, log_error, process};
+use clap::Args;
+use color_eyre::Result;
+use crossterm::style::Stylize;
+use std::num::NonZeroUsize;
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct GrepArgs {
+    /// Pattern to search for (a git grep basic regular expression)
+    pub pattern: String,
+    /// Only search worktrees whose branch matches this glob (`*` and `?`)
+    #[arg(short, long, value_name = "GLOB")]
+    pub branch: Option<String>,
+    /// Ignore case
+    #[arg(short, long)]
+    pub ignore_case: bool,
+    /// Match whole words only
+    #[arg(short, long)]
+    pub word_regexp: bool,
+    /// Treat the pattern as an extended regular expression
+    #[arg(short = 'E', long)]
+    pub extended_regexp: bool,
+    /// Only print the names of files with matches
+    #[arg(short = 'l', long)]
+    pub files_with_matches: bool,
+    /// Search this many worktrees at once (defaults to the number of CPUs)
+    #[arg(short = 'j', long, value_name = "N")]
+    pub parallel: Option<NonZeroUsize>,
+    /// Limit the search to these paths
+    #[arg(last = true, value_name = "PATHSPEC")]
+    pub pathspecs: Vec<String>,
+}
+
+pub fn run(args: &GrepArgs) -> Result<()> {
+    check_git_repo();
+    let worktrees: Vec<(String, String)> = get_all_worktrees()?
+        .into_iter()
+        .filter(|(branch, _)| {
+            args.branch
+                .as_deref()
+                .is_none_or(|pattern| list::glob_match(pattern, branch))
+        })
+        .collect();
+
+    let parallel = args
+        .parallel
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    let queue = Mutex::new(worktrees.iter().enumerate());
+    let results = Mutex::new(vec![None; worktrees.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..parallel.min(worktrees.len()) {
+            scope.spawn(|| {
+                loop {
+                    let next = queue
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner)
+                        .next();
+                    let Some((i, (_, path))) = next else {
+                        return;
+                    };
+                    let output = grep(args, path);
+                    results
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner)[i] = Some(output);
+                }
+            });
+        }
+    });
+
+    let colored = list::colored();
+    let mut matched = false;
+    let mut failed = false;
+    let results = results
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    for ((branch, _), output) in worktrees.iter().zip(results) {
+        match output {
+            Some(Ok(Some(lines))) => {
+                matched = true;
+                let prefix = if colored {
+                    branch.as_str().magenta().to_string()
+                } else {
+                    branch.clone()
+                };
+                for line in lines.lines() {
+                    println!("{prefix}:{line}");
+                }
+            }
+            Some(Ok(None)) | None => {}
+            Some(Err(message)) => {
+                failed = true;
+                log_error(&format!("{branch}: {message}"));
+            }
+        }
+    }
+
+    if failed {
+        process::exit(2);
+    }
+    if !matched {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Runs git grep in the worktree at `path`: its output if something matched,
+/// `None` if nothing did.
+fn grep(args: &GrepArgs, path: &str) -> Result<Option<String>, String> {
+    let mut command = Command::new("git");
+    git_env::in_dir(&mut command, path).args(["grep", "-n", "--no-color"]);
+    for (set, flag) in [
+        (args.ignore_case, "--ignore-case"),
+        (args.word_regexp, "--word-regexp"),
+        (args.extended_regexp, "--extended-regexp"),
+        (args.files_with_matches, "--files-with-matches"),
+    ] {
+        if set {
+            command.arg(flag);
+        }
+    }
+    command
+        .arg("-e")
+        .arg(&args.pattern)
+        .arg("--")
+        .args(&args.pathspecs);
+
+    let output = command.output().map_err(|e| e.to_string())?;
+    match output.status.code() {
+        Some(0) => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+        Some(1) if output.stderr.is_empty() => Ok(None),
+        _ => Err(crate::failure_reason(&output.stderr)),
+    }
+}
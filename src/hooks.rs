@@ -0,0 +1,462 @@
+//! User-defined commands run around worktree operations.
+//!
+//! Hook commands are run through the shell with the worktree as working
+//! directory (the worktree root for `pre_add` and `post_remove`). Besides
+//! the configured hooks, `add` and `rm` take one-off commands with
+//! `--before` and `--after`, which run after the configured ones, even when
+//! those are disabled. `{branch}`, `{path}`,
+//! `{root}`, `{project}` and `{index}` in a command are replaced with the
+//! values of the worktree, quoted for the shell (so they must not be quoted
+//! again in the command); the same values are exported as `GIT_WT_BRANCH`,
+//! `GIT_WT_PATH`, `GIT_WT_ROOT`, `GIT_WT_PROJECT` and `GIT_WT_INDEX`.
+//! Hook commands also get them as a JSON object on stdin, along with the
+//! operation, the hook, the ref a new branch starts from and `dry_run`, for
+//! scripts that would rather parse one document than a growing set of
+//! variables. `dry_run` is `true` under `--explain`, where hooks only run
+//! once the plan was confirmed.
+//!
+//! Git's own hooks (pre-commit, commit-msg, ...) are shared by all worktrees
+//! through `core.hooksPath`, see [`install`].
+
+use crate::{check_git_repo, git_common_dir, git_stdout, log_error, log_info, run_command};
+use crate::{config, doctor, explain, git_env, index};
+use clap::Args;
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    PreAdd,
+    PostAdd,
+    PreRemove,
+    PostRemove,
+}
+
+impl HookEvent {
+    /// The hook's key in `[hooks]`.
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::PreAdd => "pre_add",
+            HookEvent::PostAdd => "post_add",
+            HookEvent::PreRemove => "pre_remove",
+            HookEvent::PostRemove => "post_remove",
+        }
+    }
+
+    fn operation(self) -> &'static str {
+        match self {
+            HookEvent::PreAdd | HookEvent::PostAdd => "add",
+            HookEvent::PreRemove | HookEvent::PostRemove => "remove",
+        }
+    }
+}
+
+pub struct HookContext<'a> {
+    pub branch: &'a str,
+    pub path: &'a Path,
+    pub root: &'a Path,
+    /// The ref a newly created branch starts from
+    pub base: Option<&'a str>,
+}
+
+/// What hook commands read from stdin.
+#[derive(Serialize)]
+struct Payload<'a> {
+    operation: &'static str,
+    hook: &'static str,
+    branch: &'a str,
+    path: &'a Path,
+    root: &'a Path,
+    base: Option<&'a str>,
+    project: String,
+    index: Option<u32>,
+    dry_run: bool,
+}
+
+impl HookContext<'_> {
+    /// Name usable as a docker compose project: lowercase alphanumerics,
+    /// `-` and `_`.
+    pub fn project(&self) -> String {
+        self.branch
+            .chars()
+            .map(|c| match c.to_ascii_lowercase() {
+                c @ ('a'..='z' | '0'..='9' | '-' | '_') => c,
+                _ => '-',
+            })
+            .collect()
+    }
+
+    /// The worktree's stable index, empty if it has none.
+    pub fn index(&self) -> String {
+        index::lookup(self.path)
+            .map(|index| index.to_string())
+            .unwrap_or_default()
+    }
+
+    /// `template` with the placeholders replaced by the worktree's values.
+    pub fn expand(&self, template: &str) -> String {
+        self.expand_with(template, str::to_string)
+    }
+
+    /// Like [`expand`](Self::expand), for a command line run by the shell:
+    /// every value is quoted, so a branch named e.g. `x;rm -rf ~` stays one
+    /// word instead of running anything.
+    pub fn expand_quoted(&self, template: &str) -> String {
+        self.expand_with(template, shell_quote)
+    }
+
+    /// `template` with each value passed through `quote`. The placeholders
+    /// are replaced in one pass, so a value that happens to contain `{path}`
+    /// isn't expanded again.
+    pub fn expand_with(&self, template: &str, quote: fn(&str) -> String) -> String {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            expanded.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = rest.find('}').and_then(|end| {
+                let value = match &rest[1..end] {
+                    "branch" => self.branch.to_string(),
+                    "path" => self.path.to_string_lossy().into_owned(),
+                    "root" => self.root.to_string_lossy().into_owned(),
+                    "project" => self.project(),
+                    "index" => self.index(),
+                    _ => return None,
+                };
+                Some((value, end))
+            });
+            if let Some((value, end)) = value {
+                expanded.push_str(&quote(&value));
+                rest = &rest[end + 1..];
+            } else {
+                expanded.push('{');
+                rest = &rest[1..];
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+
+    /// `program` with the worktree's values exported.
+    pub fn command(&self, program: &str) -> Command {
+        let mut command = Command::new(program);
+        command
+            .env("GIT_WT_BRANCH", self.branch)
+            .env("GIT_WT_PATH", self.path)
+            .env("GIT_WT_ROOT", self.root)
+            .env("GIT_WT_PROJECT", self.project())
+            .env("GIT_WT_INDEX", self.index());
+        command
+    }
+
+    /// `command` run through the shell, with the worktree's values exported.
+    pub fn shell(&self, command: &str) -> Command {
+        let mut shell = if cfg!(windows) {
+            let mut shell = self.command("cmd");
+            shell.arg("/C");
+            shell
+        } else {
+            let mut shell = self.command("sh");
+            shell.arg("-c");
+            shell
+        };
+        shell.arg(command);
+        shell
+    }
+}
+
+/// `value` as a single word for the shell [`HookContext::shell`] runs:
+/// single-quoted for `sh`, double-quoted for `cmd`, which has no quotes that
+/// keep everything literal but can't find a `"` in a Windows path either.
+pub fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        sh_quote(value)
+    }
+}
+
+/// `value` single-quoted, as one word for `sh` and the shells `init` wraps.
+pub fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// One-off hook commands for a single `add` or `rm`.
+#[derive(Args, Clone, Default)]
+pub struct InlineHooks {
+    /// Shell command to run before the operation, like a `pre_add` or
+    /// `pre_remove` hook (repeatable)
+    #[arg(long = "before", value_name = "COMMAND")]
+    pub before: Vec<String>,
+    /// Shell command to run after the operation, like a `post_add` or
+    /// `post_remove` hook (repeatable)
+    #[arg(long = "after", value_name = "COMMAND")]
+    pub after: Vec<String>,
+}
+
+static INLINE: OnceLock<InlineHooks> = OnceLock::new();
+
+/// Sets the `--before` and `--after` commands of this invocation.
+pub fn set_inline(hooks: InlineHooks) {
+    let _ = INLINE.set(hooks);
+}
+
+/// The commands for `event`, each with the name of the hook it comes from:
+/// the configured ones unless hooks are disabled, then the inline ones.
+fn commands(event: HookEvent) -> Vec<(&'static str, &'static str)> {
+    let config = config::get();
+    let hooks = &config.hooks;
+    let name = event.name();
+    let configured = match event {
+        HookEvent::PreAdd => &hooks.pre_add,
+        HookEvent::PostAdd => &hooks.post_add,
+        HookEvent::PreRemove => &hooks.pre_remove,
+        HookEvent::PostRemove => &hooks.post_remove,
+    };
+    let mut commands: Vec<(&str, &str)> = if hooks.enabled.unwrap_or(true) {
+        configured
+            .iter()
+            .flatten()
+            .map(|command| (name, command.as_str()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    if let Some(inline) = INLINE.get() {
+        let (name, extra) = match event {
+            HookEvent::PreAdd | HookEvent::PreRemove => ("--before", &inline.before),
+            HookEvent::PostAdd | HookEvent::PostRemove => ("--after", &inline.after),
+        };
+        commands.extend(extra.iter().map(|command| (name, command.as_str())));
+    }
+    commands
+}
+
+/// Runs the hooks for `event`, exiting if one of them fails.
+pub fn run(event: HookEvent, context: &HookContext) -> Result<()> {
+    let enabled = config::get().hooks.enabled.unwrap_or(true);
+    let commands = commands(event);
+    let cwd = match event {
+        HookEvent::PostAdd | HookEvent::PreRemove => context.path,
+        HookEvent::PreAdd | HookEvent::PostRemove => context.root,
+    };
+
+    // Bring the compose stack down only once the worktree is gone, as
+    // removing it can still fail, but before any user hook tears down what
+    // the stack uses
+    if event == HookEvent::PostRemove && enabled {
+        run_compose(context, &["down", "-v"])?;
+    }
+
+    let payload = serde_json::to_string(&Payload {
+        operation: event.operation(),
+        hook: event.name(),
+        branch: context.branch,
+        path: context.path,
+        root: context.root,
+        base: context.base,
+        project: context.project(),
+        index: index::lookup(context.path),
+        dry_run: explain::enabled(),
+    })?;
+    for (_, command) in commands {
+        let command = context.expand_quoted(command);
+        log_info(&format!("Running hook: {command}"));
+
+        let mut child = git_env::in_dir(&mut context.shell(&command), cwd)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run hook: {command}"))?;
+        // Hooks that don't read stdin may have exited already
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = writeln!(stdin, "{payload}");
+        }
+        let status = child
+            .wait()
+            .with_context(|| format!("Failed to run hook: {command}"))?;
+
+        if !status.success() {
+            log_error(&format!("Hook failed: {command}"));
+            process::exit(1);
+        }
+    }
+
+    if event == HookEvent::PostAdd && enabled {
+        run_compose(context, &["up", "-d"])?;
+    }
+
+    Ok(())
+}
+
+/// What [`run`] would do for `event`, one line per command, for `--explain`.
+pub fn describe(event: HookEvent, context: &HookContext) -> Vec<String> {
+    let config = config::get();
+    let mut lines: Vec<String> = commands(event)
+        .into_iter()
+        .map(|(name, command)| format!("{name} hook: {}", context.expand_quoted(command)))
+        .collect();
+
+    let compose = &config.compose;
+    if compose.enabled.unwrap_or(false) && config.hooks.enabled.unwrap_or(true) {
+        let project = context.expand(compose.project.as_deref().unwrap_or("{project}"));
+        match event {
+            HookEvent::PostRemove => {
+                lines.insert(0, format!("docker compose -p {project} down -v"));
+            }
+            HookEvent::PostAdd => lines.push(format!("docker compose -p {project} up -d")),
+            HookEvent::PreAdd | HookEvent::PreRemove => {}
+        }
+    }
+    lines
+}
+
+/// Runs `docker compose -p <project> <args>` when the compose integration is
+/// enabled: in the worktree, or in the root once it's removed, where compose
+/// finds the project's containers by name instead of through its files.
+fn run_compose(context: &HookContext, args: &[&str]) -> Result<()> {
+    let compose = &config::get().compose;
+    if !compose.enabled.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let project = context.expand(compose.project.as_deref().unwrap_or("{project}"));
+    let mut command = context.command("docker");
+    command.args(["compose", "-p", &project]);
+    let dir = if context.path.exists() {
+        for file in compose.files.iter().flatten() {
+            command.args(["-f", &context.expand(file)]);
+        }
+        context.path
+    } else {
+        context.root
+    };
+    command.args(args);
+
+    log_info(&format!(
+        "Running docker compose {} for project '{project}'",
+        args.join(" ")
+    ));
+    let status = git_env::in_dir(&mut command, dir)
+        .status()
+        .context("Failed to run docker compose")?;
+
+    if !status.success() {
+        log_error(&format!("docker compose {} failed", args.join(" ")));
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Points `core.hooksPath` at `<git-common-dir>/git-wt/hooks`, so git hooks
+/// apply to every worktree. Hooks already in the repository's `hooks`
+/// directory are copied over. With `per_worktree` only the current worktree
+/// is configured.
+pub fn install(per_worktree: bool) -> Result<()> {
+    check_git_repo();
+    let common_dir = git_common_dir().context("Failed to locate the git directory")?;
+    let hooks_dir = common_dir.join("git-wt").join("hooks");
+    fs::create_dir_all(&hooks_dir).context("Failed to create hooks directory")?;
+
+    if let Ok(entries) = fs::read_dir(common_dir.join("hooks")) {
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let target = hooks_dir.join(&name);
+            let is_sample = Path::new(&name)
+                .extension()
+                .is_some_and(|ext| ext == "sample");
+            if entry.file_type().is_ok_and(|t| t.is_file()) && !is_sample && !target.exists() {
+                // fs::copy keeps the executable bit
+                fs::copy(entry.path(), &target)
+                    .with_context(|| format!("Failed to copy hook {}", entry.path().display()))?;
+                log_info(&format!("Copied hook {}", name.to_string_lossy()));
+            }
+        }
+    }
+
+    let hooks_path = hooks_dir.to_string_lossy();
+    if per_worktree {
+        doctor::require_git((2, 20), "hooks install --worktree");
+        // Worktree-specific settings live in config.worktree once enabled
+        run_command(
+            "git",
+            &["config", "extensions.worktreeConfig", "true"],
+            None,
+        )?;
+        move_bare_setting(&common_dir)?;
+        run_command(
+            "git",
+            &["config", "--worktree", "core.hooksPath", &hooks_path],
+            None,
+        )?;
+    } else {
+        run_command("git", &["config", "core.hooksPath", &hooks_path], None)?;
+    }
+
+    log_info(&format!("Git hooks are read from {hooks_path}"));
+    Ok(())
+}
+
+/// Moves `core.bare` from the shared config into the bare repository's own
+/// `config.worktree`. With `extensions.worktreeConfig` enabled git would
+/// otherwise treat every linked worktree as bare too.
+fn move_bare_setting(common_dir: &Path) -> Result<()> {
+    let shared = common_dir.join("config");
+    let own = common_dir.join("config.worktree");
+    let shared = shared.to_string_lossy();
+    let own = own.to_string_lossy();
+
+    let bare = git_stdout(&["config", "--file", &shared, "core.bare"], None);
+    if bare.as_deref() == Some("true") {
+        run_command(
+            "git",
+            &["config", "--file", &own, "core.bare", "true"],
+            None,
+        )?;
+        run_command(
+            "git",
+            &["config", "--file", &shared, "--unset", "core.bare"],
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context<'a>(branch: &'a str, path: &'a Path) -> HookContext<'a> {
+        HookContext {
+            branch,
+            path,
+            root: Path::new("/repo"),
+            base: None,
+        }
+    }
+
+    #[test]
+    fn quoted_values_stay_one_word() {
+        let path = Path::new("/repo/it's");
+        let context = context("x;rm -rf ~", path);
+        assert_eq!(
+            context.expand_quoted("echo {branch} {path} {other}"),
+            r"echo 'x;rm -rf ~' '/repo/it'\''s' {other}"
+        );
+        assert_eq!(context.expand("{branch}"), "x;rm -rf ~");
+    }
+
+    #[test]
+    fn values_are_not_expanded_twice() {
+        let context = context("{path}", Path::new("/repo/wt"));
+        assert_eq!(context.expand("{branch} {root}"), "{path} /repo");
+    }
+}
@@ -0,0 +1,99 @@
+//! Stable small integers per worktree, so parallel dev servers can derive
+//! distinct ports from them.
+//!
+//! Indexes are assigned when a worktree is added, starting at 1, and kept in
+//! `<git-common-dir>/git-wt/indexes.json` by worktree path. Indexes of
+//! removed worktrees are handed out again.
+//!
+//! Concurrent `add`s take turns through a lock on `indexes.json.lock` and
+//! replace the registry by renaming a complete copy over it, so neither hands
+//! out an index twice nor do readers see a half-written file.
+
+use crate::{get_all_worktrees, git_common_dir, same_path, shared};
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+fn registry_path() -> Result<PathBuf> {
+    let common_dir = git_common_dir().context("Not in a git repository")?;
+    Ok(common_dir.join("git-wt").join("indexes.json"))
+}
+
+fn load() -> Result<BTreeMap<String, u32>> {
+    let path = registry_path()?;
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn save(indexes: &BTreeMap<String, u32>) -> Result<()> {
+    let path = registry_path()?;
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, serde_json::to_string_pretty(indexes)?)
+        .with_context(|| format!("Failed to write {}", temp.display()))?;
+    fs::rename(&temp, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+    shared::state_file(&path);
+    Ok(())
+}
+
+/// Holds the registry lock until dropped.
+fn lock() -> Result<File> {
+    let path = registry_path()?.with_extension("json.lock");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    shared::state_file(&path);
+    file.lock()
+        .with_context(|| format!("Failed to lock {}", path.display()))?;
+    Ok(file)
+}
+
+/// The index of the worktree at `worktree`, if it has one.
+pub fn lookup(worktree: &Path) -> Option<u32> {
+    load()
+        .ok()?
+        .into_iter()
+        .find(|(path, _)| same_path(Path::new(path), worktree))
+        .map(|(_, index)| index)
+}
+
+/// The index of the worktree at `worktree`, assigning the lowest free one if
+/// it has none yet.
+pub fn assign(worktree: &Path) -> Result<u32> {
+    let _lock = lock()?;
+    let mut indexes = load()?;
+
+    // Free the indexes of worktrees that are gone
+    let worktrees = get_all_worktrees()?;
+    indexes.retain(|path, _| {
+        worktrees
+            .iter()
+            .any(|(_, existing)| same_path(Path::new(existing), Path::new(path)))
+    });
+
+    if let Some((_, index)) = indexes
+        .iter()
+        .find(|(path, _)| same_path(Path::new(path), worktree))
+    {
+        return Ok(*index);
+    }
+
+    let index = (1..=u32::MAX)
+        .find(|candidate| !indexes.values().any(|used| used == candidate))
+        .unwrap_or_default();
+    indexes.insert(worktree.to_string_lossy().into_owned(), index);
+    save(&indexes)?;
+    Ok(index)
+}
@@ -0,0 +1,626 @@
+//! `list`: the worktree overview, with sorting, filters and JSON output, and
+//! `watch`, which keeps redrawing it.
+
+use crate::config::{self, ColorMode};
+use crate::{
+    accessible, archive, check_git_repo, doctor, fetch_with_prune, format,
+    get_current_worktree_branch, git_stdout, gone_branches, log_info, owner, worktree,
+};
+use clap::{Args, ValueEnum};
+use color_eyre::Result;
+use crossterm::ExecutableCommand;
+use crossterm::cursor::MoveTo;
+use crossterm::style::Stylize;
+use crossterm::terminal::{Clear, ClearType};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[derive(Args, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ListArgs {
+    /// Group worktrees by branch prefix (feature/, bugfix/, ...)
+    #[arg(long)]
+    pub tree: bool,
+    /// Only show the groups and how many worktrees they hold
+    #[arg(long, requires = "tree")]
+    pub collapse: bool,
+    /// Order worktrees by name, age (oldest first), activity (latest commit
+    /// first) or size (largest first)
+    #[arg(long, value_enum)]
+    pub sort: Option<ListSort>,
+    /// Only show worktrees with uncommitted changes
+    #[arg(long)]
+    pub dirty: bool,
+    /// Only show worktrees without commits in this many days
+    #[arg(long, value_name = "DAYS")]
+    pub stale: Option<u64>,
+    /// Only show branches matching this glob (`*` and `?`)
+    #[arg(long = "match", value_name = "PATTERN")]
+    pub pattern: Option<String>,
+    /// Fetch first so ahead/behind counts are current
+    #[arg(long)]
+    pub fetch: bool,
+    /// Print the worktrees as JSON
+    #[arg(long, conflicts_with = "tree")]
+    pub json: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListSort {
+    Name,
+    Age,
+    Activity,
+    Size,
+}
+
+#[derive(Serialize)]
+struct Entry {
+    /// Position in the unfiltered list, usable in place of the branch name;
+    /// detached worktrees have none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<usize>,
+    /// The branch, or "(detached at <commit>)"
+    branch: String,
+    path: String,
+    current: bool,
+    detached: bool,
+    /// Why the worktree is locked, if it is (empty if no reason was given)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locked: Option<String>,
+    /// Why `git worktree prune` would remove it, e.g. its directory is gone
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prunable: Option<String>,
+    upstream_gone: bool,
+    /// Commits not on the upstream yet, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ahead: Option<usize>,
+    /// Upstream commits not merged yet, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    behind: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dirty: Option<bool>,
+    /// Unix time of the branch's latest commit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_commit: Option<u64>,
+    /// Unix time the worktree directory was created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<u64>,
+    /// Bytes on disk, not following symlinks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    /// User who created the worktree, if recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+}
+
+pub fn run(args: &ListArgs) -> Result<()> {
+    check_git_repo();
+    if args.fetch {
+        fetch_with_prune()?;
+    }
+    let entries = collect(args, args.json || args.dirty)?;
+    if args.dirty {
+        suggest_optimize()?;
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    print_entries(&entries, args.tree, args.collapse, &HashSet::new());
+
+    // Archived worktrees have no directory, so only name filters apply
+    if !args.dirty && args.stale.is_none() {
+        for branch in archive::load()?.keys().filter(|branch| {
+            args.pattern
+                .as_deref()
+                .is_none_or(|pattern| glob_match(pattern, branch))
+        }) {
+            println!("{}", dim(&format!("  -  {branch}  (archived)")));
+        }
+    }
+    Ok(())
+}
+
+/// Redraws the list every `interval` seconds, highlighting worktrees whose
+/// commits, upstream or dirtiness changed since the last redraw.
+///
+/// It polls rather than waiting for file system events: upstream changes
+/// only show up by fetching anyway, and watching every worktree would take
+/// a dependency and one watch per directory for what a redraw every few
+/// seconds already covers.
+pub fn watch(interval: u64, fetch: bool) -> Result<()> {
+    check_git_repo();
+    let args = ListArgs::default();
+    let mut previous: Option<HashMap<String, State>> = None;
+
+    loop {
+        if fetch {
+            fetch_with_prune()?;
+        }
+        let entries = collect(&args, true)?;
+        let states: HashMap<String, State> = entries
+            .iter()
+            .map(|entry| (entry.branch.clone(), State::of(entry)))
+            .collect();
+        // Nothing is new on the first draw
+        let changed: HashSet<String> = previous.as_ref().map_or_else(HashSet::new, |previous| {
+            states
+                .iter()
+                .filter(|(branch, state)| previous.get(*branch) != Some(*state))
+                .map(|(branch, _)| branch.clone())
+                .collect()
+        });
+
+        // Clearing the screen makes screen readers lose their place, so
+        // accessible mode appends each redraw instead
+        let mut stdout = io::stdout();
+        if !accessible::enabled() {
+            stdout
+                .execute(Clear(ClearType::All))?
+                .execute(MoveTo(0, 0))?;
+        }
+        println!("Every {interval}s: git-wt list (Ctrl-C to stop)\n");
+        print_entries(&entries, false, false, &changed);
+        stdout.flush()?;
+
+        previous = Some(states);
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+/// What `watch` compares between redraws.
+#[derive(PartialEq, Eq)]
+struct State {
+    head: Option<String>,
+    ahead: Option<usize>,
+    behind: Option<usize>,
+    upstream_gone: bool,
+    dirty: Option<bool>,
+}
+
+impl State {
+    fn of(entry: &Entry) -> State {
+        State {
+            head: git_stdout(&["rev-parse", "HEAD"], Some(Path::new(&entry.path))),
+            ahead: entry.ahead,
+            behind: entry.behind,
+            upstream_gone: entry.upstream_gone,
+            dirty: entry.dirty,
+        }
+    }
+}
+
+/// The worktrees `args` selects, in the order it asks for.
+fn collect(args: &ListArgs, need_dirty: bool) -> Result<Vec<Entry>> {
+    let current = get_current_worktree_branch()?;
+    let gone = gone_branches();
+    let tracking = tracking_counts();
+
+    // Only gather what the options need; dirtiness and size are per-worktree
+    // work
+    let need_commits = args.json || args.stale.is_some() || args.sort == Some(ListSort::Activity);
+    let last_commits = if need_commits {
+        last_commit_times()
+    } else {
+        HashMap::new()
+    };
+
+    // Numbers only count worktrees on a branch, like when resolving them
+    let mut position = 0;
+    let mut entries: Vec<Entry> = worktree::list()?
+        .into_iter()
+        .map(|worktree| {
+            let index = worktree.branch.is_some().then(|| {
+                position += 1;
+                position
+            });
+            (index, worktree)
+        })
+        .filter(|(_, worktree)| {
+            args.pattern
+                .as_deref()
+                .is_none_or(|pattern| glob_match(pattern, &worktree.name()))
+        })
+        .map(|(index, worktree)| {
+            let branch = worktree.name();
+            let detached = worktree.branch.is_none();
+            let (locked, prunable) = (worktree.locked, worktree.prunable);
+            let path = worktree.path;
+            let worktree = Path::new(&path);
+            let (ahead, behind) = tracking.get(&branch).copied().unzip();
+            Entry {
+                ahead,
+                behind,
+                index,
+                current: !detached && current.as_ref() == Some(&branch),
+                detached,
+                locked,
+                prunable,
+                upstream_gone: gone.contains(&branch),
+                dirty: need_dirty.then(|| is_dirty(worktree)),
+                last_commit: last_commits.get(&branch).copied(),
+                created: (args.sort == Some(ListSort::Age))
+                    .then(|| created_time(worktree))
+                    .flatten(),
+                size: (args.sort == Some(ListSort::Size)).then(|| disk_size(worktree)),
+                user: owner::creator(worktree),
+                branch,
+                path,
+            }
+        })
+        .filter(|entry| !args.dirty || entry.dirty == Some(true))
+        .collect();
+
+    if let Some(days) = args.stale {
+        let cutoff = format::now().saturating_sub(days * 24 * 60 * 60);
+        entries.retain(|entry| entry.last_commit.is_some_and(|time| time < cutoff));
+    }
+
+    match args.sort {
+        Some(ListSort::Name) => entries.sort_by(|a, b| a.branch.cmp(&b.branch)),
+        Some(ListSort::Age) => entries.sort_by_key(|entry| entry.created.unwrap_or(u64::MAX)),
+        Some(ListSort::Activity) => {
+            entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_commit));
+        }
+        Some(ListSort::Size) => entries.sort_by_key(|entry| std::cmp::Reverse(entry.size)),
+        None => {}
+    }
+
+    Ok(entries)
+}
+
+/// Checking many worktrees for changes scans every file of each unless git
+/// can cache or watch them, so point at `doctor --optimize` then.
+fn suggest_optimize() -> Result<()> {
+    const MANY_WORKTREES: usize = 10;
+    if worktree::list()?.len() < MANY_WORKTREES {
+        return Ok(());
+    }
+    let missing = doctor::missing_status_settings();
+    if !missing.is_empty() {
+        log_info(&format!(
+            "Hint: {} would make checking worktrees for changes faster; run `git-wt doctor --optimize`",
+            missing.join(" and ")
+        ));
+    }
+    Ok(())
+}
+
+/// Whether to color what goes to stdout.
+pub fn colored() -> bool {
+    let config = config::get();
+    match config.color.unwrap_or_default() {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => config.use_color() && io::stdout().is_terminal(),
+    }
+}
+
+/// `text` dimmed when stdout is colored.
+fn dim(text: &str) -> String {
+    if colored() {
+        text.dark_grey().to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn print_entries(entries: &[Entry], tree: bool, collapse: bool, highlight: &HashSet<String>) {
+    let index_width = entries
+        .iter()
+        .filter_map(|entry| entry.index)
+        .max()
+        .unwrap_or(0)
+        .to_string()
+        .len();
+    let branch_width = entries
+        .iter()
+        .map(|entry| entry.branch.len())
+        .max()
+        .unwrap_or(0);
+
+    let print_row = |indent: &str, entry: &Entry, name: &str| {
+        let marker = if entry.current { "*" } else { " " };
+        let annotation = match (entry.upstream_gone, entry.ahead, entry.behind) {
+            (true, _, _) => "  [upstream gone]".to_string(),
+            (false, Some(ahead), Some(behind)) if ahead > 0 && behind > 0 => {
+                format!("  [ahead {ahead}, behind {behind}]")
+            }
+            (false, Some(ahead), _) if ahead > 0 => format!("  [ahead {ahead}]"),
+            (false, _, Some(behind)) if behind > 0 => format!("  [behind {behind}]"),
+            _ => String::new(),
+        };
+        let annotation = if entry.dirty == Some(true) {
+            format!("{annotation}  [dirty]")
+        } else {
+            annotation
+        };
+        let annotation = match &entry.locked {
+            Some(reason) if reason.is_empty() => format!("{annotation}  [locked]"),
+            Some(reason) => format!("{annotation}  [locked: {reason}]"),
+            None => annotation,
+        };
+        let annotation = match &entry.prunable {
+            Some(reason) => format!("{annotation}  [prunable: {reason}]"),
+            None => annotation,
+        };
+        // What the worktrees are sorted or filtered by, when that was looked up
+        let annotation = match (entry.last_commit, entry.created, entry.size) {
+            (_, _, Some(size)) => format!("{annotation}  [{}]", format::size(size)),
+            (_, Some(created), _) => format!("{annotation}  [created {}]", format::date(created)),
+            (Some(time), _, _) => format!("{annotation}  [last commit {}]", format::date(time)),
+            _ => annotation,
+        };
+        // Only worth pointing out on machines shared with others
+        let annotation = match &entry.user {
+            Some(user) if *user != owner::current_user() => format!("{annotation}  [by {user}]"),
+            _ => annotation,
+        };
+        // Keep paths aligned however deep the row is indented
+        let name_width = branch_width.saturating_sub(indent.len());
+        let row = format!(
+            "{marker} {indent}{:>index_width$}  {name:<name_width$}  {}{annotation}",
+            entry
+                .index
+                .map_or("-".to_string(), |index| index.to_string()),
+            entry.path
+        );
+        if highlight.contains(&entry.branch) && accessible::enabled() {
+            println!("{row}  [changed]");
+        } else if highlight.contains(&entry.branch) && colored() {
+            println!("{}", row.bold().yellow());
+        } else {
+            println!("{row}");
+        }
+    };
+
+    if !tree {
+        for entry in entries {
+            print_row("", entry, &entry.branch);
+        }
+        return;
+    }
+
+    let mut groups: BTreeMap<&str, Vec<(&Entry, &str)>> = BTreeMap::new();
+    for entry in entries {
+        match entry.branch.split_once('/') {
+            Some((prefix, name)) => groups.entry(prefix).or_default().push((entry, name)),
+            None => print_row("", entry, &entry.branch),
+        }
+    }
+
+    for (prefix, members) in &groups {
+        let marker = if members.iter().any(|(entry, _)| entry.current) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{marker} {prefix}/ ({})", members.len());
+
+        if !collapse {
+            for (entry, name) in members {
+                print_row("  ", entry, name);
+            }
+        }
+    }
+}
+
+/// `(ahead, behind)` of every local branch with an existing upstream.
+pub fn tracking_counts() -> HashMap<String, (usize, usize)> {
+    git_stdout(
+        &[
+            "for-each-ref",
+            "--format=%(refname:short) %(upstream) %(upstream:track,nobracket)",
+            "refs/heads",
+        ],
+        None,
+    )
+    .unwrap_or_default()
+    .lines()
+    .filter_map(|line| {
+        let mut fields = line.splitn(3, ' ');
+        let (branch, upstream, track) = (fields.next()?, fields.next()?, fields.next()?);
+        if upstream.is_empty() || track == "gone" {
+            return None;
+        }
+
+        // "", "ahead 1", "behind 2" or "ahead 1, behind 2"
+        let mut counts = (0, 0);
+        for part in track.split(", ").filter(|part| !part.is_empty()) {
+            match part.split_once(' ')? {
+                ("ahead", n) => counts.0 = n.parse().ok()?,
+                ("behind", n) => counts.1 = n.parse().ok()?,
+                _ => return None,
+            }
+        }
+        Some((branch.to_string(), counts))
+    })
+    .collect()
+}
+
+/// Unix time of the latest commit of every local branch.
+pub fn last_commit_times() -> HashMap<String, u64> {
+    git_stdout(
+        &[
+            "for-each-ref",
+            "--format=%(committerdate:unix) %(refname:short)",
+            "refs/heads",
+        ],
+        None,
+    )
+    .unwrap_or_default()
+    .lines()
+    .filter_map(|line| {
+        let (time, branch) = line.split_once(' ')?;
+        Some((branch.to_string(), time.parse().ok()?))
+    })
+    .collect()
+}
+
+pub fn is_dirty(worktree: &Path) -> bool {
+    git_stdout(
+        &["--no-optional-locks", "status", "--porcelain"],
+        Some(worktree),
+    )
+    .is_some_and(|s| !s.is_empty())
+}
+
+fn created_time(worktree: &Path) -> Option<u64> {
+    let metadata = fs::metadata(worktree).ok()?;
+    let time = metadata.created().or_else(|_| metadata.modified()).ok()?;
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+/// Bytes under `path`, not following symlinks.
+pub fn disk_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    fs::read_dir(path).map_or(0, |entries| {
+        entries
+            .flatten()
+            .map(|entry| disk_size(&entry.path()))
+            .sum()
+    })
+}
+
+/// Matches `text` against a glob where `*` is any run of characters and `?`
+/// any single one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Classic two-pointer wildcard matching, backtracking to the last `*`
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Recorder};
+    use std::rc::Rc;
+
+    #[test]
+    fn star_matches_any_run_including_slashes() {
+        assert!(glob_match("feature/*", "feature/login"));
+        assert!(glob_match("feature/*", "feature/"));
+        assert!(glob_match("feature/*", "feature/api/v2"));
+        assert!(!glob_match("feature/*", "bugfix/login"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("f*/*i*", "feature/login"));
+    }
+
+    #[test]
+    fn question_mark_matches_exactly_one() {
+        assert!(glob_match("v?.0", "v1.0"));
+        assert!(!glob_match("v?.0", "v10.0"));
+        assert!(!glob_match("v?.0", "v.0"));
+    }
+
+    #[test]
+    fn patterns_are_anchored_at_both_ends() {
+        assert!(glob_match("login", "login"));
+        assert!(!glob_match("login", "feature/login"));
+        assert!(!glob_match("feature", "feature/login"));
+        assert!(glob_match("*login", "feature/login"));
+        assert!(glob_match("*login*", "feature/login-page"));
+        assert!(!glob_match("", "main"));
+    }
+
+    /// The branches `collect` keeps for `args`. `feature/login` and
+    /// `bugfix/login` haven't seen commits in ages, and only `feature/login`
+    /// and `feature/api` have changes.
+    fn collected(args: &ListArgs) -> Vec<String> {
+        let recorder = Rc::new(Recorder::default());
+        let porcelain: String = ["main", "feature/login", "feature/api", "bugfix/login"]
+            .iter()
+            .map(|branch| format!("worktree /wt/{branch}\nHEAD 0\nbranch refs/heads/{branch}\n\n"))
+            .collect::<Vec<_>>()
+            .concat();
+        recorder.respond("git worktree list", 0, &porcelain);
+        recorder.respond(
+            "git for-each-ref --format=%(committerdate:unix)",
+            0,
+            "9999999999 main\n100 feature/login\n9999999999 feature/api\n100 bugfix/login\n",
+        );
+        for dirty in ["feature/login", "feature/api"] {
+            recorder.respond(
+                &format!("/wt/{dirty}: git --no-optional-locks status"),
+                0,
+                " M a",
+            );
+        }
+
+        runner::with(recorder, || collect(args, args.dirty).unwrap())
+            .into_iter()
+            .map(|entry| entry.branch)
+            .collect()
+    }
+
+    #[test]
+    fn filters_combine() {
+        let pattern = |pattern: &str| Some(pattern.to_string());
+        assert_eq!(collected(&ListArgs::default()).len(), 4);
+        assert_eq!(
+            collected(&ListArgs {
+                pattern: pattern("*login"),
+                ..ListArgs::default()
+            }),
+            ["feature/login", "bugfix/login"]
+        );
+        assert_eq!(
+            collected(&ListArgs {
+                pattern: pattern("*login"),
+                stale: Some(1),
+                dirty: true,
+                ..ListArgs::default()
+            }),
+            ["feature/login"]
+        );
+        assert_eq!(
+            collected(&ListArgs {
+                pattern: pattern("feature/*"),
+                stale: Some(1),
+                ..ListArgs::default()
+            }),
+            ["feature/login"]
+        );
+        assert!(
+            collected(&ListArgs {
+                pattern: pattern("bugfix/*"),
+                dirty: true,
+                ..ListArgs::default()
+            })
+            .is_empty()
+        );
+    }
+}
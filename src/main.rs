@@ -1,30 +1,108 @@
 #![warn(clippy::all, clippy::pedantic)]
 
-use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+mod accessible;
+mod after_switch;
+mod archive;
+mod audit;
+mod compare;
+mod config;
+mod conflicts;
+mod copy;
+mod copy_file;
+mod devcontainer;
+mod directive;
+mod doctor;
+mod env_files;
+mod events;
+mod exec;
+mod explain;
+mod format;
+mod git_env;
+mod grep;
+mod hooks;
+mod index;
+mod list;
+mod maintenance;
+mod man;
+mod notify;
+mod offline;
+mod owner;
+mod patches;
+mod plumbing;
+mod release;
+mod report;
+mod runner;
+#[cfg(unix)]
+mod serve;
+mod setup;
+mod shared;
+mod snapshot;
+mod summary;
+mod template;
+mod throttle;
+mod timings;
+mod track;
+mod update;
+mod worktree;
+mod wsl;
+
+use accessible::Ask;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use color_eyre::eyre::ContextCompat;
 use color_eyre::{Result, eyre::Context};
+use config::{ColorMode, Config, DateStyle, HooksConfig, PullStrategy, RmConfirm, ThemeColor};
 use crossterm::ExecutableCommand;
 use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
-use inquire::ui::{Attributes, Color as InquireColor, RenderConfig, StyleSheet, Styled};
-use inquire::{Confirm, Select};
+use directive::Directive;
+use events::EventFormat;
+use exec::ExecArgs;
+use grep::GrepArgs;
+use hooks::{HookContext, HookEvent, InlineHooks};
+use inquire::ui::{
+    Attributes, Color as InquireColor, IndexPrefix, RenderConfig, StyleSheet, Styled,
+};
+use inquire::{Confirm, InquireError, MultiSelect, Select};
+use list::ListArgs;
 use nucleo_matcher::pattern::{Atom, AtomKind, CaseMatching, Normalization};
-use nucleo_matcher::{Config, Matcher, Utf32Str};
+use nucleo_matcher::{Matcher, Utf32Str};
+use runner::Capture;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::process::{self, Command};
+use std::process::{self, Child, Command, ExitStatus};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use summary::{Status, Summary};
 
-#[derive(Clone, ValueEnum)]
+#[derive(Clone, Copy, ValueEnum)]
 enum Shell {
     Fish,
     Bash,
     Zsh,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum CompletionKind {
+    /// Branches that have a worktree
+    Worktrees,
+    /// Branches, remote branches and tags a new branch can start from
+    Bases,
+}
+
 const BASH_INTEGRATION: &str = include_str!("../shell/bash.sh");
 const FISH_INTEGRATION: &str = include_str!("../shell/fish.fish");
 
+/// Exit code used when a git subprocess is killed after exceeding `--timeout`
+/// (matches coreutils `timeout`).
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
 #[derive(Parser)]
+#[allow(clippy::struct_excessive_bools)]
 #[command(name = "git-wt")]
 #[command(about = None, long_about = None)]
 struct Cli {
@@ -33,6 +111,79 @@ struct Cli {
 
     /// Branch name to switch to (when no subcommand is provided)
     branch: Option<String>,
+
+    /// Operate on the repository at this path instead of the current
+    /// directory's (also `GIT_WT_ROOT`)
+    #[arg(long, global = true, value_name = "PATH")]
+    root: Option<PathBuf>,
+
+    /// Kill git commands that run longer than this many seconds
+    #[arg(long, global = true, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Never open interactive prompts
+    #[arg(long, global = true)]
+    non_interactive: bool,
+
+    /// When to color output
+    #[arg(long, global = true, value_enum, value_name = "WHEN")]
+    color: Option<ColorMode>,
+
+    /// Don't run hooks
+    #[arg(long, global = true)]
+    no_hooks: bool,
+
+    /// Fail instead of prompting for credentials (for automation)
+    #[arg(long, global = true)]
+    no_terminal_prompt: bool,
+
+    /// Report how long each git command took
+    #[arg(long, global = true)]
+    timings: bool,
+    /// Plain numbered prompts and no animations, for screen readers
+    #[arg(long, global = true)]
+    accessible: bool,
+    /// Show dates as ISO 8601 instead of how long ago they were
+    #[arg(long, global = true)]
+    iso: bool,
+    /// Show ISO dates in UTC instead of local time
+    #[arg(long, global = true)]
+    utc: bool,
+    /// Skip invalid config settings with a warning instead of failing (also
+    /// `GIT_WT_LENIENT`)
+    #[arg(long, global = true)]
+    lenient: bool,
+    /// Describe what add, rm and clean will do and ask before doing it
+    #[arg(long, global = true)]
+    explain: bool,
+
+    /// Write the directory to switch to into this file instead of printing it
+    #[arg(long, global = true, value_name = "PATH")]
+    cd_file: Option<PathBuf>,
+
+    /// Write the shell integration's directives into this file instead of
+    /// printing them
+    #[arg(long, global = true, value_name = "PATH")]
+    directive_file: Option<PathBuf>,
+
+    /// Report progress of long operations as machine-readable events
+    #[arg(long, global = true, value_enum, value_name = "FORMAT")]
+    events: Option<EventFormat>,
+
+    /// File descriptor events are written to
+    #[arg(
+        long,
+        global = true,
+        value_name = "FD",
+        default_value_t = 2,
+        requires = "events"
+    )]
+    events_fd: u32,
+
+    /// Behave the same on every machine, for the integration tests: no
+    /// prompts, colors or notifications, and no global config
+    #[arg(long, global = true, hide = true)]
+    test_mode: bool,
 }
 
 #[derive(Subcommand)]
@@ -42,6 +193,10 @@ enum Commands {
         /// Shell: fish, bash, or zsh
         #[arg(value_enum)]
         shell: Option<Shell>,
+        /// Write the integration and man page under this prefix (e.g.
+        /// /usr/local), for packaging, instead of printing it
+        #[arg(long, value_name = "DIR", requires = "shell")]
+        write: Option<PathBuf>,
     },
     /// Clone a repository with bare worktree structure
     Clone {
@@ -49,25 +204,79 @@ enum Commands {
         url: String,
         /// Optional directory name (defaults to repo name)
         name: Option<String>,
+        /// Apply a template from the config after cloning
+        #[arg(short, long)]
+        template: Option<String>,
+        /// Print the created paths as JSON instead of changing into them
+        #[arg(long)]
+        json: bool,
+        /// Also fetch tags, notes and other configured ref namespaces, not
+        /// just branches
+        #[arg(long)]
+        all_refs: bool,
+    },
+    /// Also fetch branches matching a pattern, in a clone narrowed with
+    /// `fetch_branches`
+    Track {
+        /// Branch name or glob, e.g. `release/*`
+        pattern: String,
     },
     /// Fetch from origin with prune
-    Fetch,
-    /// Add a new worktree
-    Add {
-        /// Branch name for the new worktree
-        branch: String,
-        /// Create branch from this ref (defaults to origin/branch)
+    Fetch {
+        /// Fast-forward clean worktree branches that are behind their upstream
         #[arg(short, long)]
-        from: Option<String>,
+        update: bool,
+        /// Don't contact the remote; only do the local part with what was
+        /// fetched before
+        #[arg(long)]
+        offline: bool,
     },
+    /// Add a new worktree
+    Add(AddArgs),
     /// Remove a worktree
     #[command(alias = "remove")]
     Rm {
         /// Branch name of the worktree to remove (defaults to current worktree)
         branch: Option<String>,
-        /// Force removal even if worktree has uncommitted changes
+        /// Force removal even if worktree has uncommitted changes or belongs
+        /// to another user
+        #[arg(short, long)]
+        force: bool,
+        /// Delete untracked files and build artifacts (listed with their sizes
+        /// first), but refuse if tracked files have changes
+        #[arg(long, conflicts_with = "force")]
+        clean_artifacts: bool,
+        /// Remove without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        hooks: InlineHooks,
+    },
+    /// Check out the files of a worktree added with --no-checkout
+    Checkout {
+        /// Branch name of the worktree (defaults to current worktree)
+        branch: Option<String>,
+    },
+    /// Push a worktree's branch and remove its directory, remembering it
+    Archive {
+        /// Branch name of the worktree to archive (defaults to current worktree)
+        branch: Option<String>,
+    },
+    /// Recreate an archived worktree at the commit it was archived at
+    Unarchive {
+        /// Branch name of the archived worktree
+        branch: String,
+    },
+    /// Remove worktrees whose upstream branch was deleted
+    Clean {
+        /// Force removal even if worktrees have uncommitted changes
         #[arg(short, long)]
         force: bool,
+        /// Remove without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        failures: FailureArgs,
     },
     /// Switch to a worktree by branch name
     #[command(alias = "s")]
@@ -75,28 +284,619 @@ enum Commands {
         /// Branch name to switch to
         branch: String,
     },
+    /// List worktrees with their index (usable in place of a branch name)
+    #[command(alias = "ls")]
+    List(ListArgs),
+    /// Run a command in a worktree, or in every worktree with --all
+    Exec(ExecArgs),
+    /// Search every worktree with git grep
+    Grep(GrepArgs),
+    /// Keep redrawing the worktree list, highlighting what changed
+    Watch {
+        /// Seconds between redraws (at least 1)
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 2,
+            value_name = "SECONDS",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+        /// Fetch from origin before every redraw to notice new upstream commits
+        #[arg(long)]
+        fetch: bool,
+    },
+    /// Summarize every branch: worktree, age, last author and upstream
+    Report {
+        /// Markdown table (default) or JSON
+        #[arg(long, value_enum)]
+        format: Option<report::ReportFormat>,
+    },
+    /// Show branches without a worktree and pick one to create a worktree for
+    Branches {
+        /// Only print the branches, don't prompt
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Print completion candidates for the shell integration
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// What is being completed
+        kind: CompletionKind,
+    },
+    /// Print the absolute path of a worktree (no prompts, for scripts)
+    Path {
+        /// Branch name of the worktree
+        branch: String,
+    },
+    /// Print the path of the worktree of exactly this branch (plumbing: no
+    /// fuzzy matching or indexes)
+    Resolve {
+        /// Exact branch name of the worktree
+        branch: String,
+    },
+    /// Print the branch of the worktree containing the current directory
+    /// (plumbing; exits with 3 outside of one)
+    Current {
+        /// Print branch, path, upstream and status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Open a worktree in your editor
+    Open {
+        /// Branch name of the worktree to open (defaults to current worktree)
+        branch: Option<String>,
+        /// Start the worktree's dev container instead
+        #[arg(long)]
+        devcontainer: bool,
+    },
+    /// Save uncommitted changes to a snapshot ref without touching the branch
+    Snapshot {
+        /// Branch name of the worktree (defaults to current worktree)
+        branch: Option<String>,
+        /// Snapshot every worktree
+        #[arg(short, long, conflicts_with_all = ["branch", "restore"])]
+        all: bool,
+        /// Reapply the newest snapshot (or --id) as uncommitted changes
+        #[arg(short, long, conflicts_with = "list")]
+        restore: bool,
+        /// List snapshots
+        #[arg(short, long)]
+        list: bool,
+        /// Snapshot to restore (the timestamp, and any -<n> after it, shown by
+        /// --list)
+        #[arg(long, requires = "restore")]
+        id: Option<String>,
+    },
+    /// Push every worktree branch to a remote as a backup
+    Backup {
+        /// Remote to push to [default: `default_remote`]
+        #[arg(long)]
+        remote: Option<String>,
+        /// Push under this prefix [default: backup/<user>/]; only an
+        /// explicit empty prefix pushes to the branches themselves
+        #[arg(long)]
+        prefix: Option<String>,
+    },
+    /// Garbage-collect and repack the shared repository, reporting the space
+    /// reclaimed
+    Maintenance {
+        /// Spend much more time to pack objects more tightly
+        #[arg(long)]
+        aggressive: bool,
+    },
+    /// Show the audit log of operations that changed worktrees
+    History {
+        /// Only show the last N entries
+        #[arg(short = 'n', long, value_name = "N")]
+        limit: Option<usize>,
+        /// Only show entries by this user
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Set up the shell integration and common settings interactively
+    Setup,
+    /// Read and write settings in the config files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+        /// Use the user-wide config instead of the repository's
+        #[arg(long, global = true)]
+        global: bool,
+    },
+    /// Manage git hooks shared by all worktrees
+    #[command(subcommand)]
+    Hooks(HooksCommand),
+    /// Merge a worktree's branch into the default branch's worktree
+    Merge {
+        /// Branch name of the worktree to merge
+        branch: String,
+        /// Branch to merge into (defaults to the default branch)
+        #[arg(long, value_name = "TARGET")]
+        into: Option<String>,
+    },
+    /// Cherry-pick commits from another worktree's branch into the current one
+    Pick {
+        /// Branch name of the worktree to pick commits from
+        branch: String,
+    },
+    /// Show how two worktrees differ, including uncommitted changes
+    Compare {
+        /// Branch name of the first worktree
+        a: String,
+        /// Branch name of the second worktree
+        b: String,
+        /// Show the full diff after the diffstat
+        #[arg(short, long)]
+        patch: bool,
+    },
+    /// Copy files from another worktree into the current one
+    CopyFile {
+        /// Branch name of the worktree to copy from
+        from: String,
+        /// Files to copy, relative to the current directory
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Copy the files as committed on the branch instead of as they are in
+        /// its worktree
+        #[arg(long)]
+        committed: bool,
+        /// Overwrite files that differ without asking
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Write a worktree's commits since the default branch as patch files
+    Export {
+        /// Branch name of the worktree to export
+        branch: String,
+        /// Directory to write the patches to
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Create a worktree from an exported patch series
+    Import {
+        /// Directory containing the patches
+        dir: PathBuf,
+        /// Branch name for the new worktree (defaults to the directory name)
+        #[arg(long = "as", value_name = "BRANCH")]
+        name: Option<String>,
+    },
+    /// Create a release/<version> branch and worktree, bump the version and push it
+    Release {
+        /// Version being released
+        version: String,
+        /// Ref to branch from (defaults to `release.base`, then the default branch)
+        #[arg(short, long)]
+        from: Option<String>,
+        /// Don't push the branch
+        #[arg(long)]
+        no_push: bool,
+    },
+    /// Move worktrees created with plain `git worktree add` to where git-wt puts them
+    Adopt {
+        /// Move every such worktree without asking which
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Serve JSON-RPC on a unix socket for editor integrations
+    #[cfg(unix)]
+    Serve {
+        /// Path of the socket to listen on
+        #[arg(long)]
+        socket: PathBuf,
+    },
+    /// Check that git and the repository support what git-wt needs
+    Doctor {
+        /// Report the git version and which git features are available
+        #[arg(long)]
+        versions: bool,
+        /// Check the settings that keep status checks fast, such as
+        /// core.fsmonitor, and offer to turn on missing ones
+        #[arg(long)]
+        optimize: bool,
+        /// Turn them on without asking
+        #[arg(short, long, requires = "optimize")]
+        yes: bool,
+        /// Check the shared object store for corruption with git fsck
+        #[arg(long, conflicts_with = "optimize")]
+        fsck: bool,
+    },
+    /// Update git-wt to the latest release
+    SelfUpdate {
+        /// Only report whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
     /// Pull changes in a worktree
     Pull {
         /// Branch name of the worktree to pull (defaults to current worktree)
         branch: Option<String>,
+        /// Pull every worktree, skipping those with uncommitted changes
+        #[arg(short, long, conflicts_with = "branch")]
+        all: bool,
+        #[command(flatten)]
+        failures: FailureArgs,
+        /// Rebase local commits onto the upstream
+        #[arg(long, group = "strategy")]
+        rebase: bool,
+        /// Only fast-forward, failing if the branches diverged
+        #[arg(long, group = "strategy")]
+        ff_only: bool,
+        /// Merge the upstream, creating a merge commit if needed
+        #[arg(long, group = "strategy")]
+        merge: bool,
+    },
+}
+
+/// How operations over many worktrees treat a failing one.
+#[derive(Args)]
+struct FailureArgs {
+    /// Stop at the first worktree that fails
+    #[arg(long, overrides_with = "keep_going")]
+    fail_fast: bool,
+    /// Carry on past failing worktrees (the default)
+    #[arg(long, overrides_with = "fail_fast")]
+    keep_going: bool,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the value of a key such as `timeout` or `theme.preset`
+    Get { key: String },
+    /// Set a key, checking that the config accepts it
+    Set {
+        key: String,
+        /// Value, as TOML (`true`, `120`, `["a", "b"]`) or a plain string
+        value: String,
+    },
+    /// Remove a key
+    Unset { key: String },
+    /// Print every key set in the config files
+    List,
+    /// Open the config file in $VISUAL or $EDITOR
+    Edit,
+}
+
+#[derive(Subcommand)]
+enum HooksCommand {
+    /// Point core.hooksPath at a hooks directory managed by git-wt
+    Install {
+        /// Only configure the current worktree
+        #[arg(long)]
+        worktree: bool,
     },
 }
 
+#[derive(Args, Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct AddArgs {
+    /// Branch name for the new worktree
+    branch: String,
+    /// Create branch from this ref (defaults to <remote>/branch, see
+    /// `default_remote`)
+    #[arg(short, long)]
+    from: Option<String>,
+    /// Upstream for a newly created branch (defaults to <remote>/branch)
+    #[arg(long, value_name = "REMOTE/BRANCH", conflicts_with = "no_track")]
+    track: Option<String>,
+    /// Don't set an upstream for a newly created branch
+    #[arg(long)]
+    no_track: bool,
+    /// Fetch the branch from the remote without asking if it isn't known
+    /// locally
+    #[arg(long)]
+    fetch: bool,
+    /// Pick the ref to create the branch from out of remote branches and tags
+    #[arg(short, long, conflicts_with = "from")]
+    interactive_base: bool,
+    /// Start a dev container for the new worktree
+    #[arg(long)]
+    devcontainer: bool,
+    /// Add another worktree of a branch that already has one, on a new
+    /// <branch>-wtN branch with the same upstream
+    #[arg(long, conflicts_with_all = ["from", "interactive_base"])]
+    again: bool,
+    /// Only register the worktree; check files out later with `git-wt checkout`
+    #[arg(long, conflicts_with = "devcontainer")]
+    no_checkout: bool,
+    /// Don't report checkout progress
+    #[arg(long)]
+    quiet_checkout: bool,
+    /// Create the branch from the commit the stash was made on and apply the
+    /// stash there, dropping it if that succeeds
+    #[arg(
+        long,
+        value_name = "STASH",
+        num_args = 0..=1,
+        default_missing_value = "stash@{0}",
+        conflicts_with_all = ["from", "interactive_base", "again", "no_checkout"]
+    )]
+    from_stash: Option<String>,
+    /// Create the branch at the common ancestor of two refs, e.g. to check
+    /// whether a bug predates a branch
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["REF_A", "REF_B"],
+        conflicts_with_all = ["from", "interactive_base", "again", "from_stash"]
+    )]
+    merge_base: Option<Vec<String>>,
+    #[command(flatten)]
+    hooks: InlineHooks,
+}
+
 fn main() -> Result<()> {
+    git_env::absolutize();
     color_eyre::install()?;
-    let cli = Cli::parse();
-
-    match cli.command {
-        Some(Commands::Init { shell }) => init_shell_integration(shell)?,
-        Some(Commands::Clone { url, name }) => clone_bare_for_worktrees(&url, name.as_deref())?,
-        Some(Commands::Fetch) => fetch_with_prune()?,
-        Some(Commands::Add { branch, from }) => add_worktree(&branch, from.as_deref())?,
-        Some(Commands::Rm { branch, force }) => remove_worktree(branch.as_deref(), force)?,
+    // Git runs `git wt` as git-wt with the same argv[0], but exports
+    // GIT_EXEC_PATH; usage and errors then show the spelling the user typed
+    let mut command = Cli::command();
+    if env::var_os("GIT_EXEC_PATH").is_some() {
+        command = command.bin_name("git wt");
+    }
+    let cli = Cli::from_arg_matches(&command.get_matches()).unwrap_or_else(|error| error.exit());
+    if cli.timings {
+        timings::enable();
+    }
+
+    return_to_git_prefix();
+
+    // The cd file is named relative to where we were called from
+    let cd_file = cli.cd_file.map(std::path::absolute).transpose()?;
+    let directive_file = cli.directive_file.map(std::path::absolute).transpose()?;
+    let root = cli.root.or_else(|| {
+        env::var_os("GIT_WT_ROOT")
+            .filter(|root| !root.is_empty())
+            .map(PathBuf::from)
+    });
+    if let Some(root) = root
+        && env::set_current_dir(&root).is_err()
+    {
+        log_error(&format!("Cannot use {} as the root", root.display()));
+        process::exit(1);
+    }
+
+    let init = config::init(
+        Config {
+            timeout: cli.timeout,
+            non_interactive: cli.non_interactive.then_some(true),
+            color: cli.color,
+            accessible: cli.accessible.then_some(true),
+            dates: cli.iso.then_some(DateStyle::Iso),
+            utc: cli.utc.then_some(true),
+            cd_file,
+            directive_file,
+            lenient: cli.lenient.then_some(true),
+            explain: cli.explain.then_some(true),
+            test_mode: cli.test_mode.then_some(true),
+            terminal_prompt: cli.no_terminal_prompt.then_some(false),
+            hooks: HooksConfig {
+                enabled: cli.no_hooks.then_some(false),
+                ..HooksConfig::default()
+            },
+            ..Config::default()
+        },
+        git_common_dir().as_deref(),
+    );
+    // A broken config file shouldn't keep `config` from fixing it
+    match init {
+        Err(e) if matches!(cli.command, Some(Commands::Config { .. })) => {
+            log_info(&format!("Note: {e:#}"));
+        }
+        init => init?,
+    }
+
+    if let Some(format) = cli.events {
+        events::init(format, cli.events_fd)
+            .with_context(|| format!("Failed to open file descriptor {}", cli.events_fd))?;
+    }
+
+    if mutates(cli.command.as_ref()) {
+        audit::start();
+    }
+    if let Err(e) = dispatch(cli.command, cli.branch) {
+        audit::failed(&format!("{e}"));
+        return Err(e);
+    }
+    audit::finished();
+
+    timings::report();
+    Ok(())
+}
+
+/// Whether `command` changes worktrees, branches or settings, so that its
+/// run is recorded in the audit log.
+fn mutates(command: Option<&Commands>) -> bool {
+    match command {
+        Some(Commands::Fetch { update, .. }) => *update,
+        Some(Commands::Doctor { optimize, .. }) => *optimize,
+        Some(Commands::Config { command, .. }) => {
+            !matches!(command, ConfigCommand::Get { .. } | ConfigCommand::List)
+        }
+        Some(Commands::Exec(args)) => args.all,
+        Some(
+            Commands::Add(_)
+            | Commands::Rm { .. }
+            | Commands::Checkout { .. }
+            | Commands::Track { .. }
+            | Commands::Archive { .. }
+            | Commands::Unarchive { .. }
+            | Commands::Clean { .. }
+            | Commands::Snapshot { .. }
+            | Commands::Backup { .. }
+            | Commands::Maintenance { .. }
+            | Commands::Setup
+            | Commands::Hooks(_)
+            | Commands::Merge { .. }
+            | Commands::Pick { .. }
+            | Commands::Import { .. }
+            | Commands::Release { .. }
+            | Commands::Adopt { .. }
+            | Commands::Pull { .. },
+        ) => true,
+        _ => false,
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn dispatch(command: Option<Commands>, branch: Option<String>) -> Result<()> {
+    match command {
+        Some(Commands::Init { shell, write }) => init_shell_integration(shell, write.as_deref())?,
+        Some(Commands::Clone {
+            url,
+            name,
+            template,
+            json,
+            all_refs,
+        }) => clone_bare_for_worktrees(&url, name.as_deref(), template.as_deref(), json, all_refs)?,
+        Some(Commands::Track { pattern }) => track::run(&pattern)?,
+        Some(Commands::Fetch { update, offline }) => {
+            if !offline {
+                let previous_head = default_branch_ref();
+                fetch_with_prune()?;
+                check_remote_head(previous_head.as_deref())?;
+            }
+            if update {
+                update_worktree_branches()?;
+            }
+            report_gone_worktrees()?;
+        }
+        Some(Commands::Add(args)) => {
+            hooks::set_inline(args.hooks.clone());
+            let worktree_path = add_worktree(&args)?;
+            if args.devcontainer {
+                devcontainer::up(&worktree_path)?;
+            }
+        }
+        Some(Commands::Rm {
+            branch,
+            force,
+            clean_artifacts,
+            yes,
+            hooks,
+        }) => {
+            hooks::set_inline(hooks);
+            remove_worktree(branch.as_deref(), force, clean_artifacts, yes)?;
+        }
+        Some(Commands::Checkout { branch }) => checkout_worktree(branch.as_deref())?,
+        Some(Commands::Archive { branch }) => archive::archive(branch.as_deref())?,
+        Some(Commands::Unarchive { branch }) => archive::unarchive(&branch)?,
+        Some(Commands::Clean {
+            force,
+            yes,
+            failures,
+        }) => clean_worktrees(force, yes, failures.fail_fast)?,
         Some(Commands::Switch { branch }) => switch_to_worktree(&branch)?,
-        Some(Commands::Pull { branch }) => pull_worktree(branch.as_deref())?,
+        Some(Commands::List(args)) => list::run(&args)?,
+        Some(Commands::Exec(args)) => exec::run(&args)?,
+        Some(Commands::Grep(args)) => grep::run(&args)?,
+        Some(Commands::Watch { interval, fetch }) => list::watch(interval, fetch)?,
+        Some(Commands::Report { format }) => report::run(format.unwrap_or_default())?,
+        Some(Commands::Branches { list }) => branches_without_worktrees(list)?,
+        Some(Commands::Complete { kind }) => print_completions(kind)?,
+        Some(Commands::Path { branch }) => print_worktree_path(&branch)?,
+        Some(Commands::Resolve { branch }) => plumbing::resolve(&branch)?,
+        Some(Commands::Current { json }) => plumbing::current(json)?,
+        Some(Commands::Open {
+            branch,
+            devcontainer,
+        }) => open_worktree(branch.as_deref(), devcontainer)?,
+        Some(Commands::Snapshot {
+            branch,
+            all,
+            restore,
+            list,
+            id,
+        }) => {
+            check_git_repo();
+            if list {
+                snapshot::list(branch.as_deref())?;
+            } else if restore {
+                snapshot::restore(branch.as_deref(), id.as_deref())?;
+            } else {
+                snapshot::create(branch.as_deref(), all)?;
+            }
+        }
+        Some(Commands::Backup { remote, prefix }) => {
+            let remote = remote.unwrap_or_else(default_remote);
+            let prefix = prefix.unwrap_or_else(default_backup_prefix);
+            backup_worktree_branches(&remote, &prefix)?;
+        }
+        Some(Commands::Maintenance { aggressive }) => maintenance::run(aggressive)?,
+        Some(Commands::History { limit, user }) => {
+            check_git_repo();
+            audit::history(limit, user.as_deref())?;
+        }
+        Some(Commands::Setup) => setup::run()?,
+        Some(Commands::Config { command, global }) => {
+            let common_dir = git_common_dir();
+            let common_dir = common_dir.as_deref();
+            match command {
+                ConfigCommand::Get { key } => config::print_value(&key, global, common_dir)?,
+                ConfigCommand::Set { key, value } => {
+                    config::set_value(&key, Some(&value), global, common_dir)?;
+                }
+                ConfigCommand::Unset { key } => config::set_value(&key, None, global, common_dir)?,
+                ConfigCommand::List => config::print_all(global, common_dir)?,
+                ConfigCommand::Edit => config::edit(global, common_dir)?,
+            }
+        }
+        Some(Commands::Hooks(HooksCommand::Install { worktree })) => hooks::install(worktree)?,
+        Some(Commands::Merge { branch, into }) => merge_worktree(&branch, into.as_deref())?,
+        Some(Commands::Pick { branch }) => pick_commits(&branch)?,
+        Some(Commands::Compare { a, b, patch }) => compare::run(&a, &b, patch)?,
+        Some(Commands::CopyFile {
+            from,
+            paths,
+            committed,
+            force,
+        }) => copy_file::run(&from, &paths, committed, force)?,
+        Some(Commands::Export { branch, output }) => patches::export(&branch, &output)?,
+        Some(Commands::Import { dir, name }) => patches::import(&dir, name.as_deref())?,
+        Some(Commands::Adopt { yes }) => adopt_worktrees(yes)?,
+        Some(Commands::Release {
+            version,
+            from,
+            no_push,
+        }) => release::run(&version, from.as_deref(), !no_push)?,
+        #[cfg(unix)]
+        Some(Commands::Serve { socket }) => {
+            check_git_repo();
+            serve::run(&socket)?;
+        }
+        Some(Commands::Doctor {
+            optimize: true,
+            yes,
+            ..
+        }) => doctor::optimize(yes)?,
+        Some(Commands::Doctor { fsck: true, .. }) => doctor::fsck()?,
+        // Without --optimize or --fsck the versions are reported, so plain
+        // `doctor` does the same as `doctor --versions`
+        Some(Commands::Doctor { .. }) => doctor::versions(),
+        Some(Commands::SelfUpdate { check }) => update::run(check)?,
+        Some(Commands::Pull {
+            branch,
+            all,
+            failures,
+            rebase,
+            ff_only,
+            merge,
+        }) => {
+            let strategy = match (rebase, ff_only, merge) {
+                (true, _, _) => Some(PullStrategy::Rebase),
+                (_, true, _) => Some(PullStrategy::FfOnly),
+                (_, _, true) => Some(PullStrategy::Merge),
+                _ => config::get().pull_strategy,
+            };
+            if all {
+                pull_all_worktrees(strategy, failures.fail_fast)?;
+            } else {
+                pull_worktree(branch.as_deref(), strategy)?;
+            }
+        }
         None => {
             // No subcommand provided, check for branch argument
-            if let Some(branch) = cli.branch {
+            if let Some(branch) = branch {
                 switch_to_worktree(&branch)?;
             } else {
                 // No subcommand and no branch, print help
@@ -109,7 +909,7 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn init_shell_integration(shell: Option<Shell>) -> Result<()> {
+fn init_shell_integration(shell: Option<Shell>, write: Option<&Path>) -> Result<()> {
     let Some(shell) = shell else {
         eprintln!("Usage: git-wt init <shell> | source");
         eprintln!("  Shell: fish, bash, zsh");
@@ -117,20 +917,101 @@ fn init_shell_integration(shell: Option<Shell>) -> Result<()> {
         process::exit(1);
     };
 
-    let script = match shell {
-        Shell::Fish => FISH_INTEGRATION,
-        Shell::Bash | Shell::Zsh => BASH_INTEGRATION,
-    };
+    let name = config::get().shell_function.as_deref().unwrap_or("git-wt");
+    let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_');
+    if name.is_empty() || !name.chars().all(valid) {
+        log_error(&format!(
+            "Invalid shell_function '{name}': use letters, digits, '-' and '_'"
+        ));
+        process::exit(1);
+    }
+    let script = integration_script(shell, name);
 
+    if let Some(prefix) = write {
+        return write_shell_integration(shell, &script, prefix);
+    }
     io::stdout().write_all(script.as_bytes())?;
     Ok(())
 }
 
+/// The integration for `shell`, with the function named `name` instead of
+/// `git-wt`. The `git wt` wrapper and completions follow the new name.
+fn integration_script(shell: Shell, name: &str) -> String {
+    match shell {
+        Shell::Fish => {
+            let script = FISH_INTEGRATION
+                .replacen("\nfunction git-wt\n", &format!("\nfunction {name}\n"), 1)
+                .replacen(
+                    "        git-wt $argv\n",
+                    &format!("        {name} $argv\n"),
+                    1,
+                );
+            if name == "git-wt" {
+                script
+            } else {
+                format!("{script}complete -c {name} -w git-wt\n")
+            }
+        }
+        Shell::Bash | Shell::Zsh => BASH_INTEGRATION
+            .replacen("\ngit-wt() {", &format!("\n{name}() {{"), 1)
+            .replacen(
+                "        git-wt \"$@\"\n",
+                &format!("        {name} \"$@\"\n"),
+                1,
+            )
+            .replacen(
+                "-F _git_wt_complete git-wt\n",
+                &format!("-F _git_wt_complete {name}\n"),
+                1,
+            ),
+    }
+}
+
+/// Writes the integration for `shell` and the man page where packages
+/// install them under `prefix`. Fish loads `vendor_conf.d` on its own;
+/// bash and zsh have no such directory, so their rc file has to source the
+/// script.
+fn write_shell_integration(shell: Shell, script: &str, prefix: &Path) -> Result<()> {
+    let integration = match shell {
+        Shell::Fish => "share/fish/vendor_conf.d/git-wt.fish",
+        Shell::Bash => "share/git-wt/git-wt.bash",
+        Shell::Zsh => "share/git-wt/git-wt.zsh",
+    };
+    let files = [
+        (prefix.join(integration), script.to_string()),
+        (prefix.join("share/man/man1/git-wt.1"), man::page()),
+    ];
+    for (path, contents) in &files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        log_info(&format!("Wrote {}", path.display()));
+    }
+    if !matches!(shell, Shell::Fish) {
+        log_info(&format!(
+            "Source it from your shell's rc file: . {}",
+            files[0].0.display()
+        ));
+    }
+    Ok(())
+}
+
 fn log_info(message: &str) {
     eprintln!("{message}");
 }
 
 fn log_error(message: &str) {
+    events::error(message);
+    notify::failed(message);
+    audit::failed(message);
+
+    if !config::get().use_color() {
+        eprintln!("ERROR: {message}");
+        return;
+    }
+
     let mut stdout = io::stderr();
     let _ = stdout
         .execute(SetBackgroundColor(Color::Red))
@@ -140,25 +1021,57 @@ fn log_error(message: &str) {
         .and_then(|s| s.execute(Print(format!(" {message}\n"))));
 }
 
-fn create_select_render_config() -> RenderConfig<'static> {
+/// Default number of options visible at once in pickers.
+const DEFAULT_PAGE_SIZE: usize = 10;
+
+fn create_select_render_config(numbered: bool) -> RenderConfig<'static> {
+    let theme = &config::get().theme;
+    let prompt_prefix = theme.prompt_prefix.as_deref().unwrap_or("Select:");
+    let highlight_prefix = theme.highlight_prefix.as_deref().unwrap_or(">");
+    let option_index_prefix = if numbered {
+        IndexPrefix::Simple
+    } else {
+        IndexPrefix::None
+    };
+
+    if config::get().plain_prompts() {
+        return RenderConfig {
+            prompt_prefix: Styled::new(prompt_prefix),
+            highlighted_option_prefix: Styled::new(highlight_prefix),
+            answered_prompt_prefix: Styled::new(prompt_prefix),
+            option_index_prefix,
+            ..RenderConfig::empty()
+        };
+    }
+
+    let color = |color: Option<ThemeColor>, default| color.map_or(default, |c| c.0);
     RenderConfig {
-        prompt_prefix: Styled::new("Select:"),
-        highlighted_option_prefix: Styled::new(">"),
-        answered_prompt_prefix: Styled::new("Select:"),
+        prompt_prefix: Styled::new(prompt_prefix),
+        highlighted_option_prefix: Styled::new(highlight_prefix),
+        answered_prompt_prefix: Styled::new(prompt_prefix),
         prompt: StyleSheet::new(),
         help_message: StyleSheet::new(),
         answer: StyleSheet::new().with_attr(Attributes::BOLD),
-        option: StyleSheet::new().with_fg(InquireColor::DarkGrey),
+        option_index_prefix,
+        option: StyleSheet::new().with_fg(color(theme.option_color, InquireColor::DarkGrey)),
         selected_option: Some(
             StyleSheet::new()
-                .with_fg(InquireColor::Black)
-                .with_bg(InquireColor::White),
+                .with_fg(color(theme.highlight_fg, InquireColor::Black))
+                .with_bg(color(theme.highlight_bg, InquireColor::White)),
         ),
         ..Default::default()
     }
 }
 
 fn create_confirm_render_config(prompt: &str) -> RenderConfig<'_> {
+    if config::get().plain_prompts() {
+        return RenderConfig {
+            prompt_prefix: Styled::new(prompt),
+            answered_prompt_prefix: Styled::new(prompt),
+            ..RenderConfig::empty()
+        };
+    }
+
     RenderConfig {
         prompt_prefix: Styled::new(prompt),
         answered_prompt_prefix: Styled::new(prompt),
@@ -169,17 +1082,16 @@ fn create_confirm_render_config(prompt: &str) -> RenderConfig<'_> {
     }
 }
 
-fn run_command(cmd: &str, args: &[&str], cwd: Option<&Path>) -> Result<()> {
-    let mut command = Command::new(cmd);
-    command.args(args);
-
-    if let Some(dir) = cwd {
-        command.current_dir(dir);
-    }
+fn page_size() -> usize {
+    config::get()
+        .theme
+        .page_size
+        .filter(|&size| size > 0)
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+}
 
-    let status = command
-        .status()
-        .with_context(|| format!("Failed to execute command: {cmd}"))?;
+fn run_command(cmd: &str, args: &[&str], cwd: Option<&Path>) -> Result<()> {
+    let status = run_command_status(cmd, args, cwd)?;
 
     if !status.success() {
         log_error("Command failed");
@@ -189,7 +1101,96 @@ fn run_command(cmd: &str, args: &[&str], cwd: Option<&Path>) -> Result<()> {
     Ok(())
 }
 
-fn clone_bare_for_worktrees(url: &str, name: Option<&str>) -> Result<()> {
+/// Creates a command that inherits the terminal, so credential prompts of
+/// git and ssh stay visible, unless terminal prompts are turned off, in which
+/// case they fail instead of waiting for input.
+fn new_command(cmd: &str) -> Command {
+    let mut command = Command::new(cmd);
+    if !config::get().terminal_prompt.unwrap_or(true) {
+        disable_terminal_prompts(&mut command);
+    }
+    command
+}
+
+fn disable_terminal_prompts(command: &mut Command) {
+    command.env("GIT_TERMINAL_PROMPT", "0");
+    // Respect a user-provided ssh command; it may already be non-interactive
+    if env::var_os("GIT_SSH_COMMAND").is_none() {
+        command.env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes");
+    }
+}
+
+/// Like [`run_command`], but leaves handling a failed command to the caller.
+fn run_command_status(cmd: &str, args: &[&str], cwd: Option<&Path>) -> Result<ExitStatus> {
+    Ok(runner::run(cmd, args, cwd, Capture::Nothing)?.status)
+}
+
+/// Like [`run_command_status`], but captures stdout. Stderr stays attached to
+/// the terminal so progress and credential prompts remain visible.
+fn run_command_output(
+    cmd: &str,
+    args: &[&str],
+    cwd: Option<&Path>,
+) -> Result<(ExitStatus, String)> {
+    let output = runner::run(cmd, args, cwd, Capture::Stdout)?;
+    Ok((
+        output.status,
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ))
+}
+
+fn wait_with_timeout(child: &mut Child, cmd: &str, args: &[&str]) -> Result<ExitStatus> {
+    let Some(timeout) = config::get().timeout() else {
+        return child
+            .wait()
+            .with_context(|| format!("Failed to wait for command: {cmd}"));
+    };
+
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .with_context(|| format!("Failed to wait for command: {cmd}"))?
+        {
+            return Ok(status);
+        }
+
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            log_error(&format!(
+                "Command timed out after {}s: {cmd} {}",
+                timeout.as_secs(),
+                args.join(" ")
+            ));
+            process::exit(TIMEOUT_EXIT_CODE);
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Paths created by `clone`, as printed by `clone --json`.
+#[derive(Serialize)]
+struct Cloned {
+    path: PathBuf,
+    bare: PathBuf,
+    worktrees: Vec<PathBuf>,
+}
+
+/// Refspecs `clone --all-refs` adds unless `clone_refspecs` is configured.
+const ALL_REFS_REFSPECS: [&str; 2] = ["+refs/tags/*:refs/tags/*", "+refs/notes/*:refs/notes/*"];
+
+fn clone_bare_for_worktrees(
+    url: &str,
+    name: Option<&str>,
+    template: Option<&str>,
+    json: bool,
+    all_refs: bool,
+) -> Result<()> {
+    // Resolve the template first so a typo fails before the clone
+    let template = template.map(template::find).transpose()?;
+
     let basename = url.rsplit('/').next().context("Invalid URL")?;
     let default_name = basename.trim_end_matches(".git");
     let dir_name = name.unwrap_or(default_name);
@@ -201,285 +1202,2289 @@ fn clone_bare_for_worktrees(url: &str, name: Option<&str>) -> Result<()> {
 
     let dir_path = PathBuf::from(dir_name);
 
+    events::started("clone");
+    notify::started("clone");
     log_info(&format!("Cloning {url} into {dir_name}/"));
 
-    run_command("git", &["clone", "--bare", url, ".bare"], Some(&dir_path))?;
+    let fetch_branches = config::get().fetch_branches.as_deref();
+    match fetch_branches {
+        Some(patterns) => track::init_narrowed(url, patterns, &dir_path)?,
+        None => offline::run_git(&["clone", "--bare", url, ".bare"], Some(&dir_path))?,
+    }
+    shared::configure_repo(Some(&dir_path))?;
+    shared::directory(&dir_path);
 
     // Create .git file pointing to .bare
     fs::write(dir_path.join(".git"), "gitdir: ./.bare\n").context("Failed to create .git file")?;
 
     // Configure remote origin fetch
-    run_command(
-        "git",
-        &[
-            "config",
-            "remote.origin.fetch",
-            "+refs/heads/*:refs/remotes/origin/*",
-        ],
-        Some(&dir_path),
-    )?;
+    if fetch_branches.is_none() {
+        run_command(
+            "git",
+            &[
+                "config",
+                "remote.origin.fetch",
+                "+refs/heads/*:refs/remotes/origin/*",
+            ],
+            Some(&dir_path),
+        )?;
+    }
+    if all_refs {
+        let refspecs = config::get()
+            .clone_refspecs
+            .clone()
+            .unwrap_or_else(|| ALL_REFS_REFSPECS.map(String::from).to_vec());
+        for refspec in &refspecs {
+            run_command(
+                "git",
+                &["config", "--add", "remote.origin.fetch", refspec],
+                Some(&dir_path),
+            )?;
+        }
+    }
 
     // Fetch all branches
+    events::progress(1, 3, "fetch");
     log_info("Fetching branches...");
-    run_command("git", &["fetch", "origin"], Some(&dir_path))?;
+    offline::run_git(&["fetch", "origin"], Some(&dir_path))?;
+    if fetch_branches.is_some() {
+        track::set_default_head(&dir_path, "origin")?;
+    }
 
     log_info("Repository cloned successfully.");
+    events::progress(2, 3, "template");
+
+    let path = dir_path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {dir_name}"))?;
+    let worktrees = match template {
+        Some(template) => {
+            env::set_current_dir(&path).with_context(|| format!("Failed to enter {dir_name}"))?;
+            template::apply(template, Path::new("."))?
+        }
+        None => Vec::new(),
+    };
+    events::finished();
+    notify::finished();
+
+    let cloned = Cloned {
+        bare: path.join(".bare"),
+        path,
+        worktrees,
+    };
+    // Scripts asked for JSON on stdout, so only a --cd-file or the shell
+    // integration's directive file gets the directory
+    let target = cloned.worktrees.first().unwrap_or(&cloned.path);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&cloned)?);
+        let config = config::get();
+        if config.cd_file.is_some() || config.directive_file.is_some() {
+            change_directory(target);
+        }
+        return Ok(());
+    }
 
-    //let abs_path = std::env::current_dir()?.join(dir_name);
-    //println!("CD:{}", abs_path.display());
+    log_info(&format!("Repository: {}", cloned.path.display()));
+    log_info(&format!("Bare repository: {}", cloned.bare.display()));
+    for worktree in &cloned.worktrees {
+        log_info(&format!("Worktree: {}", worktree.display()));
+    }
+    change_directory(target);
 
     Ok(())
 }
 
 fn fetch_with_prune() -> Result<()> {
-    log_info("Fetching from origin with prune...");
-    run_command("git", &["fetch", "origin", "--prune"], None)?;
+    let remote = default_remote();
+    log_info(&format!("Fetching from {remote} with prune..."));
+    let tips = negotiation_tips();
+    let mut args = vec!["fetch", &remote, "--prune"];
+    args.extend(tips.iter().map(String::as_str));
+    offline::run_git(&args, None)?;
     log_info("Fetch completed.");
     Ok(())
 }
 
-fn check_git_repo() -> Result<()> {
-    let output = Command::new("git")
-        .args(["rev-parse", "--git-dir"])
-        .output()
-        .context("Failed to execute git rev-parse")?;
-
-    if !output.status.success() {
-        log_error("Not in a git repository");
-        process::exit(1);
+/// With `fetch_negotiation_tips`, `--negotiation-tip` arguments naming the
+/// worktree branches and their upstreams, so fetch only offers the remote
+/// commits from those instead of walking every local ref.
+fn negotiation_tips() -> Vec<String> {
+    if config::get().fetch_negotiation_tips != Some(true) || !doctor::git_supports((2, 19)) {
+        return Vec::new();
     }
+    let Ok(worktrees) = get_all_worktrees() else {
+        return Vec::new();
+    };
+    let with_worktree: HashSet<String> = worktrees.into_iter().map(|(branch, _)| branch).collect();
 
-    Ok(())
+    git_stdout(
+        &[
+            "for-each-ref",
+            "--format=%(refname)%00%(refname:lstrip=2)%00%(upstream)",
+            "refs/heads",
+        ],
+        None,
+    )
+    .unwrap_or_default()
+    .lines()
+    .filter_map(|line| {
+        let mut fields = line.split('\0');
+        let (reference, branch, upstream) = (fields.next()?, fields.next()?, fields.next()?);
+        with_worktree
+            .contains(branch)
+            .then(|| [reference.to_string(), upstream.to_string()])
+    })
+    .flatten()
+    .filter(|reference| !reference.is_empty())
+    // Worktrees of the same upstream share it
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .map(|reference| format!("--negotiation-tip={reference}"))
+    .collect()
 }
 
-fn get_worktree_root() -> Result<PathBuf> {
-    // Get the git common dir (where .bare is)
-    let output = Command::new("git")
-        .args(["rev-parse", "--git-common-dir"])
-        .output()
-        .context("Failed to execute git rev-parse")?;
-
-    if !output.status.success() {
-        log_error("Not in a git repository");
-        process::exit(1);
+/// Refreshes `<remote>/HEAD` of the default remote and, if the remote's
+/// default branch moved away from `previous`, offers to carry its worktree
+/// over to the new one.
+fn check_remote_head(previous: Option<&str>) -> Result<()> {
+    let remote = default_remote();
+    // Asks the remote; failing (e.g. offline) just keeps the stored default.
+    // Only stdout is captured, so credential prompts stay visible
+    let _ = run_command_output("git", &["remote", "set-head", &remote, "--auto"], None);
+
+    let (Some(previous), Some(current)) = (previous, default_branch_ref()) else {
+        return Ok(());
+    };
+    if previous == current {
+        return Ok(());
     }
 
-    let git_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    let git_path = PathBuf::from(git_dir);
-
-    // Get the parent directory (where worktrees are siblings)
-    let root = git_path
-        .parent()
-        .context("Could not determine worktree root")?
-        .to_path_buf();
-
-    Ok(root)
-}
+    let prefix = format!("{remote}/");
+    let old_branch = previous.strip_prefix(&prefix).unwrap_or(previous);
+    let new_branch = current.strip_prefix(&prefix).unwrap_or(&current);
+    log_info(&format!(
+        "The default branch of {remote} changed from {old_branch} to {new_branch}."
+    ));
 
-fn add_worktree(branch: &str, from: Option<&str>) -> Result<()> {
-    check_git_repo()?;
-    let root = get_worktree_root()?;
-    let worktree_path = root.join(branch);
+    let worktrees = get_all_worktrees()?;
+    let Some((_, old_path)) = worktrees.iter().find(|(branch, _)| branch == old_branch) else {
+        return Ok(());
+    };
+    if worktrees.iter().any(|(branch, _)| branch == new_branch) {
+        return Ok(());
+    }
 
-    // Check if worktree already exists
-    if worktree_path.exists() {
-        log_error(&format!(
-            "Directory '{}' already exists",
-            worktree_path.display()
+    if config::get().non_interactive_reason().is_some() {
+        log_info(&format!(
+            "Run `git-wt add {new_branch}` to create a worktree for it."
         ));
-        process::exit(1);
+        return Ok(());
     }
 
-    // Check if branch exists locally
-    let branch_exists = Command::new("git")
-        .args(["rev-parse", "--verify", &format!("refs/heads/{branch}")])
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false);
-
-    let default_ref = format!("origin/{branch}");
-    let base_ref = from.unwrap_or(&default_ref);
-
-    // Check if the base ref exists
-    let base_ref_exists = Command::new("git")
-        .args(["rev-parse", "--verify", base_ref])
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false);
-
-    log_info(&format!("Creating worktree '{branch}'..."));
+    let rename = format!("Rename the '{old_branch}' worktree and branch to '{new_branch}'");
+    let create = format!("Create a worktree for '{new_branch}'");
+    let keep = "Leave the worktrees as they are".to_string();
+    let selection = Select::new("", vec![rename.clone(), create.clone(), keep])
+        .with_render_config(create_select_render_config(false))
+        .without_help_message()
+        .ask();
+
+    let choice = prompt_result(selection)?;
+    if choice.as_ref() == Some(&rename) {
+        if ref_exists(&format!("refs/heads/{new_branch}")) {
+            log_error(&format!(
+                "A local branch '{new_branch}' already exists; create a worktree for it instead"
+            ));
+            process::exit(1);
+        }
 
-    if branch_exists {
-        run_command(
-            "git",
-            &["worktree", "add", worktree_path.to_str().unwrap(), branch],
-            None,
-        )?;
-    } else if base_ref_exists {
-        // Branch doesn't exist but base ref does, create from it
+        let root = get_worktree_root()?;
+        let new_path = worktree_path_for(&root, new_branch);
+        if let Some(conflict) = path_conflict(&new_path)? {
+            log_error(&conflict);
+            process::exit(1);
+        }
+        // Don't move the worktree from underneath ourselves
+        env::set_current_dir(&root)?;
+        // Renaming the branch also updates the worktree's HEAD
+        run_command("git", &["branch", "-m", old_branch, new_branch], None)?;
+        set_upstream(new_branch, &remote, new_branch)?;
+        if let Some(parent) = new_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
         run_command(
             "git",
-            &[
-                "worktree",
-                "add",
-                worktree_path.to_str().unwrap(),
-                "-b",
-                branch,
-                base_ref,
-            ],
+            &["worktree", "move", old_path, &new_path.to_string_lossy()],
             None,
         )?;
-    } else {
-        log_info(&format!(
-            "Note: {base_ref} doesn't exist, creating from HEAD"
-        ));
-        run_command(
-            "git",
-            &[
-                "worktree",
-                "add",
-                worktree_path.to_str().unwrap(),
-                "-b",
-                branch,
-                "HEAD",
-            ],
-            None,
+        log_info(&format!("Worktree moved to {}", new_path.display()));
+        change_directory(&new_path);
+    } else if choice.as_ref() == Some(&create) {
+        let path = add_worktree(&AddArgs {
+            branch: new_branch.to_string(),
+            ..AddArgs::default()
+        })?;
+        change_directory(&path);
+    }
+
+    Ok(())
+}
+
+fn update_worktree_branches() -> Result<()> {
+    let mut summary = Summary::default();
+
+    let worktrees = get_all_worktrees()?;
+    let total = worktrees.len();
+    events::started("update");
+    notify::started("fetch --update");
+    for (done, (branch, path)) in worktrees.into_iter().enumerate() {
+        let outcome = fast_forward_worktree(&branch, Path::new(&path))?;
+        events::progress(done + 1, total, &branch);
+        summary.push_described(&branch, &outcome);
+    }
+
+    finish_summary(&summary);
+    Ok(())
+}
+
+/// Fast-forwards the worktree's branch to its upstream if that is safe and
+/// returns a short description of what happened.
+fn fast_forward_worktree(branch: &str, path: &Path) -> Result<String> {
+    let Some(upstream) = git_stdout(
+        &[
+            "rev-parse",
+            "--abbrev-ref",
+            &format!("{branch}@{{upstream}}"),
+        ],
+        Some(path),
+    ) else {
+        return Ok("skipped: no upstream".to_string());
+    };
+
+    let Some(counts) = git_stdout(
+        &[
+            "rev-list",
+            "--left-right",
+            "--count",
+            &format!("{branch}...{upstream}"),
+        ],
+        Some(path),
+    ) else {
+        return Ok(format!("skipped: could not compare with {upstream}"));
+    };
+    let mut counts = counts.split_whitespace().map(str::parse::<usize>);
+    let (Some(Ok(ahead)), Some(Ok(behind))) = (counts.next(), counts.next()) else {
+        return Ok(format!("skipped: could not compare with {upstream}"));
+    };
+
+    if behind == 0 {
+        return Ok(if ahead == 0 {
+            "up to date".to_string()
+        } else {
+            format!("skipped: {ahead} ahead of {upstream}")
+        });
+    }
+    if ahead > 0 {
+        return Ok(format!(
+            "skipped: diverged from {upstream} ({ahead} ahead, {behind} behind)"
+        ));
+    }
+
+    let status = git_stdout(
+        &[
+            "--no-optional-locks",
+            "status",
+            "--porcelain",
+            "--untracked-files=no",
+        ],
+        Some(path),
+    );
+    if status.is_none_or(|status| !status.is_empty()) {
+        return Ok("skipped: uncommitted changes".to_string());
+    }
+
+    let merged = runner::run(
+        "git",
+        &["merge", "--ff-only", "--quiet", &upstream],
+        Some(path),
+        Capture::StdoutAndStderr,
+    )?;
+
+    Ok(if merged.status.success() {
+        format!("updated: fast-forwarded {behind} commit(s)")
+    } else {
+        "failed: fast-forward was rejected".to_string()
+    })
+}
+
+/// Runs a git command and returns its trimmed stdout, or `None` if it failed.
+fn git_stdout(args: &[&str], cwd: Option<&Path>) -> Option<String> {
+    let output = runner::run("git", args, cwd, Capture::StdoutAndStderr).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Git sets `GIT_PREFIX` when it runs an alias from a subdirectory after moving
+/// to the top level; goes back so relative paths mean what the user typed.
+/// A `!` alias may already have gone back itself (`cd "${GIT_PREFIX:-.}"`),
+/// so only when still at the top level.
+fn return_to_git_prefix() {
+    let Some(prefix) = env::var_os("GIT_PREFIX").filter(|prefix| !prefix.is_empty()) else {
+        return;
+    };
+    let at_top_level = git_stdout(&["rev-parse", "--show-toplevel"], None)
+        .zip(env::current_dir().ok())
+        .is_some_and(|(top_level, cwd)| {
+            fs::canonicalize(top_level).ok() == fs::canonicalize(cwd).ok()
+        });
+    if at_top_level && env::set_current_dir(&prefix).is_err() {
+        log_info(&format!(
+            "Could not change to GIT_PREFIX '{}'",
+            prefix.to_string_lossy()
+        ));
+    }
+}
+
+fn check_git_repo() {
+    // The version is looked up once, so this costs one `git --version`
+    doctor::require_minimum_git();
+    if git_common_dir().is_none() {
+        log_error("Not in a git repository");
+        process::exit(1);
+    }
+}
+
+/// Returns the git common dir (where .bare is), or `None` outside a repository.
+///
+/// Nearly every command needs this, so it's looked up once per working
+/// directory.
+fn git_common_dir() -> Option<PathBuf> {
+    static CACHE: Mutex<Option<(PathBuf, Option<PathBuf>)>> = Mutex::new(None);
+
+    let cwd = env::current_dir().ok()?;
+    let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((cached_cwd, common_dir)) = cache.as_ref()
+        && *cached_cwd == cwd
+    {
+        return common_dir.clone();
+    }
+
+    // Relative (e.g. "." inside .bare) unless made absolute, which would make
+    // the worktree root relative to wherever we were called from
+    let common_dir = read_common_dir(&cwd).or_else(|| {
+        git_stdout(&["rev-parse", "--git-common-dir"], None).map(|output| {
+            let git_dir = PathBuf::from(output);
+            git_dir.canonicalize().unwrap_or(git_dir)
+        })
+    });
+    *cache = Some((cwd, common_dir.clone()));
+    common_dir
+}
+
+/// Finds the common dir from the `.git` file of the enclosing worktree
+/// without running git. `None` when that isn't conclusive, e.g. inside
+/// `.bare` or with `GIT_DIR` and friends set.
+fn read_common_dir(cwd: &Path) -> Option<PathBuf> {
+    if git_env::overridden() {
+        return None;
+    }
+
+    let dot_git = cwd
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|p| p.exists())?;
+    if dot_git.is_dir() {
+        return dot_git.canonicalize().ok();
+    }
+
+    // "gitdir: <path>", relative to the directory holding the file
+    let contents = fs::read_to_string(&dot_git).ok()?;
+    let git_dir = dot_git
+        .parent()?
+        .join(contents.strip_prefix("gitdir:")?.trim());
+
+    // Linked worktrees point at .bare/worktrees/<name>, whose `commondir`
+    // leads back to .bare
+    let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(commondir) => git_dir.join(commondir.trim()),
+        Err(_) => git_dir,
+    };
+    common_dir.canonicalize().ok()
+}
+
+/// Whether the current directory is inside `.bare` (or another git dir)
+/// rather than a worktree.
+fn inside_git_dir() -> bool {
+    git_stdout(&["rev-parse", "--is-inside-git-dir"], None).as_deref() == Some("true")
+}
+
+fn get_worktree_root() -> Result<PathBuf> {
+    let Some(git_path) = git_common_dir() else {
+        log_error("Not in a git repository");
+        process::exit(1);
+    };
+
+    // Get the parent directory (where worktrees are siblings)
+    let root = git_path
+        .parent()
+        .context("Could not determine worktree root")?
+        .to_path_buf();
+
+    Ok(root)
+}
+
+/// Turns `add --again <branch>` into adding the first free `<branch>-wtN`
+/// branch, started from `branch` and tracking its upstream.
+fn again_args(args: &AddArgs) -> Result<AddArgs> {
+    check_git_repo();
+    let branch = args.branch.as_str();
+
+    let remote = default_remote();
+    let base = if ref_exists(&format!("refs/heads/{branch}")) {
+        branch.to_string()
+    } else if ref_exists(&format!("refs/remotes/{remote}/{branch}")) {
+        format!("{remote}/{branch}")
+    } else {
+        log_error(&format!("Branch '{branch}' does not exist"));
+        process::exit(1);
+    };
+
+    let upstream = git_stdout(
+        &[
+            "rev-parse",
+            "--abbrev-ref",
+            &format!("{branch}@{{upstream}}"),
+        ],
+        None,
+    )
+    .or_else(|| (base != branch).then(|| base.clone()));
+
+    let root = get_worktree_root()?;
+    let derived = (2..=u32::MAX)
+        .map(|n| format!("{branch}-wt{n}"))
+        .find(|name| {
+            !ref_exists(&format!("refs/heads/{name}")) && !worktree_path_for(&root, name).exists()
+        })
+        .unwrap_or_default();
+    log_info(&format!(
+        "Adding '{derived}' as another worktree of '{branch}'"
+    ));
+
+    Ok(AddArgs {
+        branch: derived,
+        from: Some(base),
+        no_track: upstream.is_none(),
+        track: upstream,
+        fetch: args.fetch,
+        devcontainer: args.devcontainer,
+        ..AddArgs::default()
+    })
+}
+
+/// Adds a worktree on a new branch from the commit `stash` was made on,
+/// applies the stash there, including what was staged, and drops it.
+fn add_from_stash(args: &AddArgs, stash: &str) -> Result<PathBuf> {
+    check_git_repo();
+    if ref_exists(&format!("refs/heads/{}", args.branch)) {
+        log_error(&format!(
+            "Branch '{}' already exists; --from-stash creates a new branch",
+            args.branch
+        ));
+        process::exit(1);
+    }
+    let Some(parent) = git_stdout(
+        &["rev-parse", "--verify", "--quiet", &format!("{stash}^1")],
+        None,
+    ) else {
+        log_error(&format!("No stash entry '{stash}'"));
+        process::exit(1);
+    };
+
+    let path = add_worktree(&AddArgs {
+        branch: args.branch.clone(),
+        from: Some(parent),
+        track: args.track.clone(),
+        no_track: args.no_track,
+        devcontainer: args.devcontainer,
+        quiet_checkout: args.quiet_checkout,
+        ..AddArgs::default()
+    })?;
+
+    log_info(&format!("Applying {stash}..."));
+    if !run_command_status(
+        "git",
+        &["stash", "apply", "--index", "--quiet", stash],
+        Some(&path),
+    )?
+    .success()
+    {
+        log_error(&format!(
+            "Failed to apply {stash} in {}; the stash was kept",
+            path.display()
+        ));
+        process::exit(1);
+    }
+    run_command("git", &["stash", "drop", "--quiet", stash], None)?;
+    log_info(&format!("Applied and dropped {stash}."));
+    audit::record(&format!("applied {stash} to {}", args.branch));
+    Ok(path)
+}
+
+/// Adds a worktree on a new branch created at the merge base of `refs`.
+fn add_at_merge_base(args: &AddArgs, refs: &[String]) -> Result<PathBuf> {
+    check_git_repo();
+    if ref_exists(&format!("refs/heads/{}", args.branch)) {
+        log_error(&format!(
+            "Branch '{}' already exists; --merge-base creates a new branch",
+            args.branch
+        ));
+        process::exit(1);
+    }
+    let [a, b] = refs else {
+        unreachable!("clap takes exactly two refs");
+    };
+    for name in [a, b] {
+        if !ref_exists(name) {
+            log_error(&format!("Unknown ref '{name}'"));
+            process::exit(1);
+        }
+    }
+    let Some(base) = git_stdout(&["merge-base", a, b], None) else {
+        log_error(&format!("'{a}' and '{b}' have no common ancestor"));
+        process::exit(1);
+    };
+    log_info(&format!(
+        "Merge base of {a} and {b} is {}.",
+        &base[..base.len().min(12)]
+    ));
+
+    add_worktree(&AddArgs {
+        branch: args.branch.clone(),
+        from: Some(base),
+        track: args.track.clone(),
+        no_track: args.no_track,
+        devcontainer: args.devcontainer,
+        no_checkout: args.no_checkout,
+        quiet_checkout: args.quiet_checkout,
+        ..AddArgs::default()
+    })
+}
+
+/// Where the worktree for `branch` lives under the worktree root.
+fn worktree_path_for(root: &Path, branch: &str) -> PathBuf {
+    match &config::get().worktree_dir {
+        Some(dir) => root.join(dir).join(branch),
+        // In a standard clone the root is the main checkout, so worktrees go
+        // next to it instead of inside it
+        None if is_standard_clone() => {
+            let name = root.file_name().unwrap_or_default().to_string_lossy();
+            root.with_file_name(format!("{name}-worktrees"))
+                .join(branch)
+        }
+        None => root.join(branch),
+    }
+}
+
+/// Why a new worktree can't go to `path`: the directory exists, or it would
+/// be nested in or contain another worktree, or only differ from another's
+/// path in case. Branches such as `feature/api` and `feature/api/v2`, or
+/// `Fix` and `fix`, would otherwise share directories.
+fn path_conflict(path: &Path) -> Result<Option<String>> {
+    let display = path.display();
+    let root = get_worktree_root()?;
+    let worktrees = worktree::list()?;
+    let lowercase = path.to_string_lossy().to_lowercase();
+    // Except for a standard clone's main checkout, which may hold the
+    // worktree_dir, worktrees can't contain each other
+    for worktree in &worktrees {
+        let other = Path::new(&worktree.path);
+        let name = worktree.name();
+        let conflict = if same_path(path, other) {
+            format!("'{display}' is already the worktree of '{name}'")
+        } else if path.starts_with(other) && !same_path(other, &root) {
+            format!(
+                "'{display}' would be inside the worktree of '{name}' at {}",
+                other.display()
+            )
+        } else if other.starts_with(path) {
+            format!(
+                "'{display}' would contain the worktree of '{name}' at {}",
+                other.display()
+            )
+        } else if worktree.path.to_lowercase() == lowercase {
+            format!(
+                "'{display}' only differs in case from the worktree of '{name}', which is the same directory on case-insensitive file systems"
+            )
+        } else {
+            continue;
+        };
+        return Ok(Some(conflict));
+    }
+
+    Ok(path
+        .exists()
+        .then(|| format!("Directory '{display}' already exists")))
+}
+
+/// Whether this is a regular clone with its main checkout, rather than a
+/// bare repository like the `.bare` layout. `--is-bare-repository` is false
+/// in any worktree, so this asks the repository's `core.bare`.
+fn is_standard_clone() -> bool {
+    git_common_dir().is_some() && git_config_bool("core.bare") != Some(true)
+}
+
+/// The base `args` asks for with `--interactive-base` or `--from`, with a
+/// misspelled `--from` replaced by the ref it most likely meant.
+fn explicit_base(args: &AddArgs, branch_exists: bool) -> Result<Option<String>> {
+    let from = if args.interactive_base && !branch_exists {
+        Some(pick_base_ref()?)
+    } else {
+        args.from.clone()
+    };
+    match from {
+        Some(from) if !ref_exists(&from) => suggest_base_ref(&from).map(Some),
+        from => Ok(from),
+    }
+}
+
+/// The `git worktree add` command line for `args`, creating the branch from
+/// `base_ref` unless it exists.
+fn worktree_add_args<'a>(
+    args: &'a AddArgs,
+    path: &'a str,
+    branch_exists: bool,
+    base_ref: &'a str,
+) -> Vec<&'a str> {
+    let branch = args.branch.as_str();
+    let mut git_args = vec!["worktree", "add"];
+    if args.no_track {
+        git_args.push("--no-track");
+    }
+    if args.no_checkout {
+        git_args.push("--no-checkout");
+    }
+    // Checkout progress redraws a line in place, which screen readers repeat
+    if args.quiet_checkout || accessible::enabled() {
+        git_args.push("--quiet");
+    }
+    git_args.push(path);
+
+    if branch_exists {
+        git_args.push(branch);
+    } else if ref_exists(base_ref) {
+        // Branch doesn't exist but base ref does, create from it
+        git_args.extend(["-b", branch, base_ref]);
+    } else {
+        log_info(&format!(
+            "Note: {base_ref} doesn't exist, creating from HEAD"
+        ));
+        git_args.extend(["-b", branch, "HEAD"]);
+    }
+    git_args
+}
+
+/// Records who created the worktree at `path` and shares it with the group
+/// if configured.
+fn record_new_worktree(path: &Path) -> Result<()> {
+    owner::record(path)?;
+    shared::worktree(path)
+}
+
+fn add_worktree(args: &AddArgs) -> Result<PathBuf> {
+    if args.again {
+        return add_worktree(&again_args(args)?);
+    }
+    if let Some(stash) = &args.from_stash {
+        return add_from_stash(args, stash);
+    }
+    if let Some(refs) = &args.merge_base {
+        return add_at_merge_base(args, refs);
+    }
+
+    let branch = args.branch.as_str();
+    check_git_repo();
+    let root = get_worktree_root()?;
+    let worktree_path = worktree_path_for(&root, branch);
+
+    if let Some(conflict) = path_conflict(&worktree_path)? {
+        log_error(&conflict);
+        process::exit(1);
+    }
+
+    // Check if branch exists locally
+    let branch_exists = ref_exists(&format!("refs/heads/{branch}"));
+
+    let (remote, use_remote) = remote_for(branch, branch_exists);
+    let remote_ref = format!("{remote}/{branch}");
+
+    let from = explicit_base(args, branch_exists)?;
+    let from = from.as_deref();
+
+    // The branch often exists on the remote but hasn't been fetched yet
+    if !branch_exists
+        && use_remote
+        && from.is_none()
+        && !ref_exists(&remote_ref)
+        && (args.fetch || confirm_fetch(&remote_ref)?)
+    {
+        log_info(&format!("Fetching '{branch}' from {remote}..."));
+        if !offline::run_git_status(&["fetch", &remote, branch], None)?.success() {
+            log_info(&format!("Note: {remote} has no branch '{branch}'"));
+        }
+    }
+    let base_ref = match (from, &config::get().default_base) {
+        (Some(from), _) => from,
+        (None, Some(default_base)) if !use_remote || !ref_exists(&remote_ref) => default_base,
+        (None, None) if !use_remote => "HEAD",
+        (None, _) => &remote_ref,
+    };
+
+    // Resolve the upstream up front so a typo doesn't leave a half-configured
+    // branch
+    let upstream = upstream_for(args, &remote, use_remote);
+
+    let path = worktree_path.to_str().unwrap();
+    let git_args = worktree_add_args(args, path, branch_exists, base_ref);
+
+    let context = HookContext {
+        branch,
+        path: &worktree_path,
+        root: &root,
+        // What `worktree_add_args` settled on
+        base: match (branch_exists, ref_exists(base_ref)) {
+            (true, _) => None,
+            (false, true) => Some(base_ref),
+            (false, false) => Some("HEAD"),
+        },
+    };
+    if explain::enabled() {
+        explain_add(args, &context, &git_args, upstream.as_ref())?;
+    }
+    hooks::run(HookEvent::PreAdd, &context)?;
+    create_worktree(branch, &git_args, branch_exists, upstream)?;
+    record_new_worktree(&worktree_path)?;
+
+    // Env files and hooks expect the files, so they wait for `git-wt checkout`
+    if args.no_checkout {
+        log_info(&format!(
+            "Worktree registered without files; run `git-wt checkout {branch}` to check them out."
+        ));
+        return Ok(worktree_path);
+    }
+    log_info("Worktree created.");
+    audit::record(&format!(
+        "added worktree {} ({branch})",
+        worktree_path.display()
+    ));
+
+    index::assign(&worktree_path)?;
+    env_files::render(&context)?;
+    hooks::run(HookEvent::PostAdd, &context)?;
+
+    //println!("CD:{}", worktree_path.display());
+
+    Ok(worktree_path)
+}
+
+/// Runs `git worktree add` with `git_args` and sets the upstream of a newly
+/// created branch.
+fn create_worktree(
+    branch: &str,
+    git_args: &[&str],
+    branch_exists: bool,
+    upstream: Option<(String, String)>,
+) -> Result<()> {
+    log_info(&format!("Creating worktree '{branch}'..."));
+    run_command("git", git_args, None)?;
+
+    // Branches created from anything but <remote>/<branch> get no upstream (or
+    // the base as upstream), which breaks a plain `git push`
+    if !branch_exists && let Some((remote, name)) = upstream {
+        if ref_exists(&format!("refs/remotes/{remote}/{name}")) {
+            set_upstream(branch, &remote, &name)?;
+            log_info(&format!("Branch '{branch}' tracks {remote}/{name}."));
+        } else {
+            track_on_first_push(branch, &remote, &name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Sets up `branch` to track `<remote>/<name>` once pushed. Configuring an
+/// upstream that doesn't exist yet would show the branch as gone, which
+/// `clean` takes for merged and deleted.
+fn track_on_first_push(branch: &str, remote: &str, name: &str) -> Result<()> {
+    if name != branch || !doctor::git_supports((2, 37)) {
+        log_info(&format!(
+            "{remote}/{name} doesn't exist yet; `git push -u {remote} {branch}:{name}` creates and tracks it."
+        ));
+        return Ok(());
+    }
+
+    if git_config_bool("push.autoSetupRemote").is_none() {
+        run_command("git", &["config", "push.autoSetupRemote", "true"], None)?;
+    }
+    if remote != "origin" {
+        run_command(
+            "git",
+            &["config", &format!("branch.{branch}.pushRemote"), remote],
+            None,
+        )?;
+    }
+    log_info(&format!(
+        "Branch '{branch}' will track {remote}/{name} once pushed."
+    ));
+    Ok(())
+}
+
+/// Fills in a worktree added with `--no-checkout`, keeping any files already
+/// placed there and honoring sparse-checkout, then renders its env files and
+/// runs the post-add hooks.
+fn checkout_worktree(branch: Option<&str>) -> Result<()> {
+    check_git_repo();
+    let (branch, path) = resolve_worktree(branch)?;
+    let path = PathBuf::from(path);
+
+    log_info(&format!("Checking out '{branch}'..."));
+    // Fails instead of overwriting untracked files in the way
+    run_command("git", &["read-tree", "-mu", "HEAD"], Some(&path))?;
+    log_info("Checkout completed.");
+
+    let root = get_worktree_root()?;
+    let context = HookContext {
+        branch: &branch,
+        path: &path,
+        root: &root,
+        base: None,
+    };
+    env_files::render(&context)?;
+    hooks::run(HookEvent::PostAdd, &context)
+}
+
+/// The upstream a new branch for `args` gets, if any;
+/// branch.autoSetupMerge=false means none unless asked for.
+fn upstream_for(args: &AddArgs, remote: &str, use_remote: bool) -> Option<(String, String)> {
+    match (&args.track, args.no_track) {
+        (_, true) => None,
+        (Some(track), false) => Some(parse_upstream(track)),
+        (None, false) if !use_remote || git_config_bool("branch.autoSetupMerge") == Some(false) => {
+            None
+        }
+        (None, false) => Some((remote.to_string(), args.branch.clone())),
+    }
+}
+
+fn explain_add(
+    args: &AddArgs,
+    context: &HookContext,
+    git_args: &[&str],
+    upstream: Option<&(String, String)>,
+) -> Result<()> {
+    let branch = args.branch.as_str();
+    let mut plan = explain::Plan::default();
+    match git_args.iter().position(|arg| *arg == "-b") {
+        Some(i) => plan.note(format!(
+            "No local branch '{branch}'; it will be created from {}",
+            git_args.get(i + 2).unwrap_or(&"HEAD")
+        )),
+        None => plan.note(format!("Found local branch '{branch}'")),
+    }
+    if let Some((remote, name)) = upstream
+        && git_args.contains(&"-b")
+    {
+        plan.note(format!("It will track {remote}/{name}"));
+    }
+    plan.note(format!("Worktree: {}", context.path.display()));
+    plan.hooks(HookEvent::PreAdd, context);
+    plan.command("git", git_args);
+    if args.no_checkout {
+        plan.note("Env files and post_add hooks wait for `git-wt checkout`");
+    } else {
+        for target in config::get().env_files.files.keys() {
+            plan.note(format!("Render env file {}", target.display()));
+        }
+        plan.hooks(HookEvent::PostAdd, context);
+    }
+    explain::confirm(&plan)?;
+    Ok(())
+}
+
+/// Local branches, remote branches and tags (newest first), by short name:
+/// everything a new branch is likely to start from.
+fn base_refs() -> Vec<String> {
+    let refs = |pattern: &str, sort: &str| -> Vec<String> {
+        git_stdout(
+            &["for-each-ref", sort, "--format=%(refname:short)", pattern],
+            None,
+        )
+        .unwrap_or_default()
+        .lines()
+        .filter(|name| !name.ends_with("/HEAD"))
+        .map(str::to_string)
+        .collect()
+    };
+    let mut bases = refs("refs/heads", "--sort=refname");
+    bases.extend(refs("refs/remotes", "--sort=refname"));
+    bases.extend(refs("refs/tags", "--sort=-creatordate"));
+    bases
+}
+
+/// Finds what a `--from` that names no ref was meant to be, such as `v2.3.1`
+/// for `2.3.1`, asking if several refs come close.
+fn suggest_base_ref(from: &str) -> Result<String> {
+    let mut matcher = Matcher::new(nucleo_matcher::Config::DEFAULT);
+    let mut scored: Vec<(u16, String)> = base_refs()
+        .into_iter()
+        .filter_map(|name| fuzzy_score(from, &name, &mut matcher).map(|score| (score, name)))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    let suggestions: Vec<String> = scored.into_iter().take(10).map(|(_, name)| name).collect();
+
+    if suggestions.is_empty() {
+        log_error(&format!("'{from}' is not a branch, remote branch or tag"));
+        process::exit(1);
+    }
+    if let [only] = suggestions.as_slice() {
+        log_info(&format!("Note: {from} doesn't exist, using {only}"));
+        return Ok(only.clone());
+    }
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_error(&format!(
+            "'{from}' is not a branch, remote branch or tag; did you mean one of: {}?",
+            suggestions.join(", ")
+        ));
+        log_info(&format!("Cannot ask which one because {reason}."));
+        process::exit(1);
+    }
+
+    eprintln!("'{from}' doesn't exist. Create the branch from:");
+    let selection = Select::new("", suggestions)
+        .with_page_size(page_size())
+        .with_render_config(create_select_render_config(false))
+        .without_help_message()
+        .ask();
+    let Some(selected) = prompt_result(selection)? else {
+        eprintln!("Cancelled.");
+        process::exit(0);
+    };
+    Ok(selected)
+}
+
+/// Lets the user pick a base ref among remote branches and tags, most recent
+/// first, with the cursor on the remote's default branch.
+fn pick_base_ref() -> Result<String> {
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_error(&format!(
+            "Cannot pick a base ref because {reason}; use --from instead"
+        ));
+        process::exit(1);
+    }
+
+    let refs: Vec<String> = git_stdout(
+        &[
+            "for-each-ref",
+            "--sort=-creatordate",
+            "--format=%(refname:short)",
+            "refs/remotes",
+            "refs/tags",
+        ],
+        None,
+    )
+    .unwrap_or_default()
+    .lines()
+    .filter(|name| !name.ends_with("/HEAD"))
+    .map(str::to_string)
+    .collect();
+
+    if refs.is_empty() {
+        log_error("No remote branches or tags to choose from");
+        process::exit(1);
+    }
+
+    let starting_cursor = default_branch_ref()
+        .and_then(|default| refs.iter().position(|name| *name == default))
+        .unwrap_or(0);
+
+    let matcher = RefCell::new(Matcher::new(nucleo_matcher::Config::DEFAULT));
+    let filter_scorer = picker_scorer(&matcher, false);
+
+    eprintln!("Create the branch from:");
+    let selection = Select::new("", refs)
+        .with_page_size(page_size())
+        .with_render_config(create_select_render_config(false))
+        .with_scorer(&filter_scorer)
+        .with_starting_cursor(starting_cursor)
+        .without_help_message()
+        .ask();
+
+    let Some(selected) = prompt_result(selection)? else {
+        eprintln!("Cancelled.");
+        process::exit(0);
+    };
+    Ok(selected)
+}
+
+/// Returns the remote-tracking ref of the default remote's default branch,
+/// e.g. `origin/main`, falling back to common names when `<remote>/HEAD`
+/// isn't set.
+fn default_branch_ref() -> Option<String> {
+    let remote = default_remote();
+    git_stdout(
+        &[
+            "symbolic-ref",
+            "--quiet",
+            "--short",
+            &format!("refs/remotes/{remote}/HEAD"),
+        ],
+        None,
+    )
+    .or_else(|| {
+        ["main", "master"]
+            .into_iter()
+            .map(|name| format!("{remote}/{name}"))
+            .find(|name| ref_exists(name))
+    })
+}
+
+/// Asks whether to fetch a remote ref that isn't known locally. Without a
+/// terminal the answer is no, keeping `add` usable from scripts.
+fn confirm_fetch(remote_ref: &str) -> Result<bool> {
+    if config::get().non_interactive_reason().is_some() {
+        return Ok(false);
+    }
+
+    let prompt = format!(
+        "{remote_ref} doesn't exist locally. Fetch it from {}?",
+        default_remote()
+    );
+    let confirmed = Confirm::new("")
+        .with_default(true)
+        .with_render_config(create_confirm_render_config(&prompt))
+        .ask();
+
+    Ok(prompt_result(confirmed)? == Some(true))
+}
+
+/// A boolean git config value, `None` when unset or not a boolean.
+fn git_config_bool(key: &str) -> Option<bool> {
+    match git_stdout(&["config", "--type=bool", "--get", key], None)?.as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// The remote git-wt fetches from and pushes to, and `add` branches from and
+/// tracks: `default_remote`, git's own `checkout.defaultRemote`, or origin.
+fn default_remote() -> String {
+    config::get()
+        .default_remote
+        .clone()
+        .or_else(|| git_stdout(&["config", "--get", "checkout.defaultRemote"], None))
+        .unwrap_or_else(|| "origin".to_string())
+}
+
+/// The remote `add` looks for `branch` on, and whether to base new branches
+/// on it at all.
+fn remote_for(branch: &str, branch_exists: bool) -> (String, bool) {
+    // Like `git worktree add` with worktree.guessRemote, a same-named branch
+    // on another remote counts when it's the only one
+    let guess_remote = git_config_bool("worktree.guessRemote");
+    let mut remote = default_remote();
+    if guess_remote == Some(true)
+        && !branch_exists
+        && !ref_exists(&format!("refs/remotes/{remote}/{branch}"))
+        && let Some(other) = only_remote_with(branch)
+    {
+        remote = other;
+    }
+
+    // Turned off explicitly, new branches start from default_base or HEAD
+    // instead, untracked, as they do in git
+    (remote, guess_remote != Some(false))
+}
+
+/// The remote with a `<remote>/<branch>`, if exactly one has it.
+fn only_remote_with(branch: &str) -> Option<String> {
+    let refs = git_stdout(
+        &[
+            "for-each-ref",
+            "--format=%(refname:lstrip=2)",
+            &format!("refs/remotes/*/{branch}"),
+        ],
+        None,
+    )?;
+    let mut remotes = refs
+        .lines()
+        .filter_map(|name| name.strip_suffix(&format!("/{branch}")));
+    match (remotes.next(), remotes.next()) {
+        (Some(remote), None) => Some(remote.to_string()),
+        _ => None,
+    }
+}
+
+/// Splits `<remote>/<branch>` at the longest configured remote name.
+fn parse_upstream(upstream: &str) -> (String, String) {
+    let remotes = git_stdout(&["remote"], None).unwrap_or_default();
+    let parsed = remotes
+        .lines()
+        .filter_map(|remote| {
+            let name = upstream.strip_prefix(remote)?.strip_prefix('/')?;
+            Some((remote, name))
+        })
+        .max_by_key(|(remote, _)| remote.len());
+
+    if let Some((remote, name)) = parsed.filter(|(_, name)| !name.is_empty()) {
+        (remote.to_string(), name.to_string())
+    } else {
+        log_error(&format!(
+            "'{upstream}' is not of the form <remote>/<branch> with a configured remote"
+        ));
+        process::exit(1);
+    }
+}
+
+/// Points the branch at `<remote>/<name>` via config, which unlike
+/// `git branch --set-upstream-to` works before the remote branch exists.
+fn set_upstream(branch: &str, remote: &str, name: &str) -> Result<()> {
+    run_command(
+        "git",
+        &["config", &format!("branch.{branch}.remote"), remote],
+        None,
+    )?;
+    run_command(
+        "git",
+        &[
+            "config",
+            &format!("branch.{branch}.merge"),
+            &format!("refs/heads/{name}"),
+        ],
+        None,
+    )
+}
+
+fn ref_exists(reference: &str) -> bool {
+    runner::run(
+        "git",
+        &["rev-parse", "--verify", "--quiet", reference],
+        None,
+        Capture::StdoutAndStderr,
+    )
+    .is_ok_and(|output| output.status.success())
+}
+
+/// The worktrees that are on a branch, as `(branch, path)`. `worktree::list`
+/// also has detached ones, and whether worktrees are locked or prunable.
+fn get_all_worktrees() -> Result<Vec<(String, String)>> {
+    Ok(worktree::list()?
+        .into_iter()
+        .filter_map(|worktree| Some((worktree.branch?, worktree.path)))
+        .collect())
+}
+
+fn find_worktree_path(branch: &str, interactive: bool) -> Result<Option<String>> {
+    let worktrees = get_all_worktrees()?;
+
+    if worktrees.is_empty() {
+        return Ok(None);
+    }
+
+    // First try exact match
+    for (wt_branch, wt_path) in &worktrees {
+        if wt_branch == branch {
+            return Ok(Some(wt_path.clone()));
+        }
+    }
+
+    // A number refers to the worktree's position in `git-wt list`
+    if let Ok(index) = branch.parse::<usize>()
+        && let Some((_, wt_path)) = index.checked_sub(1).and_then(|i| worktrees.get(i))
+    {
+        return Ok(Some(wt_path.clone()));
+    }
+
+    // If no exact match, try fuzzy matching
+    let mut matcher = Matcher::new(nucleo_matcher::Config::DEFAULT);
+    let mut scored: Vec<(u16, String, String)> = Vec::new();
+
+    for (wt_branch, wt_path) in worktrees {
+        if let Some(score) = fuzzy_score(branch, &wt_branch, &mut matcher) {
+            scored.push((score, wt_branch, wt_path));
+        }
+    }
+
+    if scored.is_empty() {
+        return Ok(None);
+    }
+
+    scored.sort_by_key(|(score, _, _)| std::cmp::Reverse(*score));
+
+    if scored.len() == 1 {
+        return Ok(Some(scored[0].2.clone()));
+    }
+
+    let prompt_unavailable = config::get().non_interactive_reason();
+    if !interactive || prompt_unavailable.is_some() {
+        let names: Vec<&str> = scored.iter().map(|(_, name, _)| name.as_str()).collect();
+        log_error(&format!(
+            "'{branch}' matches multiple worktrees: {}",
+            names.join(", ")
+        ));
+        if let Some(reason) = prompt_unavailable.filter(|_| interactive) {
+            log_info(&format!(
+                "Cannot ask which one because {reason}; use the full branch name or its index from `git-wt list`."
+            ));
+        }
+        process::exit(1);
+    }
+
+    let options: Vec<(String, String)> = scored
+        .iter()
+        .map(|(_, name, path)| (name.clone(), path.clone()))
+        .collect();
+    let branch_names: Vec<String> = options.iter().map(|(name, _)| name.clone()).collect();
+
+    let matcher = RefCell::new(matcher);
+    let filter_scorer = picker_scorer(&matcher, true);
+
+    eprintln!("'{branch}' matches multiple worktrees.");
+    let selection = Select::new("", branch_names)
+        .with_page_size(page_size())
+        .with_render_config(create_select_render_config(true))
+        .with_scorer(&filter_scorer)
+        .with_starting_filter_input(branch)
+        .without_help_message()
+        .ask();
+
+    let Some(selected) = prompt_result(selection)? else {
+        eprintln!("Cancelled.");
+        process::exit(0);
+    };
+
+    for (name, path) in options {
+        if name == selected {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+/// Treats a cancelled prompt as `None` and any other prompt failure as an error.
+fn prompt_result<T>(result: Result<Option<T>, InquireError>) -> Result<Option<T>> {
+    match result {
+        Ok(answer) => Ok(answer),
+        Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => Ok(None),
+        Err(e) => Err(e).context("Failed to show prompt"),
+    }
+}
+
+/// Builds a Select scorer so typing in a picker narrows it with the same fuzzy
+/// matching used for branch arguments. In `numbered` pickers typing an entry's
+/// number selects just that entry.
+fn picker_scorer(
+    matcher: &RefCell<Matcher>,
+    numbered: bool,
+) -> impl Fn(&str, &String, &str, usize) -> Option<i64> + '_ {
+    move |input, _, name, index| {
+        if input.is_empty() {
+            return Some(0);
+        }
+        if numbered && let Ok(number) = input.parse::<usize>() {
+            return (number == index + 1).then_some(0);
+        }
+        fuzzy_score(input, name, &mut matcher.borrow_mut()).map(i64::from)
+    }
+}
+
+fn fuzzy_score(query: &str, candidate: &str, matcher: &mut Matcher) -> Option<u16> {
+    let pattern = Atom::new(
+        query,
+        CaseMatching::Smart,
+        Normalization::Smart,
+        AtomKind::Fuzzy,
+        false,
+    );
+    let mut buf = Vec::new();
+    pattern.score(Utf32Str::new(candidate, &mut buf), matcher)
+}
+
+fn get_current_worktree_branch() -> Result<Option<String>> {
+    // The bare repository may be listed like a worktree, but it never is one
+    if inside_git_dir() {
+        return Ok(None);
+    }
+
+    // The toplevel also covers being in a subdirectory of the worktree
+    let Some(toplevel) = git_stdout(&["rev-parse", "--show-toplevel"], None) else {
+        return Ok(None);
+    };
+
+    Ok(get_all_worktrees()?
+        .into_iter()
+        .find(|(_, path)| same_path(Path::new(path), Path::new(&toplevel)))
+        .map(|(branch, _)| branch))
+}
+
+/// Whether two paths name the same directory, even when spelled differently
+/// (symlinked /tmp on macOS, WSL mounts, network shares).
+fn same_path(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let (Ok(a), Ok(b)) = (fs::metadata(a), fs::metadata(b)) {
+            return a.dev() == b.dev() && a.ino() == b.ino();
+        }
+    }
+
+    matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+fn remove_worktree(
+    branch: Option<&str>,
+    force: bool,
+    clean_artifacts: bool,
+    yes: bool,
+) -> Result<()> {
+    check_git_repo();
+
+    let (branch, worktree_path) = resolve_worktree(branch)?;
+
+    if clean_artifacts {
+        list_artifacts(Path::new(&worktree_path));
+    }
+
+    if !force && let Some(reason) = owner::foreign(Path::new(&worktree_path)) {
+        log_error(&format!(
+            "Worktree '{branch}' belongs to someone else: {reason}; pass --force to remove it anyway"
+        ));
+        process::exit(1);
+    }
+
+    let root = get_worktree_root()?;
+    let hook_context = HookContext {
+        branch: &branch,
+        path: Path::new(&worktree_path),
+        root: &root,
+        base: None,
+    };
+    let mut args = vec!["worktree", "remove"];
+    if force || clean_artifacts {
+        args.push("--force");
+    }
+    args.push(&worktree_path);
+
+    let mut plan = explain::Plan::default();
+    plan.note(format!("Worktree of '{branch}': {worktree_path}"));
+    plan.hooks(HookEvent::PreRemove, &hook_context);
+    plan.command("git", &args);
+    plan.hooks(HookEvent::PostRemove, &hook_context);
+    let explained = explain::confirm(&plan)?;
+
+    if !yes
+        && !explained
+        && removal_needs_confirmation(Path::new(&worktree_path))
+        && !confirm_removal("Are you sure you want to remove the worktree?")?
+    {
+        process::exit(0);
+    }
+
+    hooks::run(HookEvent::PreRemove, &hook_context)?;
+
+    run_command("git", &args, None)?;
+
+    log_info(format!("Worktree '{}' removed.", &branch).as_str());
+    audit::record(&format!("removed worktree {worktree_path} ({branch})"));
+
+    hooks::run(HookEvent::PostRemove, &hook_context)?;
+
+    Ok(())
+}
+
+/// Shows the untracked and ignored files `rm --clean-artifacts` will delete,
+/// exiting instead if tracked files have changes that would be lost too.
+fn list_artifacts(worktree: &Path) {
+    let Some(status) = git_stdout(
+        &[
+            "--no-optional-locks",
+            "status",
+            "--porcelain",
+            "-z",
+            "--ignored",
+        ],
+        Some(worktree),
+    ) else {
+        log_error("Failed to read the worktree status");
+        process::exit(1);
+    };
+
+    // "?? path" for untracked and "!! path" for ignored entries; directories
+    // are listed once, with a trailing slash
+    let mut artifacts = Vec::new();
+    for entry in status.split('\0').filter(|entry| !entry.is_empty()) {
+        match entry.split_at_checked(3) {
+            Some(("?? ", path)) => artifacts.push((path, false)),
+            Some(("!! ", path)) => artifacts.push((path, true)),
+            _ => {
+                log_error(
+                    "Tracked files have uncommitted changes; --clean-artifacts only deletes untracked files (use --force to discard everything)",
+                );
+                process::exit(1);
+            }
+        }
+    }
+
+    if artifacts.is_empty() {
+        return;
+    }
+
+    let sized: Vec<(u64, &str, bool)> = artifacts
+        .into_iter()
+        .map(|(path, ignored)| (list::disk_size(&worktree.join(path)), path, ignored))
+        .collect();
+    let total: u64 = sized.iter().map(|(size, _, _)| size).sum();
+
+    log_info("These untracked files will be deleted:");
+    for (size, path, ignored) in &sized {
+        let note = if *ignored { "  (ignored)" } else { "" };
+        log_info(&format!("  {:>10}  {path}{note}", format::size(*size)));
+    }
+    log_info(&format!("  {:>10}  total", format::size(total)));
+}
+
+/// Whether `rm` asks before removing the worktree at `path`, as
+/// `rm.confirm` says.
+fn removal_needs_confirmation(path: &Path) -> bool {
+    match config::get().rm.confirm.unwrap_or_default() {
+        RmConfirm::Always => true,
+        RmConfirm::Never => false,
+        RmConfirm::UnpushedOnly => list::is_dirty(path) || has_unpushed_commits(path),
+    }
+}
+
+/// Whether the worktree at `path` has commits no remote-tracking branch
+/// contains; also when that can't be told.
+fn has_unpushed_commits(path: &Path) -> bool {
+    git_stdout(
+        &["rev-list", "--count", "HEAD", "--not", "--remotes"],
+        Some(path),
+    )
+    .is_none_or(|count| count != "0")
+}
+
+/// Asks for confirmation before removing worktrees, exiting with an error
+/// when no prompt can be shown.
+fn confirm_removal(prompt: &str) -> Result<bool> {
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_error(&format!(
+            "Cannot confirm removal because {reason}; pass --yes to remove without confirmation"
+        ));
+        process::exit(1);
+    }
+
+    let confirmed = Confirm::new("")
+        .with_default(false)
+        .with_render_config(create_confirm_render_config(prompt))
+        .ask();
+
+    Ok(prompt_result(confirmed)? == Some(true))
+}
+
+/// Returns the worktrees whose branch tracks an upstream that no longer exists.
+fn get_gone_worktrees() -> Result<Vec<(String, String)>> {
+    let gone = gone_branches();
+    Ok(get_all_worktrees()?
+        .into_iter()
+        .filter(|(branch, _)| gone.contains(branch))
+        .collect())
+}
+
+/// Returns the local branches whose configured upstream was deleted.
+fn gone_branches() -> HashSet<String> {
+    let Some(refs) = git_stdout(
+        &[
+            "for-each-ref",
+            "--format=%(refname:short) %(upstream:remotename) %(upstream:track)",
+            "refs/heads",
+        ],
+        None,
+    ) else {
+        return HashSet::new();
+    };
+
+    // An upstream on a remote never fetched from can't have been deleted, it
+    // just never existed
+    let fetched = git_stdout(
+        &[
+            "for-each-ref",
+            "--format=%(refname:lstrip=2)",
+            "refs/remotes",
+        ],
+        None,
+    )
+    .unwrap_or_default();
+    let fetched: HashSet<&str> = fetched
+        .lines()
+        .filter_map(|name| name.split_once('/'))
+        .map(|(remote, _)| remote)
+        .collect();
+
+    refs.lines()
+        .filter_map(|line| line.strip_suffix(" [gone]"))
+        .filter_map(|line| line.rsplit_once(' '))
+        .filter(|(_, remote)| fetched.contains(remote))
+        .map(|(branch, _)| branch.to_string())
+        .collect()
+}
+
+fn report_gone_worktrees() -> Result<()> {
+    let gone = get_gone_worktrees()?;
+    if gone.is_empty() {
+        return Ok(());
+    }
+
+    log_info("Upstream branch deleted for:");
+    for (branch, path) in &gone {
+        log_info(&format!("  {branch}  ({path})"));
+    }
+    log_info("Run `git-wt clean` to remove these worktrees.");
+
+    Ok(())
+}
+
+/// The worktrees `clean` removes. Those it leaves alone, locked ones and
+/// without `force` other users', are added to `summary` as skipped.
+fn removable_gone_worktrees(force: bool, summary: &mut Summary) -> Result<Vec<(String, String)>> {
+    let listed = worktree::list()?;
+    let prunable = listed
+        .iter()
+        .filter(|worktree| worktree.prunable.is_some())
+        .count();
+    if prunable > 0 {
+        log_info(&format!(
+            "{prunable} worktree(s) are prunable, usually because their directory is gone; `git worktree prune` forgets them."
+        ));
+    }
+
+    let gone = get_gone_worktrees()?
+        .into_iter()
+        .filter(|(branch, path)| {
+            // Locking is how users protect a worktree, so not even --force
+            // overrides it
+            let locked = listed
+                .iter()
+                .find(|worktree| worktree.path == *path)
+                .and_then(|worktree| worktree.locked.as_deref());
+            if let Some(reason) = locked {
+                let reason = if reason.is_empty() {
+                    "locked".to_string()
+                } else {
+                    format!("locked: {reason}")
+                };
+                log_info(&format!(
+                    "Not removing '{branch}' ({reason}); unlock it with `git worktree unlock` first"
+                ));
+                summary.push(branch, Status::Skipped, reason);
+                return false;
+            }
+            let foreign = (!force).then(|| owner::foreign(Path::new(path))).flatten();
+            if let Some(reason) = &foreign {
+                log_info(&format!(
+                    "Not removing '{branch}' without --force: {reason}"
+                ));
+                summary.push(branch, Status::Skipped, reason.as_str());
+            }
+            foreign.is_none()
+        })
+        .collect();
+    Ok(gone)
+}
+
+fn clean_worktrees(force: bool, yes: bool, fail_fast: bool) -> Result<()> {
+    check_git_repo();
+    let mut summary = Summary::default();
+    let gone = removable_gone_worktrees(force, &mut summary)?;
+
+    if gone.is_empty() {
+        log_info("No worktrees with deleted upstream branches to remove.");
+        return Ok(());
+    }
+
+    log_info("Upstream branch deleted for:");
+    for (branch, path) in &gone {
+        log_info(&format!("  {branch}  ({path})"));
+    }
+
+    let root = get_worktree_root()?;
+    let mut plan = explain::Plan::default();
+    for (branch, path) in &gone {
+        let hook_context = HookContext {
+            branch,
+            path: Path::new(path),
+            root: &root,
+            base: None,
+        };
+        plan.hooks(HookEvent::PreRemove, &hook_context);
+        let mut args = vec!["worktree", "remove"];
+        if force {
+            args.push("--force");
+        }
+        args.push(path);
+        plan.command("git", &args);
+        plan.hooks(HookEvent::PostRemove, &hook_context);
+    }
+    let explained = explain::confirm(&plan)?;
+
+    if !yes && !explained && !confirm_removal("Remove these worktrees?")? {
+        process::exit(0);
+    }
+
+    events::started("clean");
+    notify::started("clean");
+    for (done, (branch, path)) in gone.iter().enumerate() {
+        let hook_context = HookContext {
+            branch,
+            path: Path::new(path),
+            root: &root,
+            base: None,
+        };
+        hooks::run(HookEvent::PreRemove, &hook_context)?;
+
+        let mut args = vec!["worktree", "remove"];
+        if force {
+            args.push("--force");
+        }
+        args.push(path);
+
+        let output = runner::run("git", &args, None, Capture::StdoutAndStderr)?;
+
+        if output.status.success() {
+            log_info(&format!("Worktree '{branch}' removed."));
+            audit::record(&format!("removed worktree {path} ({branch})"));
+            hooks::run(HookEvent::PostRemove, &hook_context)?;
+            summary.push(branch, Status::Removed, "");
+        } else {
+            summary.push(branch, Status::Failed, failure_reason(&output.stderr));
+        }
+        events::progress(done + 1, gone.len(), branch);
+
+        if fail_fast && summary.failed() {
+            break;
+        }
+    }
+
+    finish_summary(&summary);
+    Ok(())
+}
+
+/// Prints the summary and ends the operation, exiting with an error if any
+/// worktree failed.
+fn finish_summary(summary: &Summary) {
+    summary.print();
+    finish_printed_summary(summary);
+}
+
+/// Like `finish_summary`, for callers that printed the summary themselves to
+/// show more after it.
+fn finish_printed_summary(summary: &Summary) {
+    if summary.failed() {
+        log_error("Some worktrees failed");
+        process::exit(1);
+    }
+    events::finished();
+    notify::finished();
+}
+
+/// Why a git command failed, from its stderr: the first `fatal:` or
+/// `error:` message, or else the last line.
+fn failure_reason(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let lines = || {
+        stderr
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+    };
+    lines()
+        .find_map(|line| {
+            line.strip_prefix("fatal: ")
+                .or_else(|| line.strip_prefix("error: "))
+        })
+        .or_else(|| lines().next_back())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn switch_to_worktree(branch: &str) -> Result<()> {
+    check_git_repo();
+    let worktree_path = find_worktree_path(branch, true)?;
+
+    if let Some(path) = worktree_path {
+        let path = Path::new(&path);
+        if !path.exists() {
+            restore_missing_worktree(path)?;
+        }
+        change_directory(path);
+        Ok(())
+    } else {
+        log_error(&format!("Worktree for branch '{branch}' not found."));
+        process::exit(1);
+    }
+}
+
+/// Deals with a registered worktree whose directory was deleted by hand:
+/// recreates it for its branch, or prunes the stale registration and exits.
+fn restore_missing_worktree(path: &Path) -> Result<()> {
+    let branch = get_all_worktrees()?
+        .into_iter()
+        .find(|(_, existing)| Path::new(existing) == path)
+        .map(|(branch, _)| branch)
+        .unwrap_or_default();
+
+    log_info(&format!(
+        "The directory of worktree '{branch}' is missing: {}",
+        path.display()
+    ));
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_error(&format!(
+            "Cannot ask what to do because {reason}; if it was moved, run `git worktree repair <new path>`, otherwise `git worktree prune`"
+        ));
+        process::exit(1);
+    }
+
+    let recreate = format!("Recreate it with branch '{branch}' checked out");
+    let prune = "Prune it (git worktree prune)".to_string();
+    let abort = "Leave it as it is".to_string();
+    let selection = Select::new("", vec![recreate.clone(), prune.clone(), abort])
+        .with_render_config(create_select_render_config(false))
+        .without_help_message()
+        .ask();
+
+    let choice = prompt_result(selection)?;
+    if choice.as_ref() == Some(&recreate) {
+        let path_str = path.to_string_lossy();
+        // --force because git still has the path registered
+        run_command(
+            "git",
+            &["worktree", "add", "--force", &path_str, &branch],
+            None,
         )?;
+        log_info("Worktree recreated.");
+        audit::record(&format!("recreated worktree {path_str} ({branch})"));
+        record_new_worktree(path)?;
+
+        let root = get_worktree_root()?;
+        let context = HookContext {
+            branch: &branch,
+            path,
+            root: &root,
+            base: None,
+        };
+        env_files::render(&context)?;
+        hooks::run(HookEvent::PostAdd, &context)
+    } else if choice.as_ref() == Some(&prune) {
+        run_command("git", &["worktree", "prune"], None)?;
+        log_info(&format!("Pruned worktree '{branch}'."));
+        audit::record(&format!("pruned worktree {} ({branch})", path.display()));
+        process::exit(0);
+    } else {
+        process::exit(1);
+    }
+}
+
+/// Moves worktrees that aren't where `worktree_path_for` would put them,
+/// e.g. ones made with `git worktree add`, into place and gives them an
+/// index, so features that go by path find them.
+fn adopt_worktrees(yes: bool) -> Result<()> {
+    check_git_repo();
+    let root = get_worktree_root()?;
+    let misplaced: Vec<(String, PathBuf, PathBuf)> = get_all_worktrees()?
+        .into_iter()
+        .map(|(branch, path)| {
+            let target = worktree_path_for(&root, &branch);
+            (branch, PathBuf::from(path), target)
+        })
+        // The main checkout of a standard clone stays where it is
+        .filter(|(_, path, target)| !same_path(path, target) && !same_path(path, &root))
+        .collect();
+
+    if misplaced.is_empty() {
+        log_info("Every worktree is where git-wt expects it.");
+        return Ok(());
     }
 
-    log_info("Worktree created.");
+    let choices: Vec<String> = misplaced
+        .iter()
+        .map(|(branch, path, _)| format!("{branch}  ({})", path.display()))
+        .collect();
+    let selected: Vec<usize> = if yes {
+        (0..misplaced.len()).collect()
+    } else {
+        if let Some(reason) = config::get().non_interactive_reason() {
+            for choice in &choices {
+                println!("{choice}");
+            }
+            log_error(&format!(
+                "Cannot ask which worktrees to move because {reason}; pass --yes to move all of them"
+            ));
+            process::exit(1);
+        }
+
+        eprintln!("Worktrees to move:");
+        let selection = MultiSelect::new("", choices.clone())
+            .with_page_size(page_size())
+            .with_render_config(create_select_render_config(false))
+            .with_default(&(0..choices.len()).collect::<Vec<_>>())
+            .without_help_message()
+            .ask();
+        match prompt_result(selection)? {
+            Some(selected) if !selected.is_empty() => selected
+                .iter()
+                .filter_map(|choice| choices.iter().position(|c| c == choice))
+                .collect(),
+            _ => {
+                eprintln!("Cancelled.");
+                process::exit(0);
+            }
+        }
+    };
+
+    let cwd = env::current_dir().ok();
+    let mut new_cwd = None;
+    for (branch, path, target) in selected.into_iter().map(|i| &misplaced[i]) {
+        if target.exists() {
+            log_error(&format!(
+                "Not moving '{branch}': {} already exists",
+                target.display()
+            ));
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
 
-    //println!("CD:{}", worktree_path.display());
+        let inside = cwd.as_deref().is_some_and(|cwd| cwd.starts_with(path));
+        if inside {
+            // Windows can't move the directory we're in
+            env::set_current_dir(&root)?;
+        }
+        let from = path.to_string_lossy();
+        let to = target.to_string_lossy();
+        let status = run_command_status("git", &["worktree", "move", &from, &to], None)?;
+        if !status.success() {
+            log_error(&format!("Failed to move '{branch}'"));
+            continue;
+        }
+
+        index::assign(target)?;
+        log_info(&format!("Moved '{branch}' to {to}"));
+        audit::record(&format!("moved worktree {from} to {to} ({branch})"));
+        if inside {
+            new_cwd = cwd
+                .as_deref()
+                .and_then(|cwd| cwd.strip_prefix(path).ok())
+                .map(|rest| target.join(rest));
+        }
+    }
 
+    if let Some(dir) = new_cwd {
+        change_directory(&dir);
+    }
     Ok(())
 }
 
-fn get_all_worktrees() -> Result<Vec<(String, String)>> {
-    let output = Command::new("git")
-        .args(["worktree", "list", "--porcelain"])
-        .output()
-        .context("Failed to execute git worktree list")?;
+fn branches_without_worktrees(list_only: bool) -> Result<()> {
+    check_git_repo();
+    let with_worktree: HashSet<String> = get_all_worktrees()?
+        .into_iter()
+        .map(|(branch, _)| branch)
+        .collect();
 
-    if !output.status.success() {
-        return Ok(Vec::new());
+    let refs = |pattern: &str, format: &str| -> BTreeSet<String> {
+        git_stdout(&["for-each-ref", format, pattern], None)
+            .unwrap_or_default()
+            .lines()
+            .filter(|name| *name != "HEAD" && !with_worktree.contains(*name))
+            .map(str::to_string)
+            .collect()
+    };
+    let local = refs("refs/heads", "--format=%(refname:lstrip=2)");
+    let remote_name = default_remote();
+    let remote = refs(
+        &format!("refs/remotes/{remote_name}"),
+        "--format=%(refname:lstrip=3)",
+    );
+
+    if local.is_empty() && remote.is_empty() {
+        log_info("Every branch has a worktree.");
+        return Ok(());
+    }
+
+    let width = local.union(&remote).map(String::len).max().unwrap_or(0);
+    let choices: Vec<String> = local
+        .union(&remote)
+        .map(|branch| {
+            let location = match (local.contains(branch), remote.contains(branch)) {
+                (true, true) => format!("local, {remote_name}"),
+                (true, false) => "local".to_string(),
+                _ => remote_name.clone(),
+            };
+            format!("{branch:<width$}  ({location})")
+        })
+        .collect();
+
+    if list_only || config::get().non_interactive_reason().is_some() {
+        for choice in &choices {
+            println!("{choice}");
+        }
+        return Ok(());
     }
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let mut worktrees = Vec::new();
-    let mut current_worktree_path: Option<String> = None;
+    let matcher = RefCell::new(Matcher::new(nucleo_matcher::Config::DEFAULT));
+    let filter_scorer = picker_scorer(&matcher, false);
 
-    for line in output_str.lines() {
-        if line.starts_with("worktree ") {
-            current_worktree_path = Some(line.strip_prefix("worktree ").unwrap().to_string());
-        } else if line.starts_with("branch ") {
-            let branch_name = line
-                .strip_prefix("branch ")
-                .unwrap()
-                .trim_start_matches("refs/heads/")
-                .to_string();
+    eprintln!("Create a worktree for:");
+    let selection = Select::new("", choices)
+        .with_page_size(page_size())
+        .with_render_config(create_select_render_config(false))
+        .with_scorer(&filter_scorer)
+        .without_help_message()
+        .ask();
 
-            if let Some(path) = current_worktree_path.take() {
-                worktrees.push((branch_name, path));
-            }
+    let Some(selected) = prompt_result(selection)? else {
+        eprintln!("Cancelled.");
+        process::exit(0);
+    };
+    // Branch names can't contain spaces, so the first word is the branch
+    let branch = selected.split_whitespace().next().unwrap_or_default();
+
+    let worktree_path = add_worktree(&AddArgs {
+        branch: branch.to_string(),
+        ..AddArgs::default()
+    })?;
+    change_directory(&worktree_path);
+
+    Ok(())
+}
+
+/// Asks the caller to change into `path`: by writing it to the `--cd-file`,
+/// or else with a `CD:` directive for the shell integration. Without the
+/// integration stdout is a terminal and nothing would act on the marker, so
+/// say where to go instead.
+fn change_directory(path: &Path) {
+    let config = config::get();
+    if let Some(cd_file) = &config.cd_file {
+        let path = wsl::translate(path);
+        if let Err(e) = fs::write(cd_file, format!("{}\n", path.display())) {
+            log_error(&format!("Failed to write {}: {e}", cd_file.display()));
+            process::exit(1);
         }
+    } else if config.directive_file.is_none() && io::stdout().is_terminal() {
+        log_info(&format!("Worktree is at {}", path.display()));
+        log_info("Set up shell integration to switch to it automatically: git-wt init <shell>");
+    } else {
+        let mut directives = vec![Directive::Cd(wsl::translate(path))];
+        directives.extend(after_switch::directives(path));
+        if let Err(e) = directive::emit(&directives) {
+            log_error(&format!("Failed to hand over the directory: {e}"));
+            process::exit(1);
+        }
+    }
+}
+
+fn print_completions(kind: CompletionKind) -> Result<()> {
+    // Completion runs on every <Tab>, so it stays silent outside a repository
+    if git_common_dir().is_none() {
+        return Ok(());
+    }
+
+    let candidates = match kind {
+        CompletionKind::Worktrees => get_all_worktrees()?
+            .into_iter()
+            .map(|(branch, _)| branch)
+            .collect(),
+        CompletionKind::Bases => base_refs(),
+    };
+    for candidate in candidates {
+        println!("{candidate}");
+    }
+    Ok(())
+}
+
+fn print_worktree_path(branch: &str) -> Result<()> {
+    check_git_repo();
+
+    if let Some(path) = find_worktree_path(branch, false)? {
+        println!("{path}");
+        Ok(())
+    } else {
+        log_error(&format!("Worktree for branch '{branch}' not found."));
+        process::exit(1);
     }
+}
 
-    Ok(worktrees)
+/// `backup/<user>/`, so backups never overwrite the branches themselves or
+/// another user's backups. Characters git doesn't allow in ref names, or
+/// that would add a level, become `-`.
+fn default_backup_prefix() -> String {
+    let user: String = owner::current_user()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("backup/{user}/")
 }
 
-fn find_worktree_path(branch: &str) -> Result<Option<String>> {
+fn backup_worktree_branches(remote: &str, prefix: &str) -> Result<()> {
+    check_git_repo();
     let worktrees = get_all_worktrees()?;
 
     if worktrees.is_empty() {
-        return Ok(None);
+        log_info("No worktree branches to back up.");
+        return Ok(());
     }
 
-    // First try exact match
-    for (wt_branch, wt_path) in &worktrees {
-        if wt_branch == branch {
-            return Ok(Some(wt_path.clone()));
-        }
+    // One push for all branches; the lease makes sure a backup that changed on
+    // the remote since the last fetch isn't clobbered
+    let mut args = vec!["push".to_string(), "--porcelain".to_string()];
+    for (branch, _) in &worktrees {
+        args.push(format!("--force-with-lease=refs/heads/{prefix}{branch}"));
+    }
+    args.push(remote.to_string());
+    for (branch, _) in &worktrees {
+        args.push(format!("refs/heads/{branch}:refs/heads/{prefix}{branch}"));
     }
 
-    // If no exact match, try fuzzy matching
-    let mut matcher = Matcher::new(Config::DEFAULT);
-    let pattern = Atom::new(
-        branch,
-        CaseMatching::Smart,
-        Normalization::Smart,
-        AtomKind::Fuzzy,
-        false,
-    );
+    log_info(&format!(
+        "Backing up {} branch(es) to {remote}...",
+        worktrees.len()
+    ));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (status, output) =
+        throttle::remote(remote, None, || run_command_output("git", &args, None))?;
+
+    // Porcelain lines look like "<flag>\t<src>:<dst>\t<summary>"
+    let mut results = Vec::new();
+    for line in output.lines() {
+        let mut fields = line.split('\t');
+        let (Some(flag), Some(refspec), summary) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let Some(branch) = refspec
+            .split(':')
+            .next()
+            .and_then(|src| src.strip_prefix("refs/heads/"))
+        else {
+            continue;
+        };
+        let summary = summary.unwrap_or_default();
+        let outcome = match flag {
+            "=" => "skipped: up to date".to_string(),
+            "!" => format!("failed: {summary}"),
+            _ => format!("backed up to {remote}/{prefix}{branch}"),
+        };
+        results.push((branch.to_string(), outcome));
+    }
 
-    let mut scored: Vec<(u16, String, String)> = Vec::new();
+    let width = results
+        .iter()
+        .map(|(branch, _)| branch.len())
+        .max()
+        .unwrap_or(0);
+    for (branch, outcome) in &results {
+        log_info(&format!("  {branch:<width$}  {outcome}"));
+    }
 
-    for (wt_branch, wt_path) in worktrees {
-        let haystack = Utf32Str::Ascii(wt_branch.as_bytes());
-        if let Some(score) = pattern.score(haystack, &mut matcher) {
-            scored.push((score, wt_branch, wt_path));
-        }
+    if !status.success() {
+        log_error("Some branches could not be backed up");
+        process::exit(1);
     }
 
-    if scored.is_empty() {
-        return Ok(None);
+    Ok(())
+}
+
+fn merge_worktree(source: &str, into: Option<&str>) -> Result<()> {
+    check_git_repo();
+
+    let (source, _) = resolve_worktree(Some(source))?;
+    let remote = default_remote();
+    let target = match into {
+        Some(target) => target.to_string(),
+        None => default_branch_ref()
+            .and_then(|base| base.strip_prefix(&format!("{remote}/")).map(str::to_string))
+            .context("Could not determine the default branch; pass --into")?,
+    };
+    let (target, target_path) = resolve_worktree(Some(&target))?;
+    let target_path = PathBuf::from(target_path);
+
+    if source == target {
+        log_error(&format!("Cannot merge '{source}' into itself"));
+        process::exit(1);
     }
 
-    scored.sort_by(|a, b| b.0.cmp(&a.0));
+    let status = git_stdout(
+        &[
+            "--no-optional-locks",
+            "status",
+            "--porcelain",
+            "--untracked-files=no",
+        ],
+        Some(&target_path),
+    );
+    if status.is_none_or(|status| !status.is_empty()) {
+        log_error(&format!("Worktree '{target}' has uncommitted changes"));
+        process::exit(1);
+    }
 
-    if scored.len() == 1 {
-        return Ok(Some(scored[0].2.clone()));
+    // Bring the target up to date first so the merge lands on the latest state
+    if ref_exists(&format!("refs/remotes/{remote}/{target}")) {
+        offline::run_git(&["fetch", "--quiet", &remote, &target], None)?;
+    }
+    let outcome = fast_forward_worktree(&target, &target_path)?;
+    if outcome.starts_with("failed") || outcome.starts_with("skipped: diverged") {
+        log_error(&format!("Could not update '{target}' ({outcome})"));
+        process::exit(1);
     }
 
-    let options: Vec<(String, String)> = scored
-        .iter()
-        .map(|(_, name, path)| (name.clone(), path.clone()))
-        .collect();
-    let branch_names: Vec<String> = options.iter().map(|(name, _)| name.clone()).collect();
+    // Land in the target either way, so conflicts can be resolved right there
+    change_directory(&target_path);
 
-    eprintln!("'{branch}' matches multiple worktrees.");
-    let selection = Select::new("", branch_names)
-        .with_page_size(10)
-        .with_render_config(create_select_render_config())
-        .without_help_message()
-        .prompt_skippable();
+    log_info(&format!("Merging '{source}' into '{target}'..."));
+    let merged = run_command_status("git", &["merge", "--no-edit", &source], Some(&target_path))?;
+    if !merged.success() {
+        log_error(&format!(
+            "Merge stopped with conflicts in {}; resolve them and commit, or run `git merge --abort`",
+            target_path.display()
+        ));
+        process::exit(1);
+    }
+    log_info(&format!("Merged '{source}' into '{target}'."));
 
-    if let Ok(Some(selected)) = selection {
-        for (name, path) in options {
-            if name == selected {
-                return Ok(Some(path));
-            }
-        }
-        Ok(None)
-    } else {
-        eprintln!("Cancelled.");
-        process::exit(0);
+    if config::get().non_interactive_reason().is_some() {
+        log_info(&format!(
+            "Run `git-wt rm {source}` and `git branch -d {source}` to clean up"
+        ));
+        return Ok(());
+    }
+
+    let prompt = format!("Remove worktree '{source}' and delete the branch?");
+    let confirmed = Confirm::new("")
+        .with_default(false)
+        .with_render_config(create_confirm_render_config(&prompt))
+        .ask();
+    if prompt_result(confirmed)? == Some(true) {
+        // Don't remove the worktree from underneath ourselves
+        env::set_current_dir(&target_path)?;
+        remove_worktree(Some(&source), false, false, true)?;
+        run_command("git", &["branch", "-d", &source], None)?;
     }
+
+    Ok(())
 }
 
-fn get_current_worktree_branch() -> Result<Option<String>> {
-    let current_dir = std::env::current_dir()?.canonicalize()?;
+fn pick_commits(source: &str) -> Result<()> {
+    check_git_repo();
 
-    let worktrees = get_all_worktrees()?;
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_error(&format!(
+            "Cannot pick commits because {reason}; use `git cherry-pick` instead"
+        ));
+        process::exit(1);
+    }
 
-    for (branch, path) in worktrees {
-        if let Ok(worktree_path) = PathBuf::from(&path).canonicalize()
-            && worktree_path == current_dir
-        {
-            return Ok(Some(branch));
+    let (source, _) = resolve_worktree(Some(source))?;
+
+    // Oldest first, leaving out commits whose changes are already here
+    let commits: Vec<String> = git_stdout(
+        &[
+            "log",
+            "--cherry-pick",
+            "--right-only",
+            "--no-merges",
+            "--reverse",
+            "--format=%h %s",
+            &format!("HEAD...{source}"),
+        ],
+        None,
+    )
+    .unwrap_or_default()
+    .lines()
+    .map(str::to_string)
+    .collect();
+
+    if commits.is_empty() {
+        log_info(&format!(
+            "'{source}' has no commits missing from this worktree."
+        ));
+        return Ok(());
+    }
+
+    let matcher = RefCell::new(Matcher::new(nucleo_matcher::Config::DEFAULT));
+    let filter_scorer = picker_scorer(&matcher, false);
+
+    eprintln!("Commits to cherry-pick from '{source}':");
+    let selection = MultiSelect::new("", commits)
+        .with_page_size(page_size())
+        .with_render_config(create_select_render_config(false))
+        .with_scorer(&filter_scorer)
+        .without_help_message()
+        .ask();
+
+    let selected = match prompt_result(selection)? {
+        Some(selected) if !selected.is_empty() => selected,
+        _ => {
+            eprintln!("Cancelled.");
+            process::exit(0);
         }
+    };
+
+    let mut args = vec!["cherry-pick", "-x"];
+    args.extend(
+        selected
+            .iter()
+            .filter_map(|commit| commit.split_whitespace().next()),
+    );
+
+    if !run_command_status("git", &args, None)?.success() {
+        log_error(
+            "Cherry-pick stopped with conflicts; resolve them and run `git cherry-pick --continue`, \
+             or `git cherry-pick --abort` to give up",
+        );
+        process::exit(1);
     }
 
-    Ok(None)
+    log_info(&format!(
+        "Picked {} commit(s) from '{source}'.",
+        selected.len()
+    ));
+    Ok(())
 }
 
-fn remove_worktree(branch: Option<&str>, force: bool) -> Result<()> {
-    check_git_repo()?;
-
+/// Resolves a branch argument (defaulting to the current worktree) to the
+/// worktree's real branch name and path, exiting if there is none.
+fn resolve_worktree(branch: Option<&str>) -> Result<(String, String)> {
     let branch = match branch {
         Some(b) => b.to_string(),
         None => {
             if let Some(b) = get_current_worktree_branch()? {
                 b
+            } else if inside_git_dir() {
+                log_error(
+                    "Not inside a worktree (this is the bare repository); pass a branch name",
+                );
+                process::exit(1);
             } else {
                 log_error("Could not determine current worktree branch");
                 process::exit(1);
@@ -487,79 +3492,502 @@ fn remove_worktree(branch: Option<&str>, force: bool) -> Result<()> {
         }
     };
 
-    let worktree_path = find_worktree_path(&branch)?;
-
-    if worktree_path.is_none() {
+    let Some(worktree_path) = find_worktree_path(&branch, true)? else {
         log_error(&format!("Worktree for branch '{branch}' not found"));
         process::exit(1);
+    };
+
+    // The argument may have been a fuzzy match or index
+    let branch = get_all_worktrees()?
+        .into_iter()
+        .find(|(_, path)| *path == worktree_path)
+        .map_or(branch, |(name, _)| name);
+
+    Ok((branch, worktree_path))
+}
+
+fn open_worktree(branch: Option<&str>, devcontainer: bool) -> Result<()> {
+    check_git_repo();
+    let (_, worktree_path) = resolve_worktree(branch)?;
+
+    if devcontainer {
+        return devcontainer::up(Path::new(&worktree_path));
     }
 
-    let confirmed = Confirm::new("")
-        .with_default(false)
-        .with_render_config(create_confirm_render_config(
-            "Are you sure you want to remove the worktree?",
-        ))
-        .prompt_skippable();
+    let editor = config::get()
+        .editor
+        .clone()
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty());
+    let Some(editor) = editor else {
+        log_error("No editor configured; set `editor` in the config or $EDITOR");
+        process::exit(1);
+    };
 
-    if !matches!(confirmed, Ok(Some(true))) {
-        process::exit(0);
+    // Allow editors with arguments, e.g. "code --new-window"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+    let mut args: Vec<&str> = parts.collect();
+    args.push(&worktree_path);
+
+    run_command(program, &args, None)
+}
+
+/// Pulls in a worktree, with `strategy` overriding the worktree's own pull
+/// settings so every worktree integrates upstream changes the same way.
+fn pull_worktree(branch: Option<&str>, strategy: Option<PullStrategy>) -> Result<()> {
+    check_git_repo();
+
+    let (branch, worktree_path) = resolve_worktree(branch)?;
+    let worktree_path_buf = PathBuf::from(&worktree_path);
+
+    let mut args = vec!["pull"];
+    args.extend(strategy.map(PullStrategy::flag));
+
+    log_info(&format!("Pulling changes in worktree '{branch}'..."));
+    notify::started("pull");
+    let status = offline::run_git_status(&args, Some(&worktree_path_buf))?;
+    if let Some(conflict) = conflicts::detect(&branch, &worktree_path_buf) {
+        conflicts::report(&[conflict])?;
+        log_error("Pull stopped on conflicts");
+        process::exit(1);
     }
+    if !status.success() {
+        resolve_divergence(&worktree_path_buf)?;
+    }
+    log_info("Pull completed.");
+    notify::finished();
 
-    let mut args = vec!["worktree", "remove"];
-    if force {
-        args.push("--force");
+    Ok(())
+}
+
+/// Pulls every clean worktree, reporting the outcome of each in a summary.
+///
+/// Separate pulls would each connect to the remote, paying for an SSH
+/// handshake every time, so the upstreams are fetched first with one `git
+/// fetch` per remote and every worktree then pulls from the local repository.
+fn pull_all_worktrees(strategy: Option<PullStrategy>, fail_fast: bool) -> Result<()> {
+    check_git_repo();
+    let worktrees = get_all_worktrees()?;
+    let mut summary = Summary::default();
+    let mut unfinished = Vec::new();
+
+    events::started("pull");
+    notify::started("pull --all");
+    let upstreams = branch_upstreams();
+    let mut pulls = Vec::new();
+    for (branch, path) in &worktrees {
+        let path = Path::new(path);
+
+        // Left over from an earlier run
+        if let Some(conflict) = conflicts::detect(branch, path) {
+            summary.push(branch, Status::Skipped, conflict.describe());
+            unfinished.push(conflict);
+            continue;
+        }
+        let status = git_stdout(
+            &[
+                "--no-optional-locks",
+                "status",
+                "--porcelain",
+                "--untracked-files=no",
+            ],
+            Some(path),
+        );
+        if status.is_none_or(|status| !status.is_empty()) {
+            summary.push(branch, Status::Skipped, "uncommitted changes");
+            continue;
+        }
+        let upstream = format!("{branch}@{{upstream}}");
+        let Some(tracking) = git_stdout(
+            &[
+                "rev-parse",
+                "--symbolic-full-name",
+                "--verify",
+                "--quiet",
+                &upstream,
+            ],
+            Some(path),
+        ) else {
+            summary.push(branch, Status::Skipped, "no upstream");
+            continue;
+        };
+        pulls.push((branch, path, tracking));
     }
-    let worktree_path = worktree_path.unwrap();
-    args.push(&worktree_path);
 
-    run_command("git", &args, None)?;
+    let failed_remotes = fetch_upstreams(&pulls, &upstreams)?;
 
-    log_info(format!("Worktree '{}' removed.", &branch).as_str());
+    let total = pulls.len();
+    for (done, (branch, path, tracking)) in pulls.iter().enumerate() {
+        events::progress(done, total, branch);
+        if let Some(remote) = upstreams
+            .get(branch.as_str())
+            .map(|(remote, _)| remote)
+            .filter(|remote| failed_remotes.contains(*remote))
+        {
+            summary.push(
+                branch,
+                Status::Failed,
+                format!("fetching from {remote} failed"),
+            );
+            if fail_fast {
+                break;
+            }
+            continue;
+        }
+        let before = git_stdout(&["rev-parse", "HEAD"], Some(path));
+
+        log_info(&format!("Pulling '{branch}'..."));
+        let mut args = vec!["pull", "--quiet"];
+        args.extend(strategy.map(PullStrategy::flag));
+        args.extend([".", tracking.as_str()]);
+        let output = runner::run("git", &args, Some(path), Capture::StdoutAndStderr)?;
+
+        if let Some(conflict) = conflicts::detect(branch, path) {
+            summary.push(branch, Status::Failed, conflict.describe());
+            unfinished.push(conflict);
+            if fail_fast {
+                break;
+            }
+        } else if !output.status.success() {
+            summary.push(branch, Status::Failed, failure_reason(&output.stderr));
+            if fail_fast {
+                break;
+            }
+        } else if git_stdout(&["rev-parse", "HEAD"], Some(path)) == before {
+            summary.push(branch, Status::Unchanged, "");
+        } else {
+            summary.push(branch, Status::Updated, "");
+        }
+    }
+    events::progress(total, total, "");
 
+    summary.print();
+    conflicts::report(&unfinished)?;
+    finish_printed_summary(&summary);
     Ok(())
 }
 
-fn switch_to_worktree(branch: &str) -> Result<()> {
-    check_git_repo()?;
-    let worktree_path = find_worktree_path(branch)?;
+/// `branch.<name>.remote` and `branch.<name>.merge` of every branch that has
+/// both, by branch name.
+fn branch_upstreams() -> HashMap<String, (String, String)> {
+    let mut remotes = HashMap::new();
+    let mut merges: HashMap<String, String> = HashMap::new();
+    let config = git_stdout(
+        &["config", "--get-regexp", r"^branch\..*\.(remote|merge)$"],
+        None,
+    )
+    .unwrap_or_default();
+    for line in config.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        // Branch names may contain dots themselves
+        match key
+            .strip_prefix("branch.")
+            .and_then(|key| key.rsplit_once('.'))
+        {
+            Some((branch, "remote")) => {
+                remotes.insert(branch.to_string(), value.to_string());
+            }
+            Some((branch, "merge")) => {
+                merges.insert(branch.to_string(), value.to_string());
+            }
+            _ => {}
+        }
+    }
+    remotes
+        .into_iter()
+        .filter_map(|(branch, remote)| {
+            let merge = merges.remove(&branch)?;
+            Some((branch, (remote, merge)))
+        })
+        .collect()
+}
 
-    if let Some(path) = worktree_path {
-        println!("CD:{path}");
-        Ok(())
-    } else {
-        log_error(&format!("Worktree for branch '{branch}' not found."));
-        process::exit(1);
+/// Fetches the upstreams of `pulls` into their remote-tracking branches with
+/// one `git fetch` per remote, returning the remotes that failed.
+fn fetch_upstreams(
+    pulls: &[(&String, &Path, String)],
+    upstreams: &HashMap<String, (String, String)>,
+) -> Result<HashSet<String>> {
+    // Worktrees of the same upstream need it fetched once
+    let mut refspecs: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    for (branch, _, tracking) in pulls {
+        // Branches tracking a local branch have nothing to fetch
+        if let Some((remote, merge)) = upstreams.get(branch.as_str())
+            && remote != "."
+            && tracking.starts_with("refs/remotes/")
+        {
+            refspecs
+                .entry(remote)
+                .or_default()
+                .insert(format!("+{merge}:{tracking}"));
+        }
     }
+
+    let tips = negotiation_tips();
+    let mut failed = HashSet::new();
+    for (remote, refspecs) in refspecs {
+        log_info(&format!(
+            "Fetching {} branch(es) from {remote}...",
+            refspecs.len()
+        ));
+        let mut args = vec!["fetch", "--quiet"];
+        args.extend(tips.iter().map(String::as_str));
+        args.push(remote);
+        args.extend(refspecs.iter().map(String::as_str));
+        if !offline::run_git_status(&args, None)?.success() {
+            failed.insert(remote.to_string());
+        }
+    }
+    Ok(failed)
 }
 
-fn pull_worktree(branch: Option<&str>) -> Result<()> {
-    check_git_repo()?;
+/// After a failed pull, offers to rebase, merge or reset if the branch and
+/// its upstream diverged; any other failure is reported as is.
+fn resolve_divergence(worktree: &Path) -> Result<()> {
+    let counts = git_stdout(
+        &["rev-list", "--left-right", "--count", "HEAD...@{upstream}"],
+        Some(worktree),
+    );
+    let diverged = counts
+        .as_deref()
+        .and_then(|counts| counts.split_once('\t'))
+        .is_some_and(|(ahead, behind)| ahead != "0" && behind != "0");
+    if !diverged {
+        log_error("Pull failed");
+        process::exit(1);
+    }
 
-    let branch = match branch {
-        Some(b) => b.to_string(),
-        None => {
-            if let Some(b) = get_current_worktree_branch()? {
-                b
-            } else {
-                log_error("Could not determine current worktree branch");
-                process::exit(1);
-            }
+    log_info("The branch and its upstream have diverged.");
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_error(&format!(
+            "Cannot ask how to resolve it because {reason}; pull again with --rebase or --merge"
+        ));
+        process::exit(1);
+    }
+
+    let rebase = "Rebase local commits onto the upstream".to_string();
+    let merge = "Merge the upstream into the branch".to_string();
+    let reset = "Reset to the upstream, discarding local commits".to_string();
+    let abort = "Leave the branch as it is".to_string();
+    let selection = Select::new(
+        "",
+        vec![rebase.clone(), merge.clone(), reset.clone(), abort],
+    )
+    .with_render_config(create_select_render_config(false))
+    .without_help_message()
+    .ask();
+
+    let choice = prompt_result(selection)?;
+    let args: &[&str] = if choice.as_ref() == Some(&rebase) {
+        &["rebase", "@{upstream}"]
+    } else if choice.as_ref() == Some(&merge) {
+        &["merge", "--no-edit", "@{upstream}"]
+    } else if choice.as_ref() == Some(&reset) {
+        let confirmed = Confirm::new("")
+            .with_default(false)
+            .with_render_config(create_confirm_render_config(
+                "Discard local commits and uncommitted changes?",
+            ))
+            .ask();
+        if prompt_result(confirmed)? != Some(true) {
+            process::exit(1);
         }
+        &["reset", "--hard", "@{upstream}"]
+    } else {
+        process::exit(1);
     };
 
-    let worktree_path = find_worktree_path(&branch)?;
+    run_command("git", args, Some(worktree))
+}
 
-    if worktree_path.is_none() {
-        log_error(&format!("Worktree for branch '{branch}' not found"));
-        process::exit(1);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use runner::Recorder;
+    use std::rc::Rc;
+
+    fn add_args(branch: &str) -> AddArgs {
+        AddArgs {
+            branch: branch.to_string(),
+            ..AddArgs::default()
+        }
     }
 
-    let worktree_path = worktree_path.unwrap();
-    let worktree_path_buf = PathBuf::from(&worktree_path);
+    #[test]
+    fn new_branch_is_created_from_base_then_tracks_upstream() {
+        let recorder = Rc::new(Recorder::default());
+        runner::with(recorder.clone(), || {
+            let args = add_args("feature/login");
+            let git_args = worktree_add_args(&args, "/wt/feature/login", false, "origin/main");
+            let upstream = Some(("origin".to_string(), "feature/login".to_string()));
+            create_worktree("feature/login", &git_args, false, upstream).unwrap();
+        });
+
+        assert_eq!(
+            recorder.invocations(),
+            [
+                "git rev-parse --verify --quiet origin/main",
+                "git worktree add /wt/feature/login -b feature/login origin/main",
+                "git rev-parse --verify --quiet refs/remotes/origin/feature/login",
+                "git config branch.feature/login.remote origin",
+                "git config branch.feature/login.merge refs/heads/feature/login",
+            ]
+        );
+    }
 
-    log_info(&format!("Pulling changes in worktree '{branch}'..."));
-    run_command("git", &["pull"], Some(&worktree_path_buf))?;
-    log_info("Pull completed.");
+    #[test]
+    fn unpushed_branch_tracks_its_upstream_once_pushed() {
+        let recorder = Rc::new(Recorder::default());
+        recorder.respond(
+            "git rev-parse --verify --quiet refs/remotes/origin/feature/login",
+            1,
+            "",
+        );
+        recorder.respond("git config --type=bool --get push.autoSetupRemote", 1, "");
+        runner::with(recorder.clone(), || {
+            let args = add_args("feature/login");
+            let git_args = worktree_add_args(&args, "/wt/feature/login", false, "origin/main");
+            let upstream = Some(("origin".to_string(), "feature/login".to_string()));
+            create_worktree("feature/login", &git_args, false, upstream).unwrap();
+        });
+
+        let invocations = recorder.invocations();
+        assert!(
+            !invocations
+                .iter()
+                .any(|call| call.contains("branch.feature/login.")),
+            "{invocations:?}"
+        );
+        assert!(
+            invocations.contains(&"git config push.autoSetupRemote true".to_string()),
+            "{invocations:?}"
+        );
+    }
 
-    Ok(())
+    #[test]
+    fn missing_base_falls_back_to_head() {
+        let recorder = Rc::new(Recorder::default());
+        recorder.respond("git rev-parse --verify --quiet origin/fix", 1, "");
+        runner::with(recorder.clone(), || {
+            let args = add_args("fix");
+            let git_args = worktree_add_args(&args, "/wt/fix", false, "origin/fix");
+            create_worktree("fix", &git_args, false, None).unwrap();
+        });
+
+        assert_eq!(
+            recorder.invocations(),
+            [
+                "git rev-parse --verify --quiet origin/fix",
+                "git worktree add /wt/fix -b fix HEAD",
+            ]
+        );
+    }
+
+    #[test]
+    fn existing_branch_is_checked_out_without_touching_its_upstream() {
+        let recorder = Rc::new(Recorder::default());
+        runner::with(recorder.clone(), || {
+            let args = AddArgs {
+                no_track: true,
+                quiet_checkout: true,
+                ..add_args("main")
+            };
+            let git_args = worktree_add_args(&args, "/wt/main", true, "HEAD");
+            let upstream = Some(("origin".to_string(), "main".to_string()));
+            create_worktree("main", &git_args, true, upstream).unwrap();
+        });
+
+        assert_eq!(
+            recorder.invocations(),
+            ["git worktree add --no-track --quiet /wt/main main"]
+        );
+    }
+
+    #[test]
+    fn integration_uses_the_configured_function_name() {
+        let bash = integration_script(Shell::Bash, "wt");
+        assert!(bash.contains("\nwt() {\n"));
+        assert!(!bash.contains("git-wt() {"));
+        // The binary itself is still called by its name
+        assert!(bash.contains("command git-wt --directive-file \"$directives\" \"$@\""));
+        assert!(bash.contains("        wt \"$@\"\n"));
+        assert!(bash.contains("-F _git_wt_complete wt\n"));
+
+        let fish = integration_script(Shell::Fish, "wt");
+        assert!(fish.contains("\nfunction wt\n"));
+        assert!(fish.contains("        wt $argv\n"));
+        assert!(fish.ends_with("complete -c wt -w git-wt\n"));
+
+        assert_eq!(integration_script(Shell::Fish, "git-wt"), FISH_INTEGRATION);
+    }
+
+    /// `path_conflict(path)` with `worktrees` (branch, path) registered.
+    fn conflict(worktrees: &[(&str, &Path)], path: &Path) -> Option<String> {
+        let porcelain = worktrees
+            .iter()
+            .map(|(branch, path)| {
+                format!(
+                    "worktree {}\nHEAD 0000000\nbranch refs/heads/{branch}\n\n",
+                    path.display()
+                )
+            })
+            .collect::<Vec<_>>()
+            .concat();
+        let recorder = Rc::new(Recorder::default());
+        recorder.respond("git worktree list", 0, &porcelain);
+        runner::with(recorder, || path_conflict(path).unwrap())
+    }
+
+    #[test]
+    fn existing_directory_conflicts() {
+        let dir = env::temp_dir().join(format!("git-wt-conflict-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let found = conflict(&[], &dir);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            found,
+            Some(format!("Directory '{}' already exists", dir.display()))
+        );
+        assert_eq!(conflict(&[], &dir.join("missing")), None);
+    }
+
+    #[test]
+    fn registered_worktrees_conflict() {
+        let root = env::temp_dir().join("git-wt-conflict-registered");
+        let api = root.join("feature/api");
+        let worktrees = [("feature/api", api.as_path())];
+
+        let found = conflict(&worktrees, &api).unwrap();
+        assert!(
+            found.ends_with("is already the worktree of 'feature/api'"),
+            "{found}"
+        );
+        let found = conflict(&worktrees, &api.join("v2")).unwrap();
+        assert!(
+            found.contains("would be inside the worktree of 'feature/api'"),
+            "{found}"
+        );
+        let found = conflict(&worktrees, &root.join("feature")).unwrap();
+        assert!(
+            found.contains("would contain the worktree of 'feature/api'"),
+            "{found}"
+        );
+        assert_eq!(conflict(&worktrees, &root.join("feature/apis")), None);
+    }
+
+    #[test]
+    fn paths_differing_only_in_case_conflict() {
+        let root = env::temp_dir().join("git-wt-conflict-case");
+        let fix = root.join("fix");
+        let found = conflict(&[("fix", fix.as_path())], &root.join("Fix")).unwrap();
+        assert!(
+            found.contains("only differs in case from the worktree of 'fix'"),
+            "{found}"
+        );
+    }
 }
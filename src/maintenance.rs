@@ -0,0 +1,44 @@
+//! `maintenance`: housekeeping for the bare repository all worktrees share.
+//!
+//! In the `.bare` layout it isn't obvious where `git gc` belongs, so this
+//! runs it there, together with dropping the administrative files of
+//! worktrees whose directory is gone and expiring old reflog entries, and
+//! reports how much space that gave back.
+
+use crate::list::disk_size;
+use crate::{check_git_repo, format, git_common_dir, log_error, log_info, process, run_command};
+use color_eyre::Result;
+
+pub fn run(aggressive: bool) -> Result<()> {
+    check_git_repo();
+    let Some(common_dir) = git_common_dir() else {
+        log_error("Could not find the repository");
+        process::exit(1);
+    };
+    let before = disk_size(&common_dir);
+
+    log_info("Pruning administrative files of removed worktrees...");
+    run_command("git", &["worktree", "prune", "--verbose"], None)?;
+
+    log_info("Expiring old reflog entries...");
+    run_command("git", &["reflog", "expire", "--all"], None)?;
+
+    // gc repacks, packs refs and drops unreachable objects past their grace
+    // period
+    if aggressive {
+        log_info("Repacking thoroughly, this can take a long time...");
+        run_command("git", &["gc", "--aggressive"], None)?;
+    } else {
+        log_info("Repacking...");
+        run_command("git", &["gc"], None)?;
+    }
+
+    let after = disk_size(&common_dir);
+    log_info(&format!(
+        "Reclaimed {} ({} -> {}).",
+        format::size(before.saturating_sub(after)),
+        format::size(before),
+        format::size(after)
+    ));
+    Ok(())
+}
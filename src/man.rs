@@ -0,0 +1,103 @@
+//! The man page, generated from the command line definition so it can't
+//! drift from `--help`. `init --write` installs it, which also makes
+//! `git wt --help` work, since git opens the man page of `git-wt` for it.
+
+use crate::Cli;
+use clap::{Arg, CommandFactory};
+use std::fmt::Write;
+
+/// `git-wt(1)` in roff.
+pub fn page() -> String {
+    let mut command = Cli::command();
+    command.build();
+
+    let mut page = String::new();
+    let _ = writeln!(
+        page,
+        ".TH GIT-WT 1 \"\" \"git-wt {}\" \"Git Manual\"",
+        env!("CARGO_PKG_VERSION")
+    );
+    page.push_str(".SH NAME\ngit-wt \\- manage git worktrees in a bare repository layout\n");
+    let usage = command.render_usage().to_string();
+    let usage = usage.trim_start_matches("Usage: ");
+    let _ = writeln!(page, ".SH SYNOPSIS\n.B {}", escape(usage));
+
+    page.push_str(".SH OPTIONS\n");
+    for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let _ = writeln!(page, ".TP\n{}", arg_names(arg));
+        if let Some(help) = arg.get_help() {
+            let _ = writeln!(page, "{}", escape(&help.to_string()));
+        }
+    }
+
+    page.push_str(".SH COMMANDS\n");
+    for subcommand in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        let _ = writeln!(
+            page,
+            ".TP\n\\fBgit-wt {}\\fR",
+            escape(subcommand.get_name())
+        );
+        if let Some(about) = subcommand.get_about() {
+            let _ = writeln!(page, "{}", escape(&about.to_string()));
+        }
+    }
+    page.push_str(
+        ".SH SEE ALSO\n\\fBgit-worktree\\fR(1)\n.PP\n\
+         Run \\fBgit-wt <command> \\-\\-help\\fR for the options of a command.\n",
+    );
+    page
+}
+
+/// How `arg` is written, e.g. `-h, --help` or `--timeout <SECONDS>`, in bold
+/// with its value names in italics.
+fn arg_names(arg: &Arg) -> String {
+    // Like clap, name values after the argument unless told otherwise
+    let id = arg.get_id().as_str().to_uppercase();
+    let names = match arg.get_value_names() {
+        Some(names) => names.iter().map(ToString::to_string).collect(),
+        None => vec![id],
+    };
+    let mut values = String::new();
+    for name in names {
+        let _ = write!(values, " \\fI<{}>\\fR", escape(&name));
+    }
+    let takes_value = arg.get_num_args().is_some_and(|num| num.takes_values());
+    let values = if takes_value { values } else { String::new() };
+
+    if arg.is_positional() {
+        return values.trim_start().to_string();
+    }
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("\\fB\\-{short}\\fR"));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+    }
+    format!("{}{values}", names.join(", "))
+}
+
+/// `text` safe to put in a roff line.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    // A leading dot or quote would start a request
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{escaped}")
+    } else {
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_lists_options_and_visible_commands() {
+        let page = page();
+        assert!(page.starts_with(".TH GIT-WT 1"));
+        assert!(page.contains(".TP\n\\fB\\-\\-timeout\\fR \\fI<SECONDS>\\fR\n"));
+        assert!(page.contains(".TP\n\\fBgit-wt add\\fR\nAdd a new worktree\n"));
+        assert!(!page.contains("__complete"));
+    }
+}
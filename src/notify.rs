@@ -0,0 +1,68 @@
+//! Desktop notifications when long operations finish or fail (`notify = true`).
+//!
+//! Sent with `notify-send` on Linux and the BSDs and `osascript` on macOS;
+//! elsewhere, or without those tools, nothing is shown.
+
+use crate::config;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+static OPERATION: Mutex<Option<&'static str>> = Mutex::new(None);
+
+pub fn started(operation: &'static str) {
+    *OPERATION
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(operation);
+}
+
+pub fn finished() {
+    if let Some(operation) = take() {
+        send(&format!("{operation} finished"));
+    }
+}
+
+/// Reports a failure of the running operation, if any.
+pub fn failed(message: &str) {
+    if let Some(operation) = take() {
+        send(&format!("{operation} failed: {message}"));
+    }
+}
+
+fn take() -> Option<&'static str> {
+    OPERATION
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .take()
+}
+
+fn send(message: &str) {
+    let config = config::get();
+    if !config.notify.unwrap_or(false) || config.test_mode() {
+        return;
+    }
+
+    let mut command = if cfg!(target_os = "macos") {
+        let quote = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut command = Command::new("osascript");
+        command.args([
+            "-e",
+            &format!(
+                "display notification \"{}\" with title \"git-wt\"",
+                quote(message)
+            ),
+        ]);
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=git-wt", "git-wt", message]);
+        command
+    } else {
+        return;
+    };
+
+    let _ = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
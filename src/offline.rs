@@ -0,0 +1,108 @@
+//! Telling "the remote can't be reached" apart from other failures.
+//!
+//! Git commands that talk to a remote run with their stderr shown as usual
+//! but also scanned for network errors. Those end with a message saying so
+//! and exit code [`EXIT_CODE`], so scripts can retry later instead of
+//! treating it like any other failure.
+
+use crate::{accessible, git_env, log_error, new_command, throttle, timings, wait_with_timeout};
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::{self, ExitStatus, Stdio};
+use std::thread;
+
+/// `EX_TEMPFAIL` from sysexits.h: try again later.
+pub const EXIT_CODE: i32 = 75;
+
+/// What git, curl and ssh say when the network is down or the host can't be
+/// found.
+const NETWORK_ERRORS: [&str; 11] = [
+    "Could not resolve host",
+    "Could not resolve hostname",
+    "Temporary failure in name resolution",
+    "Name or service not known",
+    "nodename nor servname provided",
+    "Network is unreachable",
+    "No route to host",
+    "Connection timed out",
+    "Operation timed out",
+    "Failed to connect to",
+    "ssh: connect to host",
+];
+
+/// Whether `stderr` of a failed git command says the remote couldn't be
+/// reached.
+pub fn is_network_error(stderr: &str) -> bool {
+    NETWORK_ERRORS
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+}
+
+/// Reports that the remote can't be reached and exits with [`EXIT_CODE`].
+pub fn exit() -> ! {
+    log_error(
+        "Could not reach the remote; you seem to be offline. Check your connection and try again",
+    );
+    process::exit(EXIT_CODE);
+}
+
+/// Runs `git <args>`, exiting like `run_command` if it fails, but with
+/// [`exit`] if the remote couldn't be reached.
+pub fn run_git(args: &[&str], cwd: Option<&Path>) -> Result<()> {
+    if !run_git_status(args, cwd)?.success() {
+        log_error("Command failed");
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Like [`run_git`], but leaves handling other failures to the caller.
+pub fn run_git_status(args: &[&str], cwd: Option<&Path>) -> Result<ExitStatus> {
+    throttle::git(args, cwd, || spawn_git(args, cwd))
+}
+
+fn spawn_git(args: &[&str], cwd: Option<&Path>) -> Result<ExitStatus> {
+    let mut command = new_command("git");
+    // Git only reports progress on its own to a terminal; screen readers
+    // would read out every redraw of it
+    match args.split_first() {
+        Some((subcommand, rest)) if io::stderr().is_terminal() && !accessible::enabled() => {
+            command.arg(subcommand).arg("--progress").args(rest);
+        }
+        _ => {
+            command.args(args);
+        }
+    }
+    command.stderr(Stdio::piped());
+    if let Some(dir) = cwd {
+        git_env::in_dir(&mut command, dir);
+    }
+
+    let mut child = command.spawn().context("Failed to execute command: git")?;
+    let mut stderr = child.stderr.take().context("Failed to capture stderr")?;
+    // Pass stderr through as it comes, keeping a copy to look at afterwards
+    let forwarder = thread::spawn(move || {
+        let mut seen = Vec::new();
+        let mut buffer = [0; 4096];
+        while let Ok(read) = stderr.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+            let _ = io::stderr().write_all(&buffer[..read]);
+            seen.extend_from_slice(&buffer[..read]);
+        }
+        seen
+    });
+
+    let status = timings::measure(
+        || format!("git {}", args.join(" ")),
+        || wait_with_timeout(&mut child, "git", args),
+    )?;
+    let seen = forwarder.join().unwrap_or_default();
+    if !status.success() && is_network_error(&String::from_utf8_lossy(&seen)) {
+        exit();
+    }
+    Ok(status)
+}
@@ -0,0 +1,78 @@
+//! Who created a worktree, for shared development machines.
+//!
+//! `add` writes the user's name into `git-wt-user` in the worktree's
+//! administrative directory (`.bare/worktrees/<name>`), so it goes away with
+//! the worktree. `list` shows worktrees created by someone else, and `rm` and
+//! `clean` refuse to remove them, or ones whose directory another user owns,
+//! without `--force`.
+
+use crate::shared;
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const USER_FILE: &str = "git-wt-user";
+
+/// The name of the user running git-wt.
+pub fn current_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .or_else(|_| env::var("LOGNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// The git directory of the worktree at `worktree`, read from its `.git`
+/// file without running git.
+pub fn admin_dir(worktree: &Path) -> Option<PathBuf> {
+    let dot_git = worktree.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let contents = fs::read_to_string(&dot_git).ok()?;
+    Some(worktree.join(contents.strip_prefix("gitdir:")?.trim()))
+}
+
+/// Records the current user as the creator of the worktree at `worktree`.
+pub fn record(worktree: &Path) -> Result<()> {
+    let Some(dir) = admin_dir(worktree) else {
+        return Ok(());
+    };
+    let path = dir.join(USER_FILE);
+    fs::write(&path, format!("{}\n", current_user()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    shared::state_file(&path);
+    Ok(())
+}
+
+/// The user who created the worktree at `worktree`, if known.
+pub fn creator(worktree: &Path) -> Option<String> {
+    let user = fs::read_to_string(admin_dir(worktree)?.join(USER_FILE)).ok()?;
+    Some(user.trim().to_string()).filter(|user| !user.is_empty())
+}
+
+/// Why the worktree at `worktree` belongs to someone else, if it does: it was
+/// created by another user, or its directory is owned by one.
+pub fn foreign(worktree: &Path) -> Option<String> {
+    if let Some(user) = creator(worktree).filter(|user| *user != current_user()) {
+        return Some(format!("it was created by {user}"));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let owner = fs::metadata(worktree).ok()?.uid();
+        let ours = std::process::Command::new("id")
+            .arg("-u")
+            .output()
+            .ok()
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .and_then(|uid| uid.trim().parse::<u32>().ok())?;
+        if owner != ours {
+            return Some(format!("its directory is owned by uid {owner}"));
+        }
+    }
+
+    None
+}
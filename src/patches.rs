@@ -0,0 +1,108 @@
+//! Moving a branch between machines as a `git format-patch` series.
+
+use crate::{
+    AddArgs, add_worktree, check_git_repo, default_branch_ref, git_stdout, log_error, log_info,
+    resolve_worktree, run_command, run_command_status,
+};
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Writes the commits of `branch` since its merge base with the default
+/// branch as patch files into `output`.
+pub fn export(branch: &str, output: &Path) -> Result<()> {
+    let (branch, path) = resolve_worktree(Some(branch))?;
+    let path = Path::new(&path);
+
+    let Some(base) = default_branch_ref() else {
+        log_error("Could not determine the default branch (is origin/HEAD set?)");
+        process::exit(1);
+    };
+    let merge_base = git_stdout(&["merge-base", &base, "HEAD"], Some(path))
+        .with_context(|| format!("'{branch}' has no common history with {base}"))?;
+
+    let range = format!("{merge_base}..HEAD");
+    if git_stdout(&["rev-list", "--count", &range], Some(path)).as_deref() == Some("0") {
+        log_info(&format!("'{branch}' has no commits on top of {base}"));
+        return Ok(());
+    }
+
+    // format-patch runs inside the worktree, so anchor a relative output dir
+    // to where we were called from
+    let output = std::path::absolute(output).context("Invalid output directory")?;
+    let output = output.to_string_lossy();
+    log_info(&format!("Exporting '{branch}' since {base} to {output}"));
+    run_command(
+        "git",
+        &[
+            "format-patch",
+            "--quiet",
+            "--output-directory",
+            &output,
+            &range,
+        ],
+        Some(path),
+    )?;
+
+    Ok(())
+}
+
+/// Creates a worktree from the default branch and applies the patch series in
+/// `dir` to it. The branch is named after `dir` unless `name` is given.
+pub fn import(dir: &Path, name: Option<&str>) -> Result<()> {
+    check_git_repo();
+
+    let mut patches: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "patch"))
+        .collect();
+    // format-patch numbers its files, so name order is commit order
+    patches.sort();
+
+    if patches.is_empty() {
+        log_error(&format!("No .patch files in {}", dir.display()));
+        process::exit(1);
+    }
+
+    let branch = match name {
+        Some(name) => name.to_string(),
+        None => std::path::absolute(dir)?
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .context("Pass --as to name the branch")?,
+    };
+
+    let worktree_path = add_worktree(&AddArgs {
+        branch: branch.clone(),
+        from: default_branch_ref(),
+        no_track: true,
+        ..AddArgs::default()
+    })?;
+
+    log_info(&format!(
+        "Applying {} patch(es) to '{branch}'",
+        patches.len()
+    ));
+    let mut args = vec!["am".to_string(), "--3way".to_string()];
+    args.extend(
+        patches
+            .iter()
+            .map(|patch| std::path::absolute(patch).map(|p| p.to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>, _>>()?,
+    );
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    if !run_command_status("git", &args, Some(&worktree_path))?.success() {
+        log_error(&format!(
+            "Applying the series stopped; resolve it in {} and run `git am --continue`",
+            worktree_path.display()
+        ));
+        process::exit(1);
+    }
+
+    log_info("Series applied.");
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! `resolve` and `current`: plumbing for scripts, prompts and editor plugins.
+//!
+//! Unlike the porcelain commands these never prompt, never fuzzy-match and
+//! never take an index for a branch: a name either names a worktree's branch
+//! exactly or it's an error. They print bare values on stdout, one per line,
+//! and keep messages to stderr.
+
+use crate::{
+    check_git_repo, get_worktree_root, git_common_dir, git_env, git_stdout, log_error, process,
+    worktree,
+};
+use color_eyre::Result;
+use serde::Serialize;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Exit code of `current` outside any worktree, telling that apart from
+/// failures.
+pub const NOT_IN_WORKTREE_EXIT_CODE: i32 = 3;
+
+/// The worktree `current --json` describes.
+#[derive(Serialize)]
+struct Current {
+    /// `None` when detached
+    branch: Option<String>,
+    path: String,
+    root: PathBuf,
+    /// Commit checked out, `None` before the first commit
+    head: Option<String>,
+    detached: bool,
+    /// Why the worktree is locked, if it is (empty if no reason was given)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locked: Option<String>,
+    /// e.g. "origin/main", if the branch has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upstream: Option<String>,
+    /// Commits not on the upstream yet, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ahead: Option<usize>,
+    /// Upstream commits not merged yet, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    behind: Option<usize>,
+    dirty: bool,
+}
+
+/// Prints the path of the worktree that has exactly `branch` checked out.
+pub fn resolve(branch: &str) -> Result<()> {
+    check_git_repo();
+    let branch = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+    let Some(worktree) = worktree::list()?
+        .into_iter()
+        .find(|worktree| worktree.branch.as_deref() == Some(branch))
+    else {
+        log_error(&format!("No worktree has branch '{branch}' checked out"));
+        process::exit(1);
+    };
+    println!("{}", worktree.path);
+    Ok(())
+}
+
+/// Prints the branch of the worktree containing the current directory, or
+/// with `json` everything about it. Exits with [`NOT_IN_WORKTREE_EXIT_CODE`]
+/// outside of one.
+pub fn current(json: bool) -> Result<()> {
+    // Like git, a GIT_WORK_TREE from the caller wins over the directory
+    let dir = git_env::work_tree().map_or_else(env::current_dir, Ok)?;
+    let worktree = match git_common_dir() {
+        Some(_) => worktree::containing(&dir)?,
+        None => None,
+    };
+    let Some(worktree) = worktree else {
+        log_error("Not inside a worktree");
+        process::exit(NOT_IN_WORKTREE_EXIT_CODE);
+    };
+
+    if json {
+        let current = describe(worktree)?;
+        println!("{}", serde_json::to_string_pretty(&current)?);
+        return Ok(());
+    }
+    let Some(branch) = worktree.branch else {
+        log_error(&format!("{} is detached", worktree.path));
+        process::exit(1);
+    };
+    println!("{branch}");
+    Ok(())
+}
+
+fn describe(worktree: worktree::Worktree) -> Result<Current> {
+    let path = Path::new(&worktree.path);
+    let upstream = git_stdout(
+        &[
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ],
+        Some(path),
+    );
+    let counts = upstream.as_ref().and_then(|_| {
+        let counts = git_stdout(
+            &["rev-list", "--left-right", "--count", "HEAD...@{upstream}"],
+            Some(path),
+        )?;
+        let (ahead, behind) = counts.split_once('\t')?;
+        Some((ahead.parse().ok()?, behind.parse().ok()?))
+    });
+    let dirty = git_stdout(
+        &["--no-optional-locks", "status", "--porcelain"],
+        Some(path),
+    )
+    .is_some_and(|status| !status.is_empty());
+
+    Ok(Current {
+        detached: worktree.branch.is_none(),
+        branch: worktree.branch,
+        root: get_worktree_root()?,
+        head: worktree.head,
+        locked: worktree.locked,
+        upstream,
+        ahead: counts.map(|(ahead, _)| ahead),
+        behind: counts.map(|(_, behind)| behind),
+        dirty,
+        path: worktree.path,
+    })
+}
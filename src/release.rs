@@ -0,0 +1,92 @@
+//! `release`: prepares a `release/<version>` branch in its own worktree.
+//!
+//! The branch starts from `--from`, the configured `release.base` or the
+//! default branch. The `release.bump` commands then run in the new worktree
+//! and whatever they change is committed before the branch is pushed.
+
+use crate::hooks::{HookContext, shell_quote};
+use crate::{
+    AddArgs, add_worktree, change_directory, check_git_repo, config, default_branch_ref,
+    default_remote, get_worktree_root, git_env, git_stdout, log_error, log_info, run_command,
+};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use std::process;
+
+pub fn run(version: &str, from: Option<&str>, push: bool) -> Result<()> {
+    check_git_repo();
+    let config = config::get();
+    let branch = format!("release/{version}");
+
+    let Some(base) = from
+        .map(str::to_string)
+        .or_else(|| config.release.base.clone())
+        .or_else(default_branch_ref)
+    else {
+        log_error("No base for the release branch; pass --from or set release.base");
+        process::exit(1);
+    };
+
+    log_info(&format!("Preparing {branch} from {base}..."));
+    let worktree = add_worktree(&AddArgs {
+        branch: branch.clone(),
+        from: Some(base),
+        ..AddArgs::default()
+    })?;
+
+    let root = get_worktree_root()?;
+    let context = HookContext {
+        branch: &branch,
+        path: &worktree,
+        root: &root,
+        base: None,
+    };
+    for command in config.release.bump.iter().flatten() {
+        let command = context
+            .expand_quoted(command)
+            .replace("{version}", &shell_quote(version));
+        log_info(&format!("Running: {command}"));
+        let status = git_env::in_dir(&mut context.shell(&command), &worktree)
+            .env("GIT_WT_VERSION", version)
+            .status()
+            .with_context(|| format!("Failed to run {command}"))?;
+        if !status.success() {
+            log_error(&format!("Version bump failed: {command}"));
+            log_info(&format!(
+                "The worktree is at {}; fix it up and push {branch} yourself.",
+                worktree.display()
+            ));
+            process::exit(1);
+        }
+    }
+
+    let changed = git_stdout(
+        &["--no-optional-locks", "status", "--porcelain"],
+        Some(&worktree),
+    )
+    .is_some_and(|status| !status.is_empty());
+    if changed {
+        run_command("git", &["add", "--all"], Some(&worktree))?;
+        run_command(
+            "git",
+            &[
+                "commit",
+                "--quiet",
+                "-m",
+                &format!("Bump version to {version}"),
+            ],
+            Some(&worktree),
+        )?;
+        log_info(&format!("Committed the version bump to {version}."));
+    }
+
+    if push {
+        let remote = default_remote();
+        log_info(&format!("Pushing {branch} to {remote}..."));
+        run_command("git", &["push", "-u", &remote, &branch], Some(&worktree))?;
+    }
+
+    log_info(&format!("Release branch {branch} is ready."));
+    change_directory(&worktree);
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! `report`: every local branch with its worktree, age, last author and how
+//! far it is from its upstream, as a Markdown table to paste into a standup
+//! doc or as JSON.
+
+use crate::list::{self, last_commit_times, tracking_counts};
+use crate::{check_git_repo, format, git_stdout, gone_branches, owner, worktree};
+use clap::ValueEnum;
+use color_eyre::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Md,
+    Json,
+}
+
+#[derive(Serialize)]
+struct Row {
+    branch: String,
+    /// Where the branch is checked out, if anywhere
+    path: Option<String>,
+    /// Unix time of the latest commit
+    last_commit: Option<u64>,
+    /// Author of the latest commit
+    author: Option<String>,
+    upstream: Option<String>,
+    upstream_gone: bool,
+    ahead: Option<usize>,
+    behind: Option<usize>,
+    /// Uncommitted changes; unknown without a worktree
+    dirty: Option<bool>,
+    /// User who created the worktree, if recorded
+    user: Option<String>,
+}
+
+pub fn run(format: ReportFormat) -> Result<()> {
+    check_git_repo();
+    let rows = collect()?;
+    match format {
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+        ReportFormat::Md => print!("{}", markdown(&rows)),
+    }
+    Ok(())
+}
+
+/// One row per local branch, the longest untouched first.
+fn collect() -> Result<Vec<Row>> {
+    let worktrees: HashMap<String, String> = worktree::list()?
+        .into_iter()
+        .filter_map(|worktree| Some((worktree.branch?, worktree.path)))
+        .collect();
+    let last_commits = last_commit_times();
+    let tracking = tracking_counts();
+    let gone = gone_branches();
+
+    let mut rows: Vec<Row> = git_stdout(
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%00%(authorname)%00%(upstream:short)",
+            "refs/heads",
+        ],
+        None,
+    )
+    .unwrap_or_default()
+    .lines()
+    .filter_map(|line| {
+        let mut fields = line.split('\0');
+        let (branch, author, upstream) = (fields.next()?, fields.next()?, fields.next()?);
+        let path = worktrees.get(branch).cloned();
+        let (ahead, behind) = tracking.get(branch).copied().unzip();
+        Some(Row {
+            dirty: path.as_deref().map(|path| list::is_dirty(Path::new(path))),
+            user: path
+                .as_deref()
+                .and_then(|path| owner::creator(Path::new(path))),
+            path,
+            last_commit: last_commits.get(branch).copied(),
+            author: (!author.is_empty()).then(|| author.to_string()),
+            upstream: (!upstream.is_empty()).then(|| upstream.to_string()),
+            upstream_gone: gone.contains(branch),
+            ahead,
+            behind,
+            branch: branch.to_string(),
+        })
+    })
+    .collect();
+    rows.sort_by_key(|row| row.last_commit.unwrap_or(u64::MAX));
+    Ok(rows)
+}
+
+fn markdown(rows: &[Row]) -> String {
+    let mut table = String::from(
+        "| Branch | Worktree | Last commit | Author | Upstream | Ahead/behind | Status |\n\
+         | --- | --- | --- | --- | --- | --- | --- |\n",
+    );
+    for row in rows {
+        let date = row.last_commit.map_or("-".to_string(), format::date);
+        let tracking = match (row.ahead, row.behind) {
+            (Some(ahead), Some(behind)) => format!("+{ahead}/-{behind}"),
+            _ => "-".to_string(),
+        };
+        let mut status = Vec::new();
+        if row.upstream_gone {
+            status.push("upstream gone".to_string());
+        }
+        if row.dirty == Some(true) {
+            status.push("dirty".to_string());
+        }
+        if let Some(user) = &row.user {
+            status.push(format!("by {user}"));
+        }
+        let cells = [
+            row.branch.as_str(),
+            row.path.as_deref().unwrap_or("-"),
+            &date,
+            row.author.as_deref().unwrap_or("-"),
+            row.upstream.as_deref().unwrap_or("-"),
+            &tracking,
+            &status.join(", "),
+        ];
+        let cells: Vec<String> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        let _ = writeln!(table, "| {} |", cells.join(" | "));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_escapes_pipes_and_describes_status() {
+        let rows = [Row {
+            branch: "fix|pipe".to_string(),
+            path: Some("/wt/fix".to_string()),
+            last_commit: Some(format::now() - 3 * 24 * 60 * 60),
+            author: Some("Ada".to_string()),
+            upstream: Some("origin/fix".to_string()),
+            upstream_gone: false,
+            ahead: Some(2),
+            behind: Some(0),
+            dirty: Some(true),
+            user: None,
+        }];
+
+        let table = markdown(&rows);
+        assert_eq!(
+            table.lines().nth(2),
+            Some("| fix\\|pipe | /wt/fix | 3 days ago | Ada | origin/fix | +2/-0 | dirty |")
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Where git and the other programs git-wt calls are started.
+//!
+//! `run_command`, `git_stdout` and friends go through the [`GitRunner`] in
+//! use: [`SystemRunner`], unless a unit test swaps in a [`Recorder`], which
+//! logs every invocation and answers with scripted output instead of running
+//! anything. Programs whose output is streamed as it comes (`offline`,
+//! `grep`, hooks) still start their own processes, waiting for them with
+//! the same timeout.
+
+use crate::{git_env, new_command, timings, wait_with_timeout};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Output, Stdio};
+use std::rc::Rc;
+use std::thread;
+
+/// Which of the program's output streams end up in the returned [`Output`];
+/// the others stay attached to the terminal.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    Nothing,
+    /// Stdout, leaving stderr visible for progress and credential prompts
+    Stdout,
+    StdoutAndStderr,
+}
+
+pub trait GitRunner {
+    /// Runs `program` with `args`, and `env` set on top of git-wt's own
+    /// environment.
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+        env: &[(&str, &OsStr)],
+        cwd: Option<&Path>,
+        capture: Capture,
+    ) -> Result<Output>;
+}
+
+/// Starts real processes, killing them after `--timeout`.
+pub struct SystemRunner;
+
+impl GitRunner for SystemRunner {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+        env: &[(&str, &OsStr)],
+        cwd: Option<&Path>,
+        capture: Capture,
+    ) -> Result<Output> {
+        let mut command = new_command(program);
+        command.args(args);
+        if let Some(dir) = cwd {
+            git_env::in_dir(&mut command, dir);
+        }
+        // After in_dir, which would drop e.g. a GIT_INDEX_FILE given here
+        command.envs(env.iter().copied());
+        let label = || format!("{program} {}", args.join(" "));
+
+        match capture {
+            Capture::Nothing => {}
+            Capture::Stdout => {
+                command.stdout(Stdio::piped());
+            }
+            // Like `Command::output`, which this replaces to honor the timeout
+            Capture::StdoutAndStderr => {
+                command
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+            }
+        }
+
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to execute command: {program}"))?;
+
+        // Drain the pipes while waiting so a full one can't block the child
+        let stdout = child.stdout.take().map(drain);
+        let stderr = child.stderr.take().map(drain);
+
+        let status = timings::measure(label, || wait_with_timeout(&mut child, program, args))?;
+        let collect = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+            reader
+                .map(|reader| reader.join().unwrap_or_default())
+                .unwrap_or_default()
+        };
+        Ok(Output {
+            status,
+            stdout: collect(stdout),
+            stderr: collect(stderr),
+        })
+    }
+}
+
+/// Reads `pipe` to the end on another thread.
+fn drain(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = pipe.read_to_end(&mut output);
+        output
+    })
+}
+
+thread_local! {
+    static RUNNER: RefCell<Option<Rc<dyn GitRunner>>> = const { RefCell::new(None) };
+}
+
+/// Runs `program` with the runner in use on this thread.
+pub fn run(program: &str, args: &[&str], cwd: Option<&Path>, capture: Capture) -> Result<Output> {
+    run_with_env(program, args, &[], cwd, capture)
+}
+
+/// Like [`run`], with `env` set for the program.
+pub fn run_with_env(
+    program: &str,
+    args: &[&str],
+    env: &[(&str, &OsStr)],
+    cwd: Option<&Path>,
+    capture: Capture,
+) -> Result<Output> {
+    match RUNNER.with_borrow(Clone::clone) {
+        Some(runner) => runner.run(program, args, env, cwd, capture),
+        None => SystemRunner.run(program, args, env, cwd, capture),
+    }
+}
+
+/// Runs `f` with `runner` starting this thread's processes.
+#[cfg(test)]
+pub fn with<T>(runner: Rc<dyn GitRunner>, f: impl FnOnce() -> T) -> T {
+    let previous = RUNNER.replace(Some(runner));
+    let result = f();
+    RUNNER.set(previous);
+    result
+}
+
+/// Records invocations as `program arg...`, preceded by `NAME=value` for
+/// each variable in `env` and prefixed with `cwd: ` if one was given, and
+/// answers them from scripted responses, or with success and no output.
+#[cfg(test)]
+#[derive(Default)]
+pub struct Recorder {
+    invocations: RefCell<Vec<String>>,
+    responses: RefCell<Vec<(String, i32, String)>>,
+}
+
+#[cfg(test)]
+impl Recorder {
+    /// Answers invocations starting with `prefix` with exit code `code` and
+    /// `stdout`.
+    pub fn respond(&self, prefix: &str, code: i32, stdout: &str) {
+        self.responses
+            .borrow_mut()
+            .push((prefix.to_string(), code, stdout.to_string()));
+    }
+
+    pub fn invocations(&self) -> Vec<String> {
+        self.invocations.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+impl GitRunner for Recorder {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+        env: &[(&str, &OsStr)],
+        cwd: Option<&Path>,
+        _capture: Capture,
+    ) -> Result<Output> {
+        let mut invocation = format!("{program} {}", args.join(" "));
+        for (name, value) in env.iter().rev() {
+            invocation = format!("{name}={} {invocation}", value.to_string_lossy());
+        }
+        if let Some(dir) = cwd {
+            invocation = format!("{}: {invocation}", dir.display());
+        }
+        self.invocations.borrow_mut().push(invocation.clone());
+
+        let (code, stdout) = self
+            .responses
+            .borrow()
+            .iter()
+            .find(|(prefix, _, _)| invocation.starts_with(prefix.as_str()))
+            .map_or((0, String::new()), |(_, code, stdout)| {
+                (*code, stdout.clone())
+            });
+        Ok(Output {
+            status: exit_status(code),
+            stdout: stdout.into_bytes(),
+            stderr: Vec::new(),
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+fn exit_status(code: i32) -> std::process::ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    std::process::ExitStatus::from_raw(code << 8)
+}
+
+#[cfg(all(test, windows))]
+fn exit_status(code: i32) -> std::process::ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    std::process::ExitStatus::from_raw(code.cast_unsigned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_runner_captures_both_streams() {
+        let output = SystemRunner
+            .run(
+                "git",
+                &["rev-parse", "--verify", "refs/heads/git-wt-no-such-branch"],
+                &[("GIT_DIR", OsStr::new("/nonexistent"))],
+                None,
+                Capture::StdoutAndStderr,
+            )
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+        assert!(!output.stderr.is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! JSON-RPC 2.0 over a unix socket for editor plugins.
+//!
+//! Each request is one line of JSON and gets one line back. Methods:
+//!
+//! - `list`: every worktree as `{"branch", "path"}`
+//! - `resolve` `{"branch"}`: the worktree path a branch argument switches to
+//! - `add` `{"branch", "from"?}`: creates a worktree and returns its path
+//! - `remove` `{"branch", "force"?}`: removes a worktree without confirmation
+//!
+//! Commands that change worktrees run as a child `git-wt --non-interactive`
+//! process, so their hooks, output and failures stay isolated from the server.
+
+use crate::{get_all_worktrees, log_info};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const COMMAND_FAILED: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Params,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Params {
+    branch: Option<String>,
+    from: Option<String>,
+    force: bool,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct Worktree {
+    branch: String,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct WorktreePath {
+    path: String,
+}
+
+/// Listens on `socket` until the process is killed.
+pub fn run(socket: &Path) -> Result<()> {
+    // A socket file nobody listens on is left over from an earlier server
+    if socket.exists() && UnixStream::connect(socket).is_err() {
+        fs::remove_file(socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    log_info(&format!("Listening on {}", socket.display()));
+
+    for stream in listener.incoming() {
+        let stream = stream.context("Failed to accept connection")?;
+        thread::spawn(move || {
+            let _ = serve_connection(stream);
+        });
+    }
+
+    Ok(())
+}
+
+fn serve_connection(stream: UnixStream) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let id = request.id.clone().unwrap_or_default();
+                match dispatch(&request) {
+                    Ok(result) => Response {
+                        jsonrpc: "2.0",
+                        id,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err(error) => Response {
+                        jsonrpc: "2.0",
+                        id,
+                        result: None,
+                        error: Some(error),
+                    },
+                }
+            }
+            Err(e) => Response {
+                jsonrpc: "2.0",
+                id: Value::Null,
+                result: None,
+                error: Some(RpcError {
+                    code: PARSE_ERROR,
+                    message: format!("Invalid request: {e}"),
+                }),
+            },
+        };
+
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+    }
+
+    Ok(())
+}
+
+fn dispatch(request: &Request) -> Result<Value, RpcError> {
+    let params = &request.params;
+    let branch = || {
+        params.branch.as_deref().ok_or_else(|| RpcError {
+            code: INVALID_PARAMS,
+            message: "Missing parameter: branch".to_string(),
+        })
+    };
+
+    match request.method.as_str() {
+        "list" => {
+            let worktrees: Vec<Worktree> = get_all_worktrees()
+                .map_err(|e| command_failed(&e.to_string()))?
+                .into_iter()
+                .map(|(branch, path)| Worktree { branch, path })
+                .collect();
+            to_value(&worktrees)
+        }
+        "resolve" => resolve(branch()?),
+        "add" => {
+            let branch = branch()?;
+            let mut args = vec!["add", branch];
+            if let Some(from) = &params.from {
+                args.extend(["--from", from]);
+            }
+            run_self(&args)?;
+            resolve(branch)
+        }
+        "remove" => {
+            let mut args = vec!["rm", branch()?, "--yes"];
+            if params.force {
+                args.push("--force");
+            }
+            run_self(&args)?;
+            Ok(Value::Null)
+        }
+        method => Err(RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("Unknown method: {method}"),
+        }),
+    }
+}
+
+fn resolve(branch: &str) -> Result<Value, RpcError> {
+    let path = run_self(&["path", branch])?;
+    to_value(&WorktreePath { path })
+}
+
+/// Runs this binary non-interactively and returns its trimmed stdout, or its
+/// error message if it failed.
+fn run_self(args: &[&str]) -> Result<String, RpcError> {
+    let exe = env::current_exe().map_err(|e| command_failed(&e.to_string()))?;
+    let output = Command::new(exe)
+        .args([
+            "--non-interactive",
+            "--no-terminal-prompt",
+            "--color",
+            "never",
+        ])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| command_failed(&e.to_string()))?;
+
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("ERROR: "))
+        .unwrap_or_else(|| stderr.trim());
+    Err(command_failed(message))
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| command_failed(&e.to_string()))
+}
+
+fn command_failed(message: &str) -> RpcError {
+    RpcError {
+        code: COMMAND_FAILED,
+        message: message.to_string(),
+    }
+}
@@ -0,0 +1,140 @@
+//! `setup`: a first-run wizard that installs the shell integration and
+//! writes the most common settings into the global config.
+
+use crate::accessible::{self, Ask};
+use crate::{
+    Shell, config, create_confirm_render_config, log_error, log_info, process, prompt_result,
+};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use inquire::{Confirm, Text};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+pub fn run() -> Result<()> {
+    if let Some(reason) = config::get().non_interactive_reason() {
+        log_error(&format!(
+            "Cannot run the setup because {reason}; see `git-wt init` and `git-wt config` instead"
+        ));
+        process::exit(1);
+    }
+
+    install_integration()?;
+
+    let current = config::get();
+    let editor = current
+        .editor
+        .clone()
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .unwrap_or_default();
+    let editor = ask(
+        "Editor `git-wt open` starts (e.g. code --new-window):",
+        &editor,
+    )?;
+    let worktree_dir = current
+        .worktree_dir
+        .as_ref()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let worktree_dir = ask(
+        "Directory for new worktrees, relative to the repository (empty for the default):",
+        &worktree_dir,
+    )?;
+
+    for (key, value) in [("editor", editor), ("worktree_dir", worktree_dir)] {
+        if value.is_empty() {
+            continue;
+        }
+        // Quoted so that values like `true` stay strings
+        let value = format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+        config::set_value(key, Some(&value), true, None)?;
+    }
+    if let Some(path) = config::global_config_path() {
+        log_info(&format!("Settings saved to {}", path.display()));
+    }
+    log_info("Change them later with `git-wt config set --global <key> <value>`.");
+    Ok(())
+}
+
+/// Adds the `git-wt init` line to the rc file of the user's shell, unless
+/// it is already there.
+fn install_integration() -> Result<()> {
+    let shell_name = env::var("SHELL").unwrap_or_default();
+    let shell = match shell_name.rsplit('/').next().unwrap_or_default() {
+        "fish" => Shell::Fish,
+        "bash" => Shell::Bash,
+        "zsh" => Shell::Zsh,
+        _ => {
+            log_info(&format!(
+                "Note: don't know how to set up '{shell_name}'; see `git-wt init` for bash, zsh and fish"
+            ));
+            return Ok(());
+        }
+    };
+
+    let Some(home) = env::var_os("HOME").map(PathBuf::from) else {
+        return Ok(());
+    };
+    let (rc_file, line) = match shell {
+        Shell::Fish => (
+            home.join(".config/fish/config.fish"),
+            "git-wt init fish | source",
+        ),
+        Shell::Bash => (home.join(".bashrc"), "eval \"$(git-wt init bash)\""),
+        Shell::Zsh => (home.join(".zshrc"), "eval \"$(git-wt init zsh)\""),
+    };
+
+    let existing = fs::read_to_string(&rc_file).unwrap_or_default();
+    if existing.contains("git-wt init") {
+        log_info(&format!(
+            "Shell integration is already set up in {}.",
+            rc_file.display()
+        ));
+        return Ok(());
+    }
+
+    let prompt = format!("Add the shell integration to {}?", rc_file.display());
+    let answer = Confirm::new("")
+        .with_default(true)
+        .with_render_config(create_confirm_render_config(&prompt))
+        .ask();
+    if prompt_result(answer)? != Some(true) {
+        log_info(&format!("Skipped. To set it up yourself, add: {line}"));
+        return Ok(());
+    }
+
+    if let Some(parent) = rc_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&rc_file)
+        .with_context(|| format!("Failed to open {}", rc_file.display()))?;
+    let separator = if existing.is_empty() || existing.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    writeln!(file, "{separator}\n# git-wt shell integration\n{line}")
+        .with_context(|| format!("Failed to write {}", rc_file.display()))?;
+    log_info(&format!(
+        "Added to {}; open a new shell to use it.",
+        rc_file.display()
+    ));
+    Ok(())
+}
+
+/// Asks for a text value, `current` being the answer when just pressing
+/// enter. Cancelling keeps `current`.
+fn ask(prompt: &str, current: &str) -> Result<String> {
+    let answer = if accessible::enabled() {
+        accessible::text(prompt, current).context("Failed to read the answer")?
+    } else {
+        prompt_result(Text::new(prompt).with_default(current).prompt_skippable())?
+    };
+    Ok(answer.unwrap_or_else(|| current.to_string()))
+}
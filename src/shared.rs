@@ -0,0 +1,91 @@
+//! `shared = "group"`: bare repositories used by a whole team.
+//!
+//! The repository gets `core.sharedRepository=group`, so git keeps objects
+//! and refs group-writable, and the worktrees and git-wt's own state files
+//! are made group-writable too, with directories setgid so that new files
+//! keep the group. Without the setting the user's umask applies unchanged.
+
+use crate::config::{self, SharedMode};
+use crate::{git_stdout, owner, run_command};
+use color_eyre::Result;
+use std::fs;
+use std::path::Path;
+
+fn enabled() -> bool {
+    config::get().shared == Some(SharedMode::Group)
+}
+
+/// Sets `core.sharedRepository=group` on the repository in `dir`, unless it
+/// already has a sharing mode.
+pub fn configure_repo(dir: Option<&Path>) -> Result<()> {
+    if !enabled() || git_stdout(&["config", "--get", "core.sharedRepository"], dir).is_some() {
+        return Ok(());
+    }
+    run_command("git", &["config", "core.sharedRepository", "group"], dir)
+}
+
+/// Makes the state file at `path`, and the directory holding it,
+/// group-writable.
+pub fn state_file(path: &Path) {
+    if !enabled() {
+        return;
+    }
+    if let Some(parent) = path.parent() {
+        make_group_writable(parent);
+    }
+    make_group_writable(path);
+}
+
+/// Makes the new worktree at `path`, and its administrative directory in the
+/// repository, group-writable. Also configures the repository, for
+/// repositories cloned before `shared` was set.
+pub fn worktree(path: &Path) -> Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+    configure_repo(None)?;
+    directory(path);
+    if let Some(admin) = owner::admin_dir(path) {
+        directory(&admin);
+    }
+    Ok(())
+}
+
+/// Makes the directory at `path` and everything in it group-writable.
+/// Symlinks are left alone.
+pub fn directory(path: &Path) {
+    if !enabled() {
+        return;
+    }
+    make_group_writable(path);
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => directory(&entry.path()),
+            Ok(kind) if kind.is_file() => make_group_writable(&entry.path()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(unix)]
+fn make_group_writable(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return;
+    };
+    // Group gets what the owner has; directories also get setgid
+    let mode = metadata.permissions().mode();
+    let mut shared = mode | ((mode & 0o700) >> 3);
+    if metadata.is_dir() {
+        shared |= 0o2070;
+    }
+    if shared != mode {
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(shared));
+    }
+}
+
+#[cfg(not(unix))]
+fn make_group_writable(_path: &Path) {}
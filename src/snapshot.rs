@@ -0,0 +1,184 @@
+//! Checkpoints of uncommitted work stored under `refs/wt-snapshots/`.
+//!
+//! A snapshot is a commit whose parent is the worktree's HEAD and whose tree
+//! holds the worktree's tracked and untracked (but not ignored) files. It is
+//! built through a temporary index, so neither the branch, the real index nor
+//! the working tree are touched.
+
+use crate::runner::{self, Capture};
+use crate::{
+    format, get_all_worktrees, git_stdout, log_error, log_info, ref_exists, resolve_worktree,
+    run_command,
+};
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat, bail};
+use std::fs;
+use std::path::Path;
+use std::process;
+
+const SNAPSHOT_REFS: &str = "refs/wt-snapshots";
+
+/// Snapshots the given worktree (or every worktree with `all`).
+pub fn create(branch: Option<&str>, all: bool) -> Result<()> {
+    let targets = if all {
+        get_all_worktrees()?
+    } else {
+        vec![resolve_worktree(branch)?]
+    };
+
+    for (branch, path) in &targets {
+        match create_one(branch, Path::new(path))? {
+            Some(reference) => log_info(&format!("Snapshot of '{branch}' saved as {reference}")),
+            None => log_info(&format!("Nothing to snapshot in '{branch}'")),
+        }
+    }
+
+    Ok(())
+}
+
+fn create_one(branch: &str, path: &Path) -> Result<Option<String>> {
+    let index = git_stdout(&["rev-parse", "--git-path", "index"], Some(path))
+        .map(|index| path.join(index))
+        .context("Failed to locate the worktree index")?;
+
+    // Start from a copy of the real index so unchanged files hash quickly
+    let temp_index = std::env::temp_dir().join(format!("git-wt-snapshot-{}", process::id()));
+    if index.exists() {
+        fs::copy(&index, &temp_index).context("Failed to copy the worktree index")?;
+    }
+
+    let tree = git_with_index(&["add", "--all"], path, &temp_index)
+        .and_then(|_| git_with_index(&["write-tree"], path, &temp_index));
+    let _ = fs::remove_file(&temp_index);
+    let tree = tree?;
+
+    if git_stdout(&["rev-parse", "HEAD^{tree}"], Some(path)).as_deref() == Some(tree.as_str()) {
+        return Ok(None);
+    }
+
+    let message = format!("git-wt snapshot of {branch}");
+    let Some(commit) = git_stdout(
+        &["commit-tree", &tree, "-p", "HEAD", "-m", &message],
+        Some(path),
+    ) else {
+        bail!("Failed to create snapshot commit for '{branch}'");
+    };
+
+    // Snapshots taken within the same second get a counter; the empty old
+    // value makes update-ref fail instead of overwriting one taken meanwhile
+    let timestamp = format::now();
+    let reference = (1..=1000)
+        .map(|n| match n {
+            1 => format!("{SNAPSHOT_REFS}/{branch}/{timestamp}"),
+            n => format!("{SNAPSHOT_REFS}/{branch}/{timestamp}-{n}"),
+        })
+        .find(|reference| !ref_exists(reference))
+        .context("No free snapshot name")?;
+    run_command("git", &["update-ref", &reference, &commit, ""], Some(path))?;
+
+    Ok(Some(reference))
+}
+
+/// Runs git against an alternate index file and returns its trimmed stdout.
+fn git_with_index(args: &[&str], cwd: &Path, index: &Path) -> Result<String> {
+    let output = runner::run_with_env(
+        "git",
+        args,
+        &[("GIT_INDEX_FILE", index.as_os_str())],
+        Some(cwd),
+        Capture::StdoutAndStderr,
+    )?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Lists snapshots, newest first, for one branch or all of them.
+pub fn list(branch: Option<&str>) -> Result<()> {
+    let pattern = match branch {
+        Some(_) => format!("{SNAPSHOT_REFS}/{}/", resolve_worktree(branch)?.0),
+        None => format!("{SNAPSHOT_REFS}/"),
+    };
+
+    let snapshots = git_stdout(
+        &[
+            "for-each-ref",
+            "--sort=-version:refname",
+            "--sort=-committerdate",
+            "--format=%(refname:lstrip=2)  (%(committerdate:relative))",
+            &pattern,
+        ],
+        None,
+    )
+    .unwrap_or_default();
+
+    if snapshots.is_empty() {
+        log_info("No snapshots.");
+    } else {
+        println!("{snapshots}");
+    }
+
+    Ok(())
+}
+
+/// Reapplies a snapshot (the newest unless `id` is given) to its worktree as
+/// uncommitted changes.
+pub fn restore(branch: Option<&str>, id: Option<&str>) -> Result<()> {
+    let (branch, path) = resolve_worktree(branch)?;
+    let path = Path::new(&path);
+
+    let reference = match id {
+        Some(id) => format!("{SNAPSHOT_REFS}/{branch}/{id}"),
+        None => git_stdout(
+            &[
+                "for-each-ref",
+                "--sort=-version:refname",
+                "--sort=-committerdate",
+                "--count=1",
+                "--format=%(refname)",
+                &format!("{SNAPSHOT_REFS}/{branch}/"),
+            ],
+            None,
+        )
+        .filter(|reference| !reference.is_empty())
+        .unwrap_or_else(|| {
+            log_error(&format!("No snapshots of '{branch}'"));
+            process::exit(1);
+        }),
+    };
+
+    let status = git_stdout(
+        &[
+            "--no-optional-locks",
+            "status",
+            "--porcelain",
+            "--untracked-files=no",
+        ],
+        Some(path),
+    );
+    if status.is_none_or(|status| !status.is_empty()) {
+        log_error(&format!(
+            "Worktree '{branch}' has uncommitted changes; snapshot or commit them first"
+        ));
+        process::exit(1);
+    }
+
+    log_info(&format!("Restoring {reference}..."));
+    run_command(
+        "git",
+        &["cherry-pick", "--no-commit", &reference],
+        Some(path),
+    )?;
+    // Leave the restored changes unstaged, like they were when snapshotted
+    run_command("git", &["reset", "--quiet"], Some(path))?;
+    log_info("Snapshot restored.");
+
+    Ok(())
+}
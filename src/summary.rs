@@ -0,0 +1,100 @@
+//! The table printed at the end of operations that touch every worktree.
+
+use crate::log_info;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Updated,
+    Removed,
+    Unchanged,
+    Skipped,
+    Failed,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Updated => "updated",
+            Status::Removed => "removed",
+            Status::Unchanged => "unchanged",
+            Status::Skipped => "skipped",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Summary {
+    rows: Vec<(String, Status, String)>,
+}
+
+impl Summary {
+    pub fn push(&mut self, branch: &str, status: Status, detail: impl Into<String>) {
+        self.rows.push((branch.to_string(), status, detail.into()));
+    }
+
+    /// Records a `fast_forward_worktree` description such as "skipped: no
+    /// upstream" under the status its prefix names.
+    pub fn push_described(&mut self, branch: &str, description: &str) {
+        let (status, detail) = match description.split_once(": ") {
+            Some(("updated", detail)) => (Status::Updated, detail),
+            Some(("skipped", detail)) => (Status::Skipped, detail),
+            Some(("failed", detail)) => (Status::Failed, detail),
+            // "up to date"
+            _ => (Status::Unchanged, ""),
+        };
+        self.push(branch, status, detail);
+    }
+
+    pub fn failed(&self) -> bool {
+        self.rows
+            .iter()
+            .any(|(_, status, _)| *status == Status::Failed)
+    }
+
+    /// Prints one row per worktree, failures last so they aren't scrolled
+    /// away, then the counts.
+    pub fn print(&self) {
+        if self.rows.is_empty() {
+            return;
+        }
+
+        let width = self
+            .rows
+            .iter()
+            .map(|(branch, _, _)| branch.len())
+            .max()
+            .unwrap_or(0);
+        let order = [
+            Status::Ok,
+            Status::Updated,
+            Status::Removed,
+            Status::Unchanged,
+            Status::Skipped,
+            Status::Failed,
+        ];
+
+        log_info("Summary:");
+        for status in order {
+            for (branch, _, detail) in self.rows.iter().filter(|row| row.1 == status) {
+                let label = status.label();
+                if detail.is_empty() {
+                    log_info(&format!("  {branch:<width$}  {label}"));
+                } else {
+                    log_info(&format!("  {branch:<width$}  {label:<9}  {detail}"));
+                }
+            }
+        }
+
+        let counts: Vec<String> = order
+            .iter()
+            .filter_map(|status| {
+                let count = self.rows.iter().filter(|row| row.1 == *status).count();
+                (count > 0).then(|| format!("{count} {}", status.label()))
+            })
+            .collect();
+        log_info(&format!("  {}", counts.join(", ")));
+    }
+}
@@ -0,0 +1,139 @@
+//! Workspace templates applied by `clone --template`.
+
+use crate::config::{self, Template, expand_tilde};
+use crate::{AddArgs, add_worktree, copy, log_info};
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Returns the named template, or an error listing where templates come from.
+pub fn find(name: &str) -> Result<&'static Template> {
+    config::get().templates.get(name).with_context(|| {
+        let location = config::global_config_path().map_or_else(
+            || "the global config".to_string(),
+            |p| p.display().to_string(),
+        );
+        format!("Unknown template '{name}' (templates are defined in {location})")
+    })
+}
+
+/// Applies `template` to a freshly cloned repository. Must be called with the
+/// repository root as the current directory.
+pub fn apply(template: &Template, root: &Path) -> Result<Vec<PathBuf>> {
+    if let Some(hooks) = &template.hooks {
+        install_hooks(&expand_tilde(hooks), &root.join(".bare").join("hooks"))?;
+    }
+
+    let mut created = Vec::new();
+    for branch in &template.worktrees {
+        let worktree_path = add_worktree(&AddArgs {
+            branch: branch.clone(),
+            ..AddArgs::default()
+        })?;
+
+        for rule in &template.copy {
+            let source = expand_tilde(rule.path());
+            let name = source
+                .file_name()
+                .with_context(|| format!("Invalid copy source: {}", source.display()))?;
+            copy::copy(&source, &worktree_path.join(name), rule.strategy())?;
+        }
+
+        for (target, shared) in &template.share {
+            link_shared_dir(&expand_tilde(shared), &worktree_path.join(target))?;
+        }
+        created.push(worktree_path);
+    }
+
+    Ok(created)
+}
+
+fn install_hooks(source: &Path, hooks_dir: &Path) -> Result<()> {
+    log_info(&format!("Installing hooks from {}", source.display()));
+    fs::create_dir_all(hooks_dir).context("Failed to create hooks directory")?;
+
+    let entries = fs::read_dir(source)
+        .with_context(|| format!("Failed to read hooks directory {}", source.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            // fs::copy keeps the executable bit
+            fs::copy(entry.path(), hooks_dir.join(entry.file_name()))
+                .with_context(|| format!("Failed to install hook {}", entry.path().display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Symlinks `target` inside a worktree to the shared directory `shared`,
+/// creating the shared directory on first use.
+fn link_shared_dir(shared: &Path, target: &Path) -> Result<()> {
+    fs::create_dir_all(shared).with_context(|| format!("Failed to create {}", shared.display()))?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    link(shared, target)
+}
+
+#[cfg(unix)]
+fn link(shared: &Path, target: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(shared, target).with_context(|| link_failed(shared, target))
+}
+
+/// Creating symlinks on Windows takes Developer Mode or administrator
+/// rights. Without them the link is a junction, which needs neither but
+/// only points at local drives; for a shared directory on a network drive
+/// `target` gets a copy instead, which isn't shared.
+#[cfg(windows)]
+fn link(shared: &Path, target: &Path) -> Result<()> {
+    // ERROR_PRIVILEGE_NOT_HELD
+    const NO_PRIVILEGE: i32 = 1314;
+
+    match std::os::windows::fs::symlink_dir(shared, target) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.raw_os_error() == Some(NO_PRIVILEGE) => {}
+        Err(e) => return Err(e).with_context(|| link_failed(shared, target)),
+    }
+
+    // Junctions need an absolute path, which mklink doesn't take in the
+    // \\?\ form canonicalize returns
+    let absolute = fs::canonicalize(shared)
+        .with_context(|| format!("Failed to resolve {}", shared.display()))?;
+    let absolute = absolute.to_string_lossy();
+    if absolute.starts_with(r"\\?\UNC\") {
+        log_info(&format!(
+            "Copying {} to {}, which is then not shared: symlinks to network drives need Developer Mode (Settings > System > For developers)",
+            shared.display(),
+            target.display()
+        ));
+        return copy::copy(shared, target, crate::config::CopyStrategy::Auto);
+    }
+    let absolute = absolute.strip_prefix(r"\\?\").unwrap_or(&absolute);
+
+    let output = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(target)
+        .arg(absolute)
+        .output()
+        .context("Failed to run mklink")?;
+    if !output.status.success() {
+        color_eyre::eyre::bail!(
+            "{}: symlinks need Developer Mode (Settings > System > For developers), and creating a junction failed too: {}",
+            link_failed(shared, target),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn link_failed(shared: &Path, target: &Path) -> String {
+    format!(
+        "Failed to link {} to {}",
+        target.display(),
+        shared.display()
+    )
+}
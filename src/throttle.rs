@@ -0,0 +1,176 @@
+//! Keeping git-wt polite to git servers.
+//!
+//! Git commands that talk to a remote wait for one of
+//! `remotes.max_concurrent` slots, and for `remotes.host_delay` milliseconds
+//! to have passed since the last one against the same host started. Running
+//! every worktree's pull back to back would otherwise trip rate limits or
+//! SSH `MaxSessions` on some corporate servers.
+
+use crate::{config, default_remote, git_stdout};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+/// Operations running now.
+static RUNNING: Mutex<usize> = Mutex::new(0);
+static SLOT_FREED: Condvar = Condvar::new();
+/// When the latest operation against each host started, or is due to.
+static STARTS: Mutex<Option<HashMap<String, Instant>>> = Mutex::new(None);
+
+/// A slot to run a remote operation in, given back when dropped.
+struct Slot;
+
+impl Slot {
+    fn acquire() -> Slot {
+        let limit = config::get()
+            .remotes
+            .max_concurrent
+            .unwrap_or(DEFAULT_MAX_CONCURRENT)
+            .max(1);
+        let mut running = lock(&RUNNING);
+        while *running >= limit {
+            running = SLOT_FREED
+                .wait(running)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *running += 1;
+        Slot
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *lock(&RUNNING) -= 1;
+        SLOT_FREED.notify_one();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Runs `operation`, a git command talking to `remote` (a remote name or
+/// URL) from `cwd`, once the limits allow it.
+pub fn remote<T>(remote: &str, cwd: Option<&Path>, operation: impl FnOnce() -> T) -> T {
+    let _slot = Slot::acquire();
+    if let Some(host) = host(remote, cwd) {
+        wait_for(&host);
+    }
+    operation()
+}
+
+/// Like [`remote`], for the git command line `args`, whose remote is the
+/// first argument after the subcommand or else the upstream's.
+pub fn git<T>(args: &[&str], cwd: Option<&Path>, operation: impl FnOnce() -> T) -> T {
+    let remote = args
+        .iter()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .map_or_else(|| upstream_remote(cwd), ToString::to_string);
+    self::remote(&remote, cwd, operation)
+}
+
+/// The remote the current branch in `cwd` pulls from, or the default one.
+fn upstream_remote(cwd: Option<&Path>) -> String {
+    git_stdout(&["symbolic-ref", "--quiet", "HEAD"], cwd)
+        .and_then(|head| {
+            git_stdout(
+                &["for-each-ref", "--format=%(upstream:remotename)", &head],
+                cwd,
+            )
+        })
+        .filter(|remote| !remote.is_empty())
+        .unwrap_or_else(default_remote)
+}
+
+/// Sleeps until `host_delay` has passed since the previous operation against
+/// `host` started, reserving the start first so concurrent operations queue.
+fn wait_for(host: &str) {
+    let delay = Duration::from_millis(config::get().remotes.host_delay.unwrap_or(0));
+    if delay.is_zero() {
+        return;
+    }
+    let start = {
+        let mut starts = lock(&STARTS);
+        let starts = starts.get_or_insert_with(HashMap::new);
+        let now = Instant::now();
+        let start = starts
+            .get(host)
+            .map_or(now, |previous| (*previous + delay).max(now));
+        starts.insert(host.to_string(), start);
+        start
+    };
+    thread::sleep(start.saturating_duration_since(Instant::now()));
+}
+
+/// The host `remote` (a remote name or URL) lives on; `None` for local
+/// repositories.
+fn host(remote: &str, cwd: Option<&Path>) -> Option<String> {
+    let url = if is_url(remote) {
+        remote.to_string()
+    } else {
+        git_stdout(&["remote", "get-url", remote], cwd)?
+    };
+    url_host(&url).map(str::to_lowercase)
+}
+
+fn is_url(remote: &str) -> bool {
+    remote.contains(':') || remote.contains('/') || remote.contains('\\')
+}
+
+/// The host of a git URL: `scheme://[user@]host[:port]/path` or scp-like
+/// `[user@]host:path`. Local paths and `file://` URLs have none.
+fn url_host(url: &str) -> Option<&str> {
+    if let Some((scheme, rest)) = url.split_once("://") {
+        if scheme == "file" {
+            return None;
+        }
+        let authority = rest.split('/').next()?;
+        let host = authority.rsplit('@').next()?;
+        // [::1]:22 or host:22
+        let host = match host.strip_prefix('[') {
+            Some(bracketed) => bracketed.split(']').next()?,
+            None => host.split(':').next()?,
+        };
+        return (!host.is_empty()).then_some(host);
+    }
+
+    // A colon before any slash is scp syntax; otherwise it's a path, which
+    // on Windows may start with a drive letter
+    let (before, _) = url.split_once(':')?;
+    if before.contains('/') || before.contains('\\') || before.len() == 1 {
+        return None;
+    }
+    let host = before.rsplit('@').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosts_of_git_urls() {
+        let cases = [
+            ("https://github.com/grievouz/git-wt.git", Some("github.com")),
+            (
+                "ssh://git@git.corp.example:2222/team/repo",
+                Some("git.corp.example"),
+            ),
+            ("ssh://git@[::1]:22/repo", Some("::1")),
+            ("git@github.com:grievouz/git-wt.git", Some("github.com")),
+            ("host:repo", Some("host")),
+            ("/srv/git/repo.git", None),
+            ("./a:b", None),
+            ("C:\\repos\\app", None),
+            ("file:///srv/git/repo.git", None),
+        ];
+        for (url, host) in cases {
+            assert_eq!(url_host(url), host, "{url}");
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! `--timings`: how long each subprocess took, reported at the end of a run.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+struct Timings {
+    started: Instant,
+    entries: Vec<(String, Duration)>,
+}
+
+static TIMINGS: OnceLock<Mutex<Timings>> = OnceLock::new();
+
+/// Starts recording; before this, [`measure`] only runs its closure.
+pub fn enable() {
+    let _ = TIMINGS.set(Mutex::new(Timings {
+        started: Instant::now(),
+        entries: Vec::new(),
+    }));
+}
+
+/// Runs `f`, recording how long it took under `label` when enabled.
+pub fn measure<T>(label: impl FnOnce() -> String, f: impl FnOnce() -> T) -> T {
+    let Some(timings) = TIMINGS.get() else {
+        return f();
+    };
+
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    timings
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .entries
+        .push((label(), elapsed));
+    result
+}
+
+/// Prints every recorded step, the slowest ones marked, and the totals.
+pub fn report() {
+    let Some(timings) = TIMINGS.get() else {
+        return;
+    };
+    let timings = timings
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    let total = timings.started.elapsed();
+    let in_subprocesses: Duration = timings.entries.iter().map(|(_, elapsed)| *elapsed).sum();
+
+    eprintln!("Timings:");
+    for (label, elapsed) in &timings.entries {
+        // Flag anything taking a fifth of the run or more
+        let marker = if *elapsed * 5 >= total { "!" } else { " " };
+        eprintln!(
+            "{marker} {:>8.1}ms  {label}",
+            elapsed.as_secs_f64() * 1000.0
+        );
+    }
+    eprintln!(
+        "  {:>8.1}ms  in {} subprocess(es)",
+        in_subprocesses.as_secs_f64() * 1000.0,
+        timings.entries.len()
+    );
+    eprintln!("  {:>8.1}ms  total", total.as_secs_f64() * 1000.0);
+}
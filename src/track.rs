@@ -0,0 +1,139 @@
+//! Fetching only some branches of huge repositories.
+//!
+//! With `fetch_branches` configured, `clone` sets up the bare repository
+//! with one fetch refspec per pattern instead of cloning every branch, so
+//! later fetches only transfer those. `git-wt track <pattern>` widens the
+//! set afterwards and fetches what it adds.
+
+use crate::{default_remote, git_stdout, log_info, offline, run_command, run_command_output};
+use color_eyre::Result;
+use std::path::Path;
+
+/// The refspec fetching the branches `pattern` matches from `remote` into
+/// its remote-tracking branches. `pattern` is a branch name or glob, and may
+/// be given as a full `refs/heads/...` refspec source.
+fn refspec(remote: &str, pattern: &str) -> String {
+    let pattern = pattern.trim_start_matches('+');
+    let pattern = pattern.strip_prefix("refs/heads/").unwrap_or(pattern);
+    format!("+refs/heads/{pattern}:refs/remotes/{remote}/{pattern}")
+}
+
+/// Creates the bare repository `.bare` in `dir` with `url` as origin,
+/// fetching only the branches matching `patterns`.
+pub fn init_narrowed(url: &str, patterns: &[String], dir: &Path) -> Result<()> {
+    run_command("git", &["init", "--quiet", "--bare", ".bare"], Some(dir))?;
+    let bare = dir.join(".bare");
+    run_command("git", &["config", "remote.origin.url", url], Some(&bare))?;
+    for pattern in patterns {
+        run_command(
+            "git",
+            &[
+                "config",
+                "--add",
+                "remote.origin.fetch",
+                &refspec("origin", pattern),
+            ],
+            Some(&bare),
+        )?;
+    }
+    log_info(&format!("Fetching only {}", patterns.join(", ")));
+    Ok(())
+}
+
+/// Points the bare repository's `HEAD` at the default branch of `remote`, as
+/// `clone --bare` would, once a narrowed clone has fetched.
+pub fn set_default_head(dir: &Path, remote: &str) -> Result<()> {
+    // Asks the remote; without an answer HEAD stays at git's default. Stderr
+    // stays on the terminal for credential prompts
+    let (status, _) =
+        run_command_output("git", &["remote", "set-head", remote, "--auto"], Some(dir))?;
+    if !status.success() {
+        return Ok(());
+    }
+    if let Some(default) = git_stdout(
+        &[
+            "symbolic-ref",
+            "--short",
+            &format!("refs/remotes/{remote}/HEAD"),
+        ],
+        Some(dir),
+    ) {
+        let branch = default
+            .strip_prefix(&format!("{remote}/"))
+            .unwrap_or(&default);
+        run_command(
+            "git",
+            &["symbolic-ref", "HEAD", &format!("refs/heads/{branch}")],
+            Some(dir),
+        )?;
+    }
+    Ok(())
+}
+
+/// Adds the branches matching `pattern` to what is fetched from the default
+/// remote, and fetches them.
+pub fn run(pattern: &str) -> Result<()> {
+    let remote = default_remote();
+    let key = format!("remote.{remote}.fetch");
+    let refspec = refspec(&remote, pattern);
+    let existing = git_stdout(&["config", "--get-all", &key], None).unwrap_or_default();
+
+    if existing.lines().any(|line| line == refspec) {
+        log_info(&format!("Already fetching {pattern} from {remote}."));
+    } else if existing
+        .lines()
+        .any(|line| line == format!("+refs/heads/*:refs/remotes/{remote}/*"))
+    {
+        log_info(&format!("Already fetching every branch from {remote}."));
+        return Ok(());
+    } else {
+        run_command("git", &["config", "--add", &key, &refspec], None)?;
+        log_info(&format!("Now fetching {pattern} from {remote}."));
+    }
+
+    offline::run_git(&["fetch", &remote, &refspec], None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{self, Recorder};
+    use std::rc::Rc;
+
+    #[test]
+    fn narrowed_clone_fetches_only_the_patterns() {
+        let recorder = Rc::new(Recorder::default());
+        let patterns = ["main".to_string(), "refs/heads/release/*".to_string()];
+        runner::with(recorder.clone(), || {
+            init_narrowed("https://example.com/repo.git", &patterns, Path::new("/r")).unwrap();
+        });
+
+        assert_eq!(
+            recorder.invocations(),
+            [
+                "/r: git init --quiet --bare .bare",
+                "/r/.bare: git config remote.origin.url https://example.com/repo.git",
+                "/r/.bare: git config --add remote.origin.fetch +refs/heads/main:refs/remotes/origin/main",
+                "/r/.bare: git config --add remote.origin.fetch +refs/heads/release/*:refs/remotes/origin/release/*",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_head_follows_the_given_remote() {
+        let recorder = Rc::new(Recorder::default());
+        recorder.respond("/r: git symbolic-ref", 0, "upstream/trunk");
+        runner::with(recorder.clone(), || {
+            set_default_head(Path::new("/r"), "upstream").unwrap();
+        });
+
+        assert_eq!(
+            recorder.invocations(),
+            [
+                "/r: git remote set-head upstream --auto",
+                "/r: git symbolic-ref --short refs/remotes/upstream/HEAD",
+                "/r: git symbolic-ref HEAD refs/heads/trunk",
+            ]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! `self-update`: replaces the binary with the latest GitHub release.
+//!
+//! Downloads go through `curl`, archives are unpacked with `tar` and checked
+//! against the `.sha256` file published next to each release asset.
+
+use crate::{log_error, log_info, run_command, run_command_output, run_command_status};
+use color_eyre::Result;
+use color_eyre::eyre::{Context, ContextCompat, bail};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/grievouz/git-wt/releases/latest";
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Checks for a newer release and, unless `check_only`, installs it over the
+/// running binary.
+pub fn run(check_only: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let (status, body) = run_command_output(
+        "curl",
+        &[
+            "-fsSL",
+            "-H",
+            "Accept: application/vnd.github+json",
+            LATEST_RELEASE_URL,
+        ],
+        None,
+    )?;
+    if !status.success() {
+        log_error("Failed to look up the latest release");
+        process::exit(1);
+    }
+    let release: Release =
+        serde_json::from_str(&body).context("Unexpected response from GitHub")?;
+    let latest = release.tag_name.trim_start_matches('v');
+
+    if parse_version(latest) <= parse_version(current) {
+        log_info(&format!("git-wt {current} is up to date."));
+        return Ok(());
+    }
+    if check_only {
+        log_info(&format!(
+            "git-wt {latest} is available (installed: {current})."
+        ));
+        return Ok(());
+    }
+
+    let target = target_triple().context("No release builds exist for this platform")?;
+    let archive_name = format!("git-wt-{target}.tar.gz");
+    let find_asset = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .map(|asset| asset.browser_download_url.as_str())
+            .with_context(|| format!("Release {latest} has no {name}"))
+    };
+    let archive_url = find_asset(&archive_name)?;
+    let checksum_url = find_asset(&format!("{archive_name}.sha256"))?;
+
+    let work_dir = env::temp_dir().join(format!("git-wt-update-{}", process::id()));
+    fs::create_dir_all(&work_dir)?;
+    let installed = install(&work_dir, &archive_name, archive_url, checksum_url);
+    let _ = fs::remove_dir_all(&work_dir);
+    installed?;
+
+    log_info(&format!("Updated git-wt {current} -> {latest}."));
+    Ok(())
+}
+
+fn install(
+    work_dir: &Path,
+    archive_name: &str,
+    archive_url: &str,
+    checksum_url: &str,
+) -> Result<()> {
+    let archive = work_dir.join(archive_name);
+    log_info(&format!("Downloading {archive_name}..."));
+    download(archive_url, &archive)?;
+
+    let (status, expected) = run_command_output("curl", &["-fsSL", checksum_url], None)?;
+    if !status.success() {
+        bail!("Failed to download the checksum for {archive_name}");
+    }
+    let expected = expected
+        .split_whitespace()
+        .next()
+        .context("Empty checksum file")?;
+    let actual = sha256(&archive)?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("Checksum mismatch for {archive_name}: expected {expected}, got {actual}");
+    }
+
+    run_command("tar", &["-xzf", &archive.to_string_lossy()], Some(work_dir))?;
+    let binary = work_dir.join(format!("git-wt{}", env::consts::EXE_SUFFIX));
+
+    // Stage next to the current binary so the final rename stays on one
+    // filesystem and is atomic
+    let current_exe = env::current_exe()?.canonicalize()?;
+    let staged = current_exe.with_extension("new");
+    fs::copy(&binary, &staged).context("Failed to stage the new binary")?;
+
+    // A running executable can't be replaced on Windows, but it can be moved
+    if cfg!(windows) {
+        let old = current_exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(&current_exe, &old).context("Failed to move the old binary aside")?;
+    }
+    fs::rename(&staged, &current_exe).context("Failed to replace the binary")?;
+
+    Ok(())
+}
+
+fn download(url: &str, destination: &Path) -> Result<()> {
+    let status = run_command_status(
+        "curl",
+        &["-fsSL", "-o", &destination.to_string_lossy(), url],
+        None,
+    )?;
+    if !status.success() {
+        bail!("Failed to download {url}");
+    }
+    Ok(())
+}
+
+fn sha256(path: &Path) -> Result<String> {
+    let path = path.to_string_lossy();
+    for (cmd, args) in [
+        ("sha256sum", vec![path.as_ref()]),
+        ("shasum", vec!["-a", "256", path.as_ref()]),
+    ] {
+        if let Ok((status, output)) = run_command_output(cmd, &args, None)
+            && status.success()
+            && let Some(hash) = output.split_whitespace().next()
+        {
+            return Ok(hash.to_string());
+        }
+    }
+    bail!("Neither sha256sum nor shasum is available to verify the download")
+}
+
+/// The target triple release archives are named after.
+fn target_triple() -> Option<String> {
+    let os = match env::consts::OS {
+        "linux" if cfg!(target_env = "musl") => "unknown-linux-musl",
+        "linux" => "unknown-linux-gnu",
+        "macos" => "apple-darwin",
+        "windows" => "pc-windows-msvc",
+        "freebsd" => "unknown-freebsd",
+        _ => return None,
+    };
+    Some(format!("{}-{os}", env::consts::ARCH))
+}
+
+/// Numeric `major.minor.patch` parts for comparison; anything after a `-` is
+/// ignored.
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split('-')
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
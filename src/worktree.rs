@@ -0,0 +1,115 @@
+//! Worktrees as `git worktree list --porcelain` describes them.
+
+use crate::runner::{self, Capture};
+use crate::{git_common_dir, same_path};
+use color_eyre::Result;
+use color_eyre::eyre::Context;
+use std::path::Path;
+
+#[derive(Clone, Debug, Default)]
+pub struct Worktree {
+    pub path: String,
+    /// Commit checked out, `None` before the first commit
+    pub head: Option<String>,
+    /// Branch checked out, without `refs/heads/`; `None` when detached
+    pub branch: Option<String>,
+    /// Why the worktree is locked against pruning, moving and removal (empty
+    /// if no reason was given)
+    pub locked: Option<String>,
+    /// Why `git worktree prune` would remove the worktree, usually because its
+    /// directory is gone
+    pub prunable: Option<String>,
+}
+
+impl Worktree {
+    /// The branch, or for a detached worktree its short commit, e.g.
+    /// "(detached at 1a2b3c4)".
+    pub fn name(&self) -> String {
+        match (&self.branch, &self.head) {
+            (Some(branch), _) => branch.clone(),
+            (None, Some(head)) => format!("(detached at {})", &head[..head.len().min(7)]),
+            (None, None) => "(detached)".to_string(),
+        }
+    }
+}
+
+/// Every worktree but the bare repository itself, in git's order.
+pub fn list() -> Result<Vec<Worktree>> {
+    let output = runner::run(
+        "git",
+        &["worktree", "list", "--porcelain"],
+        None,
+        Capture::StdoutAndStderr,
+    )
+    .context("Failed to execute git worktree list")?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    // With per-worktree config enabled git may list the bare repository
+    // itself as a worktree, without a `bare` line
+    let bare_dir = git_common_dir();
+    Ok(parse(&String::from_utf8_lossy(&output.stdout))
+        .into_iter()
+        .filter(|worktree| {
+            !bare_dir
+                .as_deref()
+                .is_some_and(|bare| same_path(Path::new(&worktree.path), bare))
+        })
+        .collect())
+}
+
+/// The worktree `dir` is in, if any. The innermost one wins where worktrees
+/// are nested, e.g. inside a standard clone's main checkout.
+pub fn containing(dir: &Path) -> Result<Option<Worktree>> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    Ok(list()?
+        .into_iter()
+        .filter_map(|worktree| {
+            let path = Path::new(&worktree.path);
+            let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            dir.starts_with(&path)
+                .then(|| (path.components().count(), worktree))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, worktree)| worktree))
+}
+
+/// Parses porcelain output: records of `key value` lines separated by blank
+/// lines, leaving out bare repositories.
+fn parse(output: &str) -> Vec<Worktree> {
+    let mut worktrees = Vec::new();
+    let mut current: Option<Worktree> = None;
+    let mut bare = false;
+
+    for line in output.lines().chain([""]) {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match (key, current.as_mut()) {
+            ("", _) => {
+                if let Some(worktree) = current.take()
+                    && !bare
+                {
+                    worktrees.push(worktree);
+                }
+                bare = false;
+            }
+            ("worktree", _) => {
+                current = Some(Worktree {
+                    path: value.to_string(),
+                    ..Worktree::default()
+                });
+            }
+            ("HEAD", Some(worktree)) => worktree.head = Some(value.to_string()),
+            ("branch", Some(worktree)) => {
+                worktree.branch = Some(value.trim_start_matches("refs/heads/").to_string());
+            }
+            ("locked", Some(worktree)) => worktree.locked = Some(value.to_string()),
+            ("prunable", Some(worktree)) => worktree.prunable = Some(value.to_string()),
+            ("bare", _) => bare = true,
+            _ => {}
+        }
+    }
+
+    worktrees
+}
@@ -0,0 +1,57 @@
+//! Paths for a shell on the other side of the WSL boundary.
+//!
+//! A Windows shell can run git-wt inside WSL (`wsl git-wt ...`), and a WSL
+//! shell can run the Windows build; either way the directory git-wt asks to
+//! switch to is a path the shell can't use. With `wsl_paths` set, `wslpath`
+//! translates it. Translation only happens where it applies, inside WSL for
+//! `windows` and on Windows for `unix`, so the setting can live in a config
+//! file both sides read.
+
+use crate::config::{self, WslPaths};
+use crate::log_error;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// `path` as the shell integration on the other side needs it.
+pub fn translate(path: &Path) -> PathBuf {
+    let flag = match config::get().wsl_paths.unwrap_or_default() {
+        WslPaths::Windows if in_wsl() => "-w",
+        WslPaths::Unix if cfg!(windows) => "-u",
+        WslPaths::Off | WslPaths::Windows | WslPaths::Unix => return path.to_path_buf(),
+    };
+
+    // From Windows, wslpath runs in the default distribution
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("wsl.exe");
+        command.args(["--exec", "wslpath"]);
+        command
+    } else {
+        Command::new("wslpath")
+    };
+    match command.arg(flag).arg(path).output() {
+        Ok(output) if output.status.success() => {
+            PathBuf::from(String::from_utf8_lossy(&output.stdout).trim_end())
+        }
+        Ok(output) => {
+            log_error(&format!(
+                "wslpath couldn't translate {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+            path.to_path_buf()
+        }
+        Err(e) => {
+            log_error(&format!("Failed to run wslpath: {e}"));
+            path.to_path_buf()
+        }
+    }
+}
+
+/// Whether this is a Linux running under WSL.
+fn in_wsl() -> bool {
+    env::var_os("WSL_DISTRO_NAME").is_some()
+        || fs::read_to_string("/proc/sys/kernel/osrelease")
+            .is_ok_and(|release| release.to_lowercase().contains("microsoft"))
+}
@@ -0,0 +1,161 @@
+//! `git-wt add` against real repositories.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+#[test]
+fn creates_branch_and_worktree_from_head() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature/login"]).success();
+
+    let path = repo.worktree("feature/login");
+    assert!(path.join("README.md").exists());
+    assert_eq!(
+        repo.git(&path, &["rev-parse", "--abbrev-ref", "HEAD"]),
+        "feature/login"
+    );
+    assert_eq!(
+        repo.git(&path, &["rev-parse", "HEAD"]),
+        repo.git(&repo.worktree("main"), &["rev-parse", "HEAD"])
+    );
+}
+
+#[test]
+fn starts_from_the_given_base() {
+    let repo = Repo::new();
+    let base = repo.git(&repo.worktree("main"), &["rev-parse", "HEAD"]);
+    repo.commit("main", "later.txt", "Later commit");
+
+    repo.wt_main(&["add", "hotfix", "--from", &base]).success();
+
+    let path = repo.worktree("hotfix");
+    assert_eq!(repo.git(&path, &["rev-parse", "HEAD"]), base);
+    assert!(!path.join("later.txt").exists());
+}
+
+#[test]
+fn checks_out_an_existing_branch() {
+    let repo = Repo::new();
+    repo.git(&repo.root, &["branch", "existing", "main"]);
+
+    repo.wt_main(&["add", "existing"]).success();
+
+    assert_eq!(
+        repo.git(
+            &repo.worktree("existing"),
+            &["rev-parse", "--abbrev-ref", "HEAD"]
+        ),
+        "existing"
+    );
+}
+
+#[test]
+fn tracks_a_remote_branch_of_the_same_name() {
+    let repo = Repo::new();
+    repo.git(
+        &repo.worktree("main"),
+        &["push", "--quiet", "origin", "main:remote-only"],
+    );
+    repo.git(&repo.root, &["fetch", "--quiet", "origin"]);
+
+    repo.wt_main(&["add", "remote-only"]).success();
+
+    assert_eq!(
+        repo.git(
+            &repo.worktree("remote-only"),
+            &["rev-parse", "--abbrev-ref", "@{upstream}"]
+        ),
+        "origin/remote-only"
+    );
+}
+
+#[test]
+fn refuses_a_branch_that_already_has_a_worktree() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "twice"]).success();
+
+    let run = repo.wt_main(&["add", "twice"]).failure();
+    assert_eq!(run.cd(), None);
+}
+
+#[test]
+fn starts_from_the_merge_base_of_two_refs() {
+    let repo = Repo::new();
+    let fork = repo.git(&repo.worktree("main"), &["rev-parse", "HEAD"]);
+    repo.wt_main(&["add", "topic"]).success();
+    repo.commit("topic", "topic.txt", "Topic commit");
+    repo.commit("main", "later.txt", "Later commit");
+
+    repo.wt_main(&["add", "before-topic", "--merge-base", "main", "topic"])
+        .success();
+
+    let path = repo.worktree("before-topic");
+    assert_eq!(repo.git(&path, &["rev-parse", "HEAD"]), fork);
+    assert!(!path.join("topic.txt").exists());
+    assert!(!path.join("later.txt").exists());
+}
+
+#[test]
+fn refuses_a_merge_base_of_unknown_refs() {
+    let repo = Repo::new();
+
+    repo.wt_main(&["add", "nowhere", "--merge-base", "main", "no-such-ref"])
+        .failure();
+    assert!(!repo.has_branch("nowhere"));
+}
+
+#[test]
+fn hooks_get_the_context_on_stdin() {
+    let repo = Repo::new();
+
+    // One hook reads the JSON, the other leaves stdin alone
+    repo.wt_main(&[
+        "add",
+        "hooked",
+        "--before",
+        "cat > payload.json",
+        "--after",
+        "true",
+    ])
+    .success();
+
+    assert!(repo.root.join("payload.json").exists());
+    assert!(repo.worktree("hooked").exists());
+}
+
+#[test]
+fn bare_repository_named_dot_git_keeps_worktrees_in_the_root() {
+    let repo = Repo::new();
+    let root = repo.root.with_file_name("dot-git");
+    fs::create_dir(&root).unwrap();
+    repo.git(&root, &["clone", "--quiet", "--bare", "../origin", ".git"]);
+    repo.git(&root, &["worktree", "add", "--quiet", "main", "main"]);
+
+    repo.wt(&root.join("main"), &["add", "feature"]).success();
+
+    assert!(root.join("feature").join("README.md").exists());
+}
+
+#[test]
+fn concurrent_adds_get_distinct_indexes() {
+    let repo = Repo::new();
+    let branches = ["one", "two", "three", "four", "five", "six"];
+
+    std::thread::scope(|scope| {
+        for branch in branches {
+            let repo = &repo;
+            scope.spawn(move || repo.wt_main(&["add", branch]).success());
+        }
+    });
+
+    let registry = fs::read_to_string(repo.root.join(".bare/git-wt/indexes.json")).unwrap();
+    let mut indexes: Vec<&str> = registry
+        .lines()
+        .filter_map(|line| line.trim().trim_end_matches(',').rsplit_once(": "))
+        .map(|(_, index)| index)
+        .collect();
+    indexes.sort_unstable();
+    assert_eq!(indexes, ["1", "2", "3", "4", "5", "6"], "{registry}");
+}
@@ -0,0 +1,43 @@
+//! `git-wt backup` against real repositories.
+
+mod common;
+
+use common::Repo;
+
+#[test]
+fn pushes_under_the_users_prefix_by_default() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "topic"]).success();
+    repo.commit("topic", "topic.txt", "Topic commit");
+    let main = repo.worktree("main");
+
+    repo.wt_with_env(&main, &[("USER", "alice")], &["backup"])
+        .success();
+
+    let origin = repo.root.with_file_name("origin");
+    assert_eq!(
+        repo.git(&origin, &["rev-parse", "backup/alice/topic"]),
+        repo.git(&repo.worktree("topic"), &["rev-parse", "HEAD"])
+    );
+    assert!(
+        repo.git(&origin, &["branch", "--list", "topic"]).is_empty(),
+        "backup pushed to the branch itself"
+    );
+}
+
+#[test]
+fn an_explicit_prefix_is_used_as_given() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "topic"]).success();
+
+    repo.wt_main(&["backup", "--prefix", "saved/"]).success();
+
+    let origin = repo.root.with_file_name("origin");
+    assert_eq!(
+        repo.git(
+            &origin,
+            &["branch", "--list", "--format=%(refname)", "saved/*"]
+        ),
+        "refs/heads/saved/main\nrefs/heads/saved/topic"
+    );
+}
@@ -0,0 +1,64 @@
+//! `git-wt clean` against real repositories.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+#[test]
+fn removes_worktrees_whose_upstream_is_gone() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "merged"]).success();
+    repo.wt_main(&["add", "kept"]).success();
+    for branch in ["merged", "kept"] {
+        repo.git(
+            &repo.worktree(branch),
+            &["push", "--quiet", "-u", "origin", branch],
+        );
+    }
+    repo.git(
+        &repo.worktree("main"),
+        &["push", "--quiet", "origin", "--delete", "merged"],
+    );
+
+    let run = repo.wt_main(&["clean", "--yes"]).success();
+
+    assert!(run.stderr().contains("1 removed"), "{}", run.stderr());
+    assert!(!repo.worktree("merged").exists());
+    assert!(repo.worktree("kept").exists());
+    assert!(repo.worktree("main").exists());
+}
+
+#[test]
+fn keeps_gone_worktrees_with_changes_unless_forced() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "dirty"]).success();
+    let path = repo.worktree("dirty");
+    repo.git(&path, &["push", "--quiet", "-u", "origin", "dirty"]);
+    repo.git(&path, &["push", "--quiet", "origin", "--delete", "dirty"]);
+    fs::write(path.join("README.md"), "changed\n").unwrap();
+
+    repo.wt_main(&["clean", "--yes"]);
+    assert!(path.exists());
+
+    repo.wt_main(&["clean", "--yes", "--force"]).success();
+    assert!(!path.exists());
+}
+
+#[test]
+fn keeps_branches_that_were_never_pushed() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "unpushed"]).success();
+
+    repo.wt_main(&["clean", "--yes"]).success();
+
+    assert!(repo.worktree("unpushed").exists());
+    assert!(repo.has_branch("unpushed"));
+
+    let path = repo.worktree("unpushed");
+    repo.git(&path, &["push", "--quiet"]);
+    assert_eq!(
+        repo.git(&path, &["rev-parse", "--abbrev-ref", "@{upstream}"]),
+        "origin/unpushed"
+    );
+}
@@ -0,0 +1,204 @@
+//! Scripted git fixtures for the integration tests.
+//!
+//! A [`Repo`] is a fresh `.bare` layout in a temporary directory, with a
+//! `main` worktree and an `origin` to push to and fetch from. git-wt runs
+//! with `--test-mode`, and both it and git get an empty home, so neither the
+//! machine's git config nor its git-wt config leaks in.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Repo {
+    /// Holds the repository and everything else the test creates
+    dir: PathBuf,
+    /// The worktree root, containing `.bare` and the worktrees
+    pub root: PathBuf,
+}
+
+impl Repo {
+    /// A repository with one commit on `main`, tracking `origin/main`.
+    pub fn new() -> Repo {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let dir = env::temp_dir().join(format!("git-wt-test-{}-{id}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("home")).unwrap();
+        let root = dir.join("repo");
+        fs::create_dir_all(&root).unwrap();
+        let repo = Repo { dir, root };
+
+        // The first commit is made in a scratch clone, as the `.bare` layout
+        // can't check out an unborn branch on older git
+        let seed = repo.dir.join("seed");
+        repo.git(&repo.dir, &["init", "--quiet", "--bare", "origin"]);
+        repo.git(&repo.dir, &["init", "--quiet", "seed"]);
+        repo.git(&seed, &["checkout", "--quiet", "-b", "main"]);
+        fs::write(seed.join("README.md"), "Initial commit\n").unwrap();
+        repo.git(&seed, &["add", "README.md"]);
+        repo.git(&seed, &["commit", "--quiet", "-m", "Initial commit"]);
+        repo.git(&seed, &["push", "--quiet", "../origin", "main"]);
+        fs::remove_dir_all(&seed).unwrap();
+
+        repo.git(
+            &repo.root,
+            &["clone", "--quiet", "--bare", "../origin", ".bare"],
+        );
+        fs::write(repo.root.join(".git"), "gitdir: ./.bare\n").unwrap();
+        repo.git(
+            &repo.root,
+            &[
+                "config",
+                "remote.origin.fetch",
+                "+refs/heads/*:refs/remotes/origin/*",
+            ],
+        );
+        repo.git(&repo.root, &["fetch", "--quiet", "origin"]);
+        repo.git(&repo.root, &["worktree", "add", "--quiet", "main", "main"]);
+        repo.git(
+            &repo.worktree("main"),
+            &["branch", "--quiet", "--set-upstream-to", "origin/main"],
+        );
+        repo
+    }
+
+    pub fn worktree(&self, branch: &str) -> PathBuf {
+        self.root.join(branch)
+    }
+
+    /// Writes `file` in the worktree of `branch` and commits it.
+    pub fn commit(&self, branch: &str, file: &str, message: &str) {
+        let worktree = self.worktree(branch);
+        fs::write(worktree.join(file), format!("{message}\n")).unwrap();
+        self.git(&worktree, &["add", file]);
+        self.git(&worktree, &["commit", "--quiet", "-m", message]);
+    }
+
+    /// Runs git in `dir`, panicking if it fails, and returns its stdout.
+    pub fn git(&self, dir: &Path, args: &[&str]) -> String {
+        let output = self
+            .command("git", dir)
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    /// Whether `branch` exists locally.
+    pub fn has_branch(&self, branch: &str) -> bool {
+        self.command("git", &self.root)
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("refs/heads/{branch}"))
+            .stdout(Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    }
+
+    /// Runs git-wt in `dir`.
+    pub fn wt(&self, dir: &Path, args: &[&str]) -> Run {
+        self.wt_with_env(dir, &[], args)
+    }
+
+    /// Runs git-wt in `dir` with extra environment variables, e.g. those git
+    /// sets for hooks.
+    pub fn wt_with_env(&self, dir: &Path, vars: &[(&str, &str)], args: &[&str]) -> Run {
+        let output = self
+            .command(env!("CARGO_BIN_EXE_git-wt"), dir)
+            .envs(vars.iter().copied())
+            .arg("--test-mode")
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        Run { output }
+    }
+
+    /// Runs git-wt in the `main` worktree.
+    pub fn wt_main(&self, args: &[&str]) -> Run {
+        self.wt(&self.worktree("main"), args)
+    }
+
+    fn command(&self, program: &str, dir: &Path) -> Command {
+        let home = self.dir.join("home");
+        let mut command = Command::new(program);
+        command
+            .current_dir(dir)
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CONFIG_GLOBAL", home.join(".gitconfig"))
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_WORK_TREE")
+            .env_remove("GIT_WT_ROOT")
+            .env_remove("GIT_WT_CD_FILE");
+        command
+    }
+}
+
+impl Drop for Repo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The outcome of a git-wt run.
+pub struct Run {
+    pub output: Output,
+}
+
+impl Run {
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.output.stdout).into_owned()
+    }
+
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.output.stderr).into_owned()
+    }
+
+    /// Asserts git-wt succeeded and returns itself for further checks.
+    #[track_caller]
+    pub fn success(self) -> Run {
+        assert!(
+            self.output.status.success(),
+            "git-wt failed:\n{}",
+            self.stderr()
+        );
+        self
+    }
+
+    /// Asserts git-wt failed and returns itself for further checks.
+    #[track_caller]
+    pub fn failure(self) -> Run {
+        assert!(
+            !self.output.status.success(),
+            "git-wt unexpectedly succeeded:\n{}",
+            self.stderr()
+        );
+        self
+    }
+
+    /// The directory git-wt asked the shell to change into, if any.
+    pub fn cd(&self) -> Option<PathBuf> {
+        self.stdout()
+            .lines()
+            .find_map(|line| line.strip_prefix("CD:"))
+            .map(PathBuf::from)
+    }
+}
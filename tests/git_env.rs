@@ -0,0 +1,60 @@
+//! git-wt called with variables git sets for hooks and aliases.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+/// What git exports for hooks running in the `main` worktree.
+const HOOK_ENV: [(&str, &str); 2] = [
+    ("GIT_DIR", "../.bare/worktrees/main"),
+    ("GIT_WORK_TREE", "."),
+];
+
+#[test]
+fn other_worktrees_are_inspected_in_their_own_directory() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "edited"]).success();
+    repo.wt_main(&["add", "untouched"]).success();
+    fs::write(repo.worktree("edited").join("README.md"), "changed\n").unwrap();
+
+    let run = repo
+        .wt_with_env(&repo.worktree("main"), &HOOK_ENV, &["list", "--dirty"])
+        .success();
+
+    assert!(run.stdout().contains("edited"), "{}", run.stdout());
+    assert!(!run.stdout().contains("untouched"), "{}", run.stdout());
+}
+
+#[test]
+fn current_and_resolve_use_the_callers_repository() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature"]).success();
+
+    let run = repo
+        .wt_with_env(&repo.worktree("main"), &HOOK_ENV, &["current"])
+        .success();
+    assert_eq!(run.stdout(), "main\n");
+
+    let run = repo
+        .wt_with_env(&repo.worktree("main"), &HOOK_ENV, &["resolve", "feature"])
+        .success();
+    assert_eq!(
+        run.stdout(),
+        format!("{}\n", repo.worktree("feature").display())
+    );
+}
+
+#[test]
+fn aliases_that_change_to_git_prefix_themselves_work() {
+    let repo = Repo::new();
+    let sub = repo.worktree("main").join("sub");
+    fs::create_dir(&sub).unwrap();
+
+    // alias.w = !cd "${GIT_PREFIX:-.}" && git-wt list, run from sub/
+    let run = repo
+        .wt_with_env(&sub, &[("GIT_PREFIX", "sub/")], &["list"])
+        .success();
+
+    assert!(run.stdout().contains("main"), "{}", run.stdout());
+}
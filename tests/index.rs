@@ -0,0 +1,51 @@
+//! Commands that read uncommitted changes through a temporary index must
+//! leave the worktree's own index alone.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+/// A worktree `changed` with a modified and an untracked file, nothing staged.
+fn repo_with_changes() -> Repo {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "changed"]).success();
+    let worktree = repo.worktree("changed");
+    fs::write(worktree.join("README.md"), "modified\n").unwrap();
+    fs::write(worktree.join("new.txt"), "untracked\n").unwrap();
+    repo
+}
+
+/// What is staged in `changed`, and the status of its files (trimmed).
+fn staged(repo: &Repo) -> (String, String) {
+    let worktree = repo.worktree("changed");
+    (
+        repo.git(&worktree, &["diff", "--cached", "--name-only"]),
+        repo.git(&worktree, &["status", "--porcelain"]),
+    )
+}
+
+#[test]
+fn compare_leaves_the_index_alone() {
+    let repo = repo_with_changes();
+
+    let run = repo.wt_main(&["compare", "main", "changed"]).success();
+
+    assert!(run.stdout().contains("README.md"), "{}", run.stdout());
+    assert_eq!(
+        staged(&repo),
+        (String::new(), "M README.md\n?? new.txt".to_string())
+    );
+}
+
+#[test]
+fn snapshot_leaves_the_index_alone() {
+    let repo = repo_with_changes();
+
+    repo.wt(&repo.worktree("changed"), &["snapshot"]).success();
+
+    assert_eq!(
+        staged(&repo),
+        (String::new(), "M README.md\n?? new.txt".to_string())
+    );
+}
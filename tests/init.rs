@@ -0,0 +1,21 @@
+//! `git-wt init --write` for packaging the shell integration.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+#[test]
+fn writes_integration_and_man_page_under_a_prefix() {
+    let repo = Repo::new();
+    let prefix = repo.root.join("prefix");
+
+    repo.wt_main(&["init", "fish", "--write", prefix.to_str().unwrap()])
+        .success();
+
+    let integration =
+        fs::read_to_string(prefix.join("share/fish/vendor_conf.d/git-wt.fish")).unwrap();
+    assert!(integration.contains("function git-wt"));
+    let man = fs::read_to_string(prefix.join("share/man/man1/git-wt.1")).unwrap();
+    assert!(man.starts_with(".TH GIT-WT 1"));
+}
@@ -0,0 +1,51 @@
+//! `git-wt resolve` and `git-wt current` against real repositories.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+#[test]
+fn resolve_prints_the_path_of_the_exact_branch() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature/api"]).success();
+
+    let run = repo.wt_main(&["resolve", "feature/api"]).success();
+    assert_eq!(
+        run.stdout(),
+        format!("{}\n", repo.worktree("feature/api").display())
+    );
+}
+
+#[test]
+fn resolve_does_not_fuzzy_match_or_take_indexes() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature/api"]).success();
+
+    for name in ["api", "1"] {
+        let run = repo.wt_main(&["resolve", name]).failure();
+        assert_eq!(run.stdout(), "");
+    }
+}
+
+#[test]
+fn current_prints_the_branch_from_a_subdirectory() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature/api"]).success();
+    let subdirectory = repo.worktree("feature/api").join("src");
+    fs::create_dir_all(&subdirectory).unwrap();
+
+    let run = repo.wt(&subdirectory, &["current"]).success();
+    assert_eq!(run.stdout(), "feature/api\n");
+}
+
+#[test]
+fn current_fails_outside_a_worktree() {
+    let repo = Repo::new();
+
+    for dir in [repo.root.join(".bare"), std::env::temp_dir()] {
+        let run = repo.wt(&dir, &["current"]).failure();
+        assert_eq!(run.output.status.code(), Some(3));
+        assert_eq!(run.stdout(), "");
+    }
+}
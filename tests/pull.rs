@@ -0,0 +1,27 @@
+//! `git-wt pull --all` against real repositories.
+
+mod common;
+
+use common::Repo;
+
+#[test]
+fn pull_all_fetches_and_fast_forwards_every_worktree() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "topic"]).success();
+    let topic = repo.worktree("topic");
+    let before = repo.git(&topic, &["rev-parse", "HEAD"]);
+    repo.commit("topic", "topic.txt", "Topic commit");
+    repo.git(&topic, &["push", "--quiet", "-u", "origin", "topic"]);
+    let pushed = repo.git(&topic, &["rev-parse", "HEAD"]);
+    // Forget the commit locally, so only origin has it
+    repo.git(&topic, &["reset", "--quiet", "--hard", &before]);
+    repo.git(
+        &repo.root,
+        &["update-ref", "refs/remotes/origin/topic", &before],
+    );
+
+    let run = repo.wt_main(&["pull", "--all"]).success();
+
+    assert_eq!(repo.git(&topic, &["rev-parse", "HEAD"]), pushed);
+    assert!(run.stderr().contains("Fetching 2 branch(es) from origin"));
+}
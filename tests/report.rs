@@ -0,0 +1,31 @@
+//! `git-wt report` against real repositories.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+#[test]
+fn markdown_has_a_row_per_branch() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature/api"]).success();
+    repo.git(&repo.root, &["branch", "no-worktree", "main"]);
+    fs::write(repo.worktree("feature/api").join("README.md"), "changed\n").unwrap();
+
+    let run = repo.wt_main(&["report"]).success();
+    let stdout = run.stdout();
+    let mut lines = stdout.lines();
+    assert!(lines.next().unwrap().starts_with("| Branch | Worktree |"));
+    assert_eq!(stdout.lines().count(), 2 + 3);
+
+    let row = |branch: &str| {
+        stdout
+            .lines()
+            .find(|line| line.starts_with(&format!("| {branch} |")))
+            .unwrap()
+            .to_string()
+    };
+    assert!(row("feature/api").contains(&repo.worktree("feature/api").display().to_string()));
+    assert!(row("feature/api").contains("dirty"));
+    assert!(row("no-worktree").starts_with("| no-worktree | - | just now |"));
+}
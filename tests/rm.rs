@@ -0,0 +1,115 @@
+//! `git-wt rm` against real repositories.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+#[test]
+fn removes_worktree_but_keeps_branch() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "done"]).success();
+
+    repo.wt_main(&["rm", "done", "--yes"]).success();
+
+    assert!(!repo.worktree("done").exists());
+    assert!(repo.has_branch("done"));
+}
+
+#[test]
+fn refuses_uncommitted_changes_without_force() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "dirty"]).success();
+    fs::write(repo.worktree("dirty").join("README.md"), "changed\n").unwrap();
+
+    repo.wt_main(&["rm", "dirty", "--yes"]).failure();
+    assert!(repo.worktree("dirty").exists());
+
+    repo.wt_main(&["rm", "dirty", "--yes", "--force"]).success();
+    assert!(!repo.worktree("dirty").exists());
+}
+
+#[test]
+fn removes_the_current_worktree_by_default() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "current"]).success();
+
+    repo.wt(&repo.worktree("current"), &["rm", "--yes"])
+        .success();
+
+    assert!(!repo.worktree("current").exists());
+}
+
+#[test]
+fn asks_for_yes_without_a_terminal() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "kept"]).success();
+
+    let run = repo.wt_main(&["rm", "kept"]).failure();
+
+    assert!(run.stderr().contains("--yes"));
+    assert!(repo.worktree("kept").exists());
+}
+
+#[test]
+fn asks_only_about_unpushed_work_when_configured() {
+    let repo = Repo::new();
+    let env = [("GIT_WT_RM_CONFIRM", "unpushed-only")];
+    repo.wt_main(&["add", "pushed"]).success();
+    repo.wt_main(&["add", "unpushed"]).success();
+    repo.commit("unpushed", "new.txt", "Not pushed yet");
+
+    repo.wt_with_env(&repo.worktree("main"), &env, &["rm", "pushed"])
+        .success();
+    assert!(!repo.worktree("pushed").exists());
+
+    let run = repo
+        .wt_with_env(&repo.worktree("main"), &env, &["rm", "unpushed"])
+        .failure();
+    assert!(run.stderr().contains("--yes"));
+    assert!(repo.worktree("unpushed").exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn brings_the_compose_stack_down_only_once_removed() {
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    let repo = Repo::new();
+    repo.wt_main(&["add", "stack"]).success();
+    let path = repo.worktree("stack");
+    fs::write(path.join("README.md"), "changed\n").unwrap();
+
+    // A docker that records its arguments
+    let bin = repo.root.with_file_name("bin");
+    fs::create_dir(&bin).unwrap();
+    let calls = bin.join("calls");
+    let docker = bin.join("docker");
+    fs::write(
+        &docker,
+        format!("#!/bin/sh\necho \"$@\" >> '{}'\n", calls.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&docker, fs::Permissions::from_mode(0o755)).unwrap();
+    let path_var = format!("{}:{}", bin.display(), env::var("PATH").unwrap());
+    let vars = [
+        ("PATH", path_var.as_str()),
+        ("GIT_WT_COMPOSE_ENABLED", "true"),
+    ];
+
+    repo.wt_with_env(&repo.worktree("main"), &vars, &["rm", "stack", "--yes"])
+        .failure();
+    assert!(!calls.exists());
+
+    repo.wt_with_env(
+        &repo.worktree("main"),
+        &vars,
+        &["rm", "stack", "--yes", "--force"],
+    )
+    .success();
+    assert_eq!(
+        fs::read_to_string(&calls).unwrap(),
+        "compose -p stack down -v\n"
+    );
+}
@@ -0,0 +1,199 @@
+//! The shell integration's handling of git-wt's directives.
+//!
+//! Each test replaces `git-wt` with a stub writing fixed directives, sources the
+//! integration and checks what the shell did. Fish is only tested where it's
+//! installed.
+
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// A directory with a stub `git-wt` that writes the file named by
+/// `GIT_WT_STUB_OUTPUT` to its `--directive-file` and prints the one named by
+/// `GIT_WT_STUB_STDOUT`, if there is one.
+fn stub_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("git-wt-shell-{}-{name}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let stub = dir.join("git-wt");
+    fs::write(
+        &stub,
+        "#!/bin/sh\n\
+         [ \"$1\" = --directive-file ] && cat \"$GIT_WT_STUB_OUTPUT\" > \"$2\"\n\
+         [ -f \"$GIT_WT_STUB_STDOUT\" ] && cat \"$GIT_WT_STUB_STDOUT\"\n\
+         exit 0\n",
+    )
+    .unwrap();
+    fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+    dir
+}
+
+/// Runs `script` in `shell` with the integration sourced and the stub
+/// handing over the directives in `output`, returning stdout.
+fn run(shell: &str, dir: &Path, output: &str, script: &str) -> String {
+    let integration = Path::new(env!("CARGO_MANIFEST_DIR")).join(match shell {
+        "fish" => "shell/fish.fish",
+        _ => "shell/bash.sh",
+    });
+    let output_file = dir.join("output");
+    fs::write(&output_file, output).unwrap();
+    let path = format!("{}:{}", dir.display(), env::var("PATH").unwrap_or_default());
+
+    let result = Command::new(shell)
+        .arg("-c")
+        .arg(format!("source {}; {script}", integration.display()))
+        .env("PATH", path)
+        .env("GIT_WT_STUB_OUTPUT", &output_file)
+        .env("GIT_WT_STUB_STDOUT", dir.join("stdout"))
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        result.status.success(),
+        "{shell} failed: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    String::from_utf8(result.stdout).unwrap()
+}
+
+fn installed(shell: &str) -> bool {
+    Command::new(shell)
+        .args(["-c", "true"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn shells() -> Vec<&'static str> {
+    ["bash", "fish"]
+        .into_iter()
+        .filter(|shell| installed(shell))
+        .collect()
+}
+
+#[test]
+fn changes_directory_and_ignores_directives_on_stdout() {
+    for shell in shells() {
+        let dir = stub_dir(&format!("cd-{shell}"));
+        let target = dir.join("target dir");
+        fs::create_dir_all(&target).unwrap();
+        let pwned = dir.join("pwned");
+
+        // What e.g. `git-wt exec -- cat README` passes through
+        let stdout = format!(
+            "Created worktree\nCD:/\nENV:PATH=/nowhere\nEXEC:touch {}\n",
+            pwned.display()
+        );
+        fs::write(dir.join("stdout"), &stdout).unwrap();
+
+        let output = format!("CD:{}\n", target.display());
+        let printed = run(shell, &dir, &output, "git-wt; pwd");
+        assert_eq!(
+            printed,
+            format!("{stdout}{}\n", target.display()),
+            "{shell}"
+        );
+        assert!(!pwned.exists(), "{shell} ran a command from stdout");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
+
+#[test]
+fn bash_wraps_git_and_completes() {
+    if !installed("bash") {
+        return;
+    }
+    let dir = stub_dir("wrap-bash");
+    let target = dir.join("target");
+    fs::create_dir_all(&target).unwrap();
+
+    let output = format!("CD:{}\n", target.display());
+    let script = "type -t git; complete -p git-wt; git wt; pwd";
+    let printed = run("bash", &dir, &output, script);
+    assert_eq!(
+        printed,
+        format!(
+            "function\ncomplete -o default -F _git_wt_complete git-wt\n{}\n",
+            target.display()
+        )
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn exports_variables_and_unsets_them_on_the_next_switch() {
+    for shell in shells() {
+        let dir = stub_dir(&format!("env-{shell}"));
+        let first = dir.join("first");
+        let second = dir.join("second");
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        let first_output = format!(
+            "CD:{}\nENV:APP_ENV=dev\nENV:DATABASE_URL=postgres://localhost/a=b\n",
+            first.display()
+        );
+        let second_output = dir.join("second-output");
+        fs::write(
+            &second_output,
+            format!("CD:{}\nENV:APP_ENV=staging\n", second.display()),
+        )
+        .unwrap();
+
+        let script = format!(
+            "git-wt; echo \"$APP_ENV $DATABASE_URL\"; \
+             GIT_WT_STUB_OUTPUT={} git-wt; echo \"$APP_ENV [$DATABASE_URL]\"",
+            second_output.display()
+        );
+        let stdout = run(shell, &dir, &first_output, &script);
+        assert_eq!(
+            stdout, "dev postgres://localhost/a=b\nstaging []\n",
+            "{shell}"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
+
+#[test]
+fn unescapes_values() {
+    for shell in shells() {
+        let dir = stub_dir(&format!("escape-{shell}"));
+        let target = dir.join("back\\slash");
+        fs::create_dir_all(&target).unwrap();
+
+        let output = format!(
+            "CD:{}\nENV:VALUE=two\\nlines \\\\n\n",
+            target.display().to_string().replace('\\', "\\\\")
+        );
+        let stdout = run(shell, &dir, &output, "git-wt; pwd; printf '%s' \"$VALUE\"");
+        assert_eq!(
+            stdout,
+            format!("{}\ntwo\nlines \\n", target.display()),
+            "{shell}"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
+
+#[test]
+fn runs_commands_after_changing_directory() {
+    for shell in shells() {
+        let dir = stub_dir(&format!("exec-{shell}"));
+        let target = dir.join("target");
+        fs::create_dir_all(&target).unwrap();
+
+        let output = format!(
+            "EXEC:echo \"in $PWD\"\nCD:{}\nEXEC:echo done\n",
+            target.display()
+        );
+        let stdout = run(shell, &dir, &output, "git-wt");
+        assert_eq!(
+            stdout,
+            format!("in {}\ndone\n", target.display()),
+            "{shell}"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,34 @@
+//! `git-wt snapshot` against real repositories.
+
+mod common;
+
+use common::Repo;
+use std::fs;
+
+#[test]
+fn snapshots_within_the_same_second_are_all_kept() {
+    let repo = Repo::new();
+    let main = repo.worktree("main");
+
+    for contents in ["first\n", "second\n", "third\n"] {
+        fs::write(main.join("README.md"), contents).unwrap();
+        repo.wt_main(&["snapshot"]).success();
+    }
+
+    let snapshots = repo.git(
+        &main,
+        &[
+            "for-each-ref",
+            "--format=%(refname)",
+            "refs/wt-snapshots/main/",
+        ],
+    );
+    assert_eq!(snapshots.lines().count(), 3, "{snapshots}");
+
+    fs::write(main.join("README.md"), "Initial commit\n").unwrap();
+    repo.wt_main(&["snapshot", "--restore"]).success();
+    assert_eq!(
+        fs::read_to_string(main.join("README.md")).unwrap(),
+        "third\n"
+    );
+}
@@ -0,0 +1,41 @@
+//! `git-wt switch` against real repositories.
+
+mod common;
+
+use common::Repo;
+
+#[test]
+fn switches_by_exact_branch_name() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature/api"]).success();
+    repo.wt_main(&["add", "feature/api-v2"]).success();
+
+    let run = repo.wt_main(&["switch", "feature/api"]).success();
+    assert_eq!(run.cd(), Some(repo.worktree("feature/api")));
+}
+
+#[test]
+fn switches_without_the_subcommand() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature/api"]).success();
+
+    let run = repo.wt_main(&["feature/api"]).success();
+    assert_eq!(run.cd(), Some(repo.worktree("feature/api")));
+}
+
+#[test]
+fn switches_by_unique_fuzzy_match() {
+    let repo = Repo::new();
+    repo.wt_main(&["add", "feature/login"]).success();
+
+    let run = repo.wt_main(&["switch", "login"]).success();
+    assert_eq!(run.cd(), Some(repo.worktree("feature/login")));
+}
+
+#[test]
+fn fails_for_an_unknown_branch_without_prompting() {
+    let repo = Repo::new();
+
+    let run = repo.wt_main(&["switch", "does-not-exist"]).failure();
+    assert_eq!(run.cd(), None);
+}